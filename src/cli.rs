@@ -1,12 +1,26 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-use clap::{Parser, ValueEnum};
+use clap::{Parser, Subcommand, ValueEnum};
+use ethers::types::{Address, H256};
+use eyre::{Context, ContextCompat};
 
+pub mod batch_size_addition;
+pub mod forge_env;
 pub mod private_key;
+pub mod rpc_header;
 
+pub use batch_size_addition::BatchSizeAddition;
+pub use forge_env::ForgeEnv;
 pub use private_key::PrivateKey;
 use reqwest::Url;
 
+use crate::gas_oracle::GasOracle;
+use crate::report::costs::GasToken;
+use crate::types::{GroupId, TreeDepth};
+use crate::utils::parse_leaf_value;
+pub use rpc_header::RpcHeader;
+
 #[derive(Debug, Clone, ValueEnum, PartialEq)]
 #[clap(rename_all = "kebab-case")]
 pub enum DeploymentType {
@@ -20,34 +34,447 @@ pub enum DeploymentType {
     IdentityManager,
 }
 
+/// Which verifiers to deploy, independently of the target
+#[derive(Debug, Clone, ValueEnum, PartialEq)]
+#[clap(rename_all = "kebab-case")]
+pub enum VerifierMode {
+    Insertion,
+    Deletion,
+    Both,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+#[clap(rename_all = "kebab-case")]
+pub enum Command {
+    /// Disable every group route on the router of an existing deployment
+    Decommission(DecommissionArgs),
+    /// Print the group routes of the router as they are on-chain
+    RouterRoutes(RouterRoutesArgs),
+    /// Compare the lookup table entries of the report with the verifiers
+    /// associated on-chain
+    CheckLookupTables,
+    /// Deploy the same config to multiple chains concurrently
+    MultiChain(MultiChainArgs),
+    /// Send transactions previously dumped with --dump-tx-for
+    Broadcast(BroadcastArgs),
+    /// Create a report for contracts deployed outside of the deployer
+    Import(ImportArgs),
+    /// Reconstruct the config and addresses of an existing deployment from
+    /// its router on-chain, for `import`
+    Discover(DiscoverArgs),
+    /// Rewrite the report of an existing deployment from its own contents,
+    /// without deploying anything
+    Reassemble,
+    /// Verify contracts listed in an addresses file, without a report
+    VerifyAddresses(VerifyAddressesArgs),
+    /// Verify every contract in the report of an existing deployment
+    VerifyAll(VerifyAllArgs),
+    /// Pause the router and identity managers of an existing deployment
+    Pause,
+    /// Unpause the router and identity managers of an existing deployment
+    Unpause,
+    /// Print the step graph of the deployment and which steps would run
+    Plan(PlanArgs),
+    /// Print the config as the deployer sees it, with all defaults applied
+    DumpConfig(DumpConfigArgs),
+    /// Print the initial root of a tree filled with a given leaf
+    ComputeRoot(ComputeRootArgs),
+    /// Generate the verifier contracts of the config without deploying them
+    GenerateVerifiers(GenerateVerifiersArgs),
+    /// Create a config by answering prompts for its groups
+    CreateConfig(CreateConfigArgs),
+}
+
+#[derive(Debug, Clone, Parser)]
+#[clap(rename_all = "kebab-case")]
+pub struct GenerateVerifiersArgs {
+    /// Directory the verifier contracts are written to
+    #[clap(long, default_value = "verifiers")]
+    pub out_dir: PathBuf,
+}
+
+#[derive(Debug, Clone, Parser)]
+#[clap(rename_all = "kebab-case")]
+pub struct CreateConfigArgs {
+    /// YAML list of answers to the prompts, for unattended config creation
+    ///
+    /// Selections are answered with the displayed option, `null` skips a
+    /// prompt
+    #[clap(long)]
+    pub answers_file: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Parser)]
+#[clap(rename_all = "kebab-case")]
+pub struct ComputeRootArgs {
+    #[clap(long)]
+    pub tree_depth: TreeDepth,
+
+    /// Value of every leaf, like `misc.initial_leaf_value` - zero by default
+    ///
+    /// Hex, decimal or a preset name like `semaphore_empty`
+    #[clap(long, value_parser = parse_leaf)]
+    pub leaf: Option<H256>,
+}
+
+fn parse_leaf(value: &str) -> Result<H256, String> {
+    parse_leaf_value(value).map_err(|err| err.to_string())
+}
+
+/// Output format of the `dump-config` command
+#[derive(Debug, Clone, ValueEnum, PartialEq)]
+#[clap(rename_all = "kebab-case")]
+pub enum ConfigFormat {
+    Yaml,
+    Json,
+}
+
+#[derive(Debug, Clone, Parser)]
+#[clap(rename_all = "kebab-case")]
+pub struct DumpConfigArgs {
+    #[clap(long, default_value = "yaml")]
+    pub format: ConfigFormat,
+}
+
+/// Output format of the `plan` command
+#[derive(Debug, Clone, ValueEnum, PartialEq)]
+#[clap(rename_all = "kebab-case")]
+pub enum PlanFormat {
+    Dot,
+    Json,
+}
+
+#[derive(Debug, Clone, Parser)]
+#[clap(rename_all = "kebab-case")]
+pub struct RouterRoutesArgs {
+    /// Address of the router proxy - defaults to the one in the report
+    #[clap(long)]
+    pub router: Option<Address>,
+}
+
+#[derive(Debug, Clone, Parser)]
+#[clap(rename_all = "kebab-case")]
+pub struct DecommissionArgs {
+    /// Propose this address (e.g. a burn address) as the owner of the router
+    /// and identity managers after disabling the groups
+    ///
+    /// The transfers are two step, this address still has to accept the
+    /// ownership of every contract
+    #[clap(long)]
+    pub transfer_ownership_to: Option<Address>,
+}
+
+#[derive(Debug, Clone, Parser)]
+#[clap(rename_all = "kebab-case")]
+pub struct MultiChainArgs {
+    /// RPC url of a chain to deploy to - can be repeated
+    ///
+    /// Each chain gets its own report, named `report.<chain_id>.yml`
+    #[clap(long = "chain-rpc-url", required = true)]
+    pub rpc_urls: Vec<Url>,
+
+    /// How many chains to deploy to at the same time
+    #[clap(long, default_value = "2")]
+    pub parallelism: usize,
+
+    /// Don't fail when contracts end up at different addresses on different
+    /// chains
+    #[clap(long)]
+    pub allow_divergent_addresses: bool,
+}
+
 #[derive(Debug, Clone, Parser)]
 #[clap(rename_all = "kebab-case")]
+pub struct BroadcastArgs {
+    /// File with one signed raw transaction per line
+    pub file: PathBuf,
+}
+
+#[derive(Debug, Clone, Parser)]
+#[clap(rename_all = "kebab-case")]
+pub struct ImportArgs {
+    /// YAML (or JSON) file with the addresses of the deployed contracts
+    pub file: PathBuf,
+}
+
+#[derive(Debug, Clone, Parser)]
+#[clap(rename_all = "kebab-case")]
+pub struct DiscoverArgs {
+    /// Address of the router of the existing deployment
+    pub router: Address,
+
+    /// Where the reconstructed config is written
+    #[clap(long, default_value = "discovered_config.yml")]
+    pub config_out: PathBuf,
+
+    /// Where the discovered addresses are written, in the format of `import`
+    #[clap(long, default_value = "discovered_addresses.yml")]
+    pub addresses_out: PathBuf,
+
+    /// Block the events of the lookup tables are searched from
+    ///
+    /// Some RPC endpoints limit the range of blocks a log query may span, so
+    /// passing the deployment block helps there.
+    #[clap(long, default_value = "0")]
+    pub from_block: u64,
+}
+
+#[derive(Debug, Clone, Parser)]
+#[clap(rename_all = "kebab-case")]
+pub struct VerifyAddressesArgs {
+    /// YAML (or JSON) file mapping contract specs to addresses
+    ///
+    /// Contract specs are either a name or `path/to/Contract.sol:Name`
+    pub file: PathBuf,
+
+    /// Id of the chain the contracts are deployed on
+    #[clap(long)]
+    pub chain_id: u64,
+}
+
+#[derive(Debug, Clone, Parser)]
+#[clap(rename_all = "kebab-case")]
+pub struct VerifyAllArgs {
+    /// How many contracts to verify at the same time
+    #[clap(long, default_value = "4")]
+    pub parallelism: usize,
+
+    /// How many times to retry a failed verification, with exponential
+    /// backoff
+    #[clap(long, default_value = "3")]
+    pub retries: u32,
+}
+
+#[derive(Debug, Clone, Parser)]
+#[clap(rename_all = "kebab-case")]
+pub struct PlanArgs {
+    #[clap(long, default_value = "dot")]
+    pub format: PlanFormat,
+
+    /// Write the graph to this file instead of stdout
+    #[clap(short, long)]
+    pub output: Option<PathBuf>,
+}
+
+/// The config, deployment name, private key and RPC url are only required
+/// without a subcommand - the subcommands which need them check for them,
+/// see e.g. [`Args::config_path`]
+#[derive(Debug, Clone, Parser)]
+#[clap(rename_all = "kebab-case", subcommand_negates_reqs = true)]
 pub struct Args {
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+
     #[clap(short, long, env, default_value = "full")]
     pub target: DeploymentType,
 
+    /// Restrict which verifiers are deployed
+    ///
+    /// The verifiers of the other mode are taken from the existing report
+    #[clap(long, env, default_value = "both")]
+    pub verifier_mode: VerifierMode,
+
+    /// Skip everything related to deletions, for contract versions which
+    /// predate them
+    ///
+    /// No deletion verifiers or delete lookup tables are deployed, and the
+    /// identity managers are kept at V1
+    #[clap(long, env)]
+    pub no_deletion: bool,
+
+    /// Only deploy to this chain id - can be repeated
+    ///
+    /// Any other chain is refused before anything is sent, on top of
+    /// `misc.allowed_chain_ids`. No restriction when unset.
+    #[clap(long = "allow-chain", env = "ALLOW_CHAIN", value_delimiter = ',')]
+    pub allow_chains: Vec<u64>,
+
+    /// Fail instead of warning when a group has no batch sizes for a mode
+    /// whose verifiers are deployed
+    #[clap(long, env)]
+    pub strict_batch_sizes: bool,
+
+    /// Before deploying, warn about contracts of the report the deployer
+    /// doesn't own
+    ///
+    /// Owner-only calls on those (e.g. `updateVerifier`) would revert.
+    #[clap(long, env)]
+    pub check_owners: bool,
+
+    /// Like --check-owners, but fail instead of warning
+    #[clap(long, env)]
+    pub strict_owners: bool,
+
     /// Path to the deployment configuration file
-    #[clap(short, long, env)]
-    pub config: PathBuf,
+    #[clap(short, long, env, required = true)]
+    pub config: Option<PathBuf>,
 
     /// The name of the deployment
     ///
     /// Should be something meaningful like 'prod-2023-04-18'
-    #[clap(short, long, env)]
-    pub deployment_name: String,
+    #[clap(short, long, env, required = true)]
+    pub deployment_name: Option<String>,
 
     /// Private key to use for the deployment
-    #[clap(short, long, env)]
-    pub private_key: PrivateKey,
+    #[clap(short, long, env, required = true)]
+    pub private_key: Option<PrivateKey>,
+
+    /// Abort unless the private key derives this address
+    #[clap(long, env)]
+    pub expected_deployer: Option<Address>,
 
     /// The RPC Url to use for the deployment
-    #[clap(short, long, env)]
-    pub rpc_url: Url,
+    #[clap(short, long, env, required = true)]
+    pub rpc_url: Option<Url>,
+
+    /// Extra header for RPC requests, formatted as 'Name: value'
+    ///
+    /// Can be repeated. Only supported for HTTP RPC urls, and also passed to
+    /// forge. The values are redacted in logs.
+    #[clap(long = "rpc-header")]
+    pub rpc_headers: Vec<RpcHeader>,
+
+    /// Environment variable set on every forge invocation, as 'KEY=VALUE'
+    ///
+    /// Can be repeated, e.g. to select a foundry profile or a custom solc.
+    #[clap(long = "forge-env")]
+    pub forge_env: Vec<ForgeEnv>,
+
+    /// Extra remapping passed to forge, e.g. '@openzeppelin/=lib/oz/'
+    ///
+    /// Can be repeated. Applied to creating, inspecting and verifying
+    /// contracts, on top of the contracts repo's own remappings.
+    #[clap(long = "remappings")]
+    pub remappings: Vec<String>,
+
+    /// File with extra remappings for forge, in the format of remappings.txt
+    #[clap(long, env)]
+    pub remappings_file: Option<PathBuf>,
+
+    /// URL of an external gas oracle for the EIP-1559 fees of transactions
+    ///
+    /// Falls back to the node's fee estimation if the oracle fails
+    #[clap(long, env)]
+    pub gas_oracle_url: Option<Url>,
+
+    /// Extra header for gas oracle requests, formatted as 'Name: value'
+    #[clap(long = "gas-oracle-header")]
+    pub gas_oracle_headers: Vec<RpcHeader>,
+
+    /// Dot separated path of the max fee per gas in the oracle response
+    #[clap(long, env, default_value = "maxFeePerGas")]
+    pub gas_oracle_max_fee_path: String,
+
+    /// Dot separated path of the max priority fee per gas in the oracle
+    /// response
+    #[clap(long, env, default_value = "maxPriorityFeePerGas")]
+    pub gas_oracle_priority_fee_path: String,
+
+    /// Decimals of the fees in the oracle response, 9 for gwei
+    #[clap(long, env, default_value = "9")]
+    pub gas_oracle_decimals: u32,
+
+    /// Seconds a gas oracle request may take before falling back to the
+    /// node's fee estimation
+    #[clap(long, env, default_value = "5")]
+    pub gas_oracle_timeout: u64,
+
+    /// Send legacy transactions instead of EIP-1559 ones, for chains which
+    /// reject the latter
+    #[clap(long, env)]
+    pub legacy: bool,
+
+    /// How often forge retries a failed verification request
+    #[clap(long, env, default_value = "5")]
+    pub verify_retries: u32,
+
+    /// Seconds forge waits between retries of verification requests
+    #[clap(long, env, default_value = "5")]
+    pub verify_delay: u32,
+
+    /// Seconds a single contract verification may take before forge is
+    /// killed
+    #[clap(long, env, default_value = "600")]
+    pub verify_timeout: u64,
+
+    /// Seconds to wait for a transaction to be mined and confirmed
+    #[clap(long, env, default_value = "300")]
+    pub tx_timeout: u64,
+
+    /// Gas price in wei of every contract creation, for legacy transactions
+    #[clap(long, env, conflicts_with = "create_max_fee_per_gas")]
+    pub create_gas_price: Option<u128>,
+
+    /// Max fee per gas in wei of every contract creation
+    #[clap(long, env)]
+    pub create_max_fee_per_gas: Option<u128>,
+
+    /// Max priority fee per gas in wei of every contract creation
+    #[clap(long, env)]
+    pub create_max_priority_fee_per_gas: Option<u128>,
+
+    /// Gas limit of every contract creation, instead of forge's estimate
+    #[clap(long, env)]
+    pub create_gas_limit: Option<u64>,
+
+    /// Nonce of the first transaction, instead of querying it from the chain
+    ///
+    /// Useful for producing transactions for offline signing. If the chain is
+    /// reachable the value is checked against it and a mismatch is logged.
+    #[clap(long, env)]
+    pub start_nonce: Option<u64>,
+
+    /// Chain id to sign for, with --start-nonce
+    ///
+    /// Together they make the deployer skip querying the chain id and nonce,
+    /// e.g. on an air-gapped machine.
+    #[clap(long, env, requires = "start_nonce")]
+    pub chain_id: Option<u64>,
+
+    /// CREATE2 factory forge deploys deterministic contracts through, instead
+    /// of its default deployer
+    ///
+    /// Must have code on the target chain. Recorded in the report and reused
+    /// when resuming.
+    #[clap(long, env)]
+    pub create2_factory: Option<Address>,
+
+    /// Create the contracts without an owner deterministically, with CREATE2
+    /// salts derived from this one
+    ///
+    /// Given identical bytecode and constructor args the contracts then get
+    /// the same addresses on every chain. Owned contracts are created
+    /// normally, as they'd be owned by the CREATE2 factory.
+    #[clap(long, env)]
+    pub create2_salt: Option<H256>,
+
+    /// Don't build the contracts once before the deployment
+    ///
+    /// Each create and ABI inspection then checks (and if needed refreshes)
+    /// the build on its own.
+    #[clap(long, env)]
+    pub skip_build: bool,
+
+    /// Number of confirmations an implementation deployment needs before its
+    /// proxy is deployed or upgraded to it
+    ///
+    /// Defaults to 3 on mainnet and 1 elsewhere
+    #[clap(long, env)]
+    pub impl_confirmations: Option<u64>,
 
     /// The etherscan API key to use
-    #[clap(short, long, env)]
+    ///
+    /// Takes precedence over --etherscan-api-key-file, which in turn takes
+    /// precedence over the ETHERSCAN_API_KEY env var
+    #[clap(short, long)]
     pub etherscan_api_key: Option<String>,
 
+    /// Path to a file containing the etherscan API key
+    ///
+    /// Keeps the key out of the process arguments and shell history
+    #[clap(long, env)]
+    pub etherscan_api_key_file: Option<PathBuf>,
+
     /// The verifier to use for the deployment - blockscout, etherscan
     #[clap(long, env)]
     pub verifier: Option<String>,
@@ -58,7 +485,310 @@ pub struct Args {
     #[clap(long, env)]
     pub verifier_url: Option<String>,
 
+    /// Symbol of the chain's native token, used in the cost summary
+    #[clap(long, env, default_value = "ETH")]
+    pub gas_token_symbol: String,
+
+    /// Decimals of the chain's native token, used in the cost summary
+    #[clap(long, env, default_value = "18")]
+    pub gas_token_decimals: u32,
+
+    /// Answer yes to all confirmation prompts, for automation
+    ///
+    /// Required when decommissioning a deployment on a protected chain
+    #[clap(short, long, global = true)]
+    pub yes: bool,
+
+    /// Only log warnings and errors, regardless of RUST_LOG
+    #[clap(short, long, conflicts_with = "verbose")]
+    pub quiet: bool,
+
+    /// Log debug output, regardless of RUST_LOG
+    #[clap(short, long)]
+    pub verbose: bool,
+
+    /// Sign the transactions of this step without sending them, and write
+    /// them to `<step>.txs` in the deployment directory
+    ///
+    /// The deployment stops after the step, without recording it in the
+    /// report. forge broadcasts contract creations itself, so the step fails
+    /// if it would create a contract that isn't in the report yet.
+    #[clap(
+        long,
+        env,
+        value_parser = [
+            "insertion_verifiers",
+            "deletion_verifiers",
+            "lookup_tables",
+            "semaphore_verifier",
+            "identity_managers",
+            "world_id_router",
+            "timelock",
+        ],
+    )]
+    pub dump_tx_for: Option<String>,
+
+    /// Gas limit of every dumped transaction
+    #[clap(long, env)]
+    pub dump_gas_limit: Option<u64>,
+
+    /// Max fee per gas in wei of every dumped transaction
+    #[clap(long, env)]
+    pub dump_max_fee_per_gas: Option<u128>,
+
+    /// Max priority fee per gas in wei of every dumped transaction
+    #[clap(long, env)]
+    pub dump_max_priority_fee_per_gas: Option<u128>,
+
+    /// Gas price in wei of every dumped transaction, with --legacy
+    #[clap(long, env)]
+    pub dump_gas_price: Option<u128>,
+
+    /// Bypass this step and take its outputs from the existing report - can
+    /// be repeated
+    ///
+    /// E.g. for a semaphore verifier shared with another deployment. Fails
+    /// up front if a step which runs depends on a skipped step without
+    /// outputs in the report.
+    #[clap(
+        long = "skip-step",
+        env = "SKIP_STEP",
+        value_delimiter = ',',
+        value_parser = [
+            "insertion_verifiers",
+            "deletion_verifiers",
+            "lookup_tables",
+            "semaphore_verifier",
+            "identity_managers",
+            "world_id_router",
+            "timelock",
+        ],
+    )]
+    pub skip_steps: Vec<String>,
+
+    /// Record the contract address of the creation receipt instead of the
+    /// one reported by forge, for chains with custom CREATE semantics
+    #[clap(long, env)]
+    pub use_receipt_address: bool,
+
+    /// Seconds the MTB key setup of a single verifier may take before it's
+    /// killed, unlimited by default
+    #[clap(long, env)]
+    pub mtb_setup_timeout: Option<u64>,
+
+    /// Milliseconds to wait before sending each transaction, including the
+    /// ones of `forge create`, for rate-limited RPC endpoints
+    #[clap(long, env, default_value = "0")]
+    pub tx_delay_ms: u64,
+
+    /// How many times a failed transaction or `forge create` is retried
+    ///
+    /// Unconfirmed transactions are resent with bumped fees. Nothing is
+    /// retried once its nonce has been used, e.g. after a revert.
+    #[clap(long, env, default_value = "3")]
+    pub max_retries_per_tx: u32,
+
+    /// Only deploy the verifier of this new batch size, as
+    /// 'GROUP:MODE:SIZE', and add it to the group's lookup table
+    ///
+    /// All other steps are skipped. The batch size has to be in the config
+    /// already.
+    #[clap(long, env)]
+    pub add_batch_size: Option<BatchSizeAddition>,
+
+    /// Adopt this existing router proxy into the report instead of
+    /// deploying a new one, e.g. to recover from a lost report
+    ///
+    /// Only used when the report has no router. The router must already be
+    /// initialized.
+    #[clap(long, env)]
+    pub adopt_router: Option<Address>,
+
+    /// The group whose identity manager the router is initialized with
+    ///
+    /// The router always assigns id 0 to it, so group 0 must not be in the
+    /// config unless this is 0. Only used when the router is deployed.
+    #[clap(long, env, default_value = "0")]
+    pub bootstrap_group: GroupId,
+
+    /// Print the decoded identity manager initializer arguments before
+    /// they're sent
+    #[clap(long)]
+    pub print_init_calldata: bool,
+
+    /// Fail on fields of the existing report which the deployer doesn't
+    /// know, e.g. typos in a hand-edited report
+    #[clap(long)]
+    pub strict_report: bool,
+
+    /// Also write a `group-{id}.yml` report for every group, next to the
+    /// combined report
+    #[clap(long)]
+    pub group_reports: bool,
+
+    /// Don't write the report when the deployment fails
+    ///
+    /// By default the outputs of the steps which ran are written to the
+    /// report on failure, keeping the previous report's deployments for the
+    /// rest.
+    #[clap(long, env)]
+    pub no_report_on_error: bool,
+
+    /// Name the report after the chain id, set for multi-chain deployments
+    #[clap(skip)]
+    pub report_per_chain: bool,
+
+    /// List the verifiers the config expands to and which of them are
+    /// already cached, without generating or deploying anything
+    #[clap(long)]
+    pub list_verifiers: bool,
+
+    /// Estimate the duration and disk usage of the MTB setups the config
+    /// still needs, without generating or deploying anything
+    #[clap(long)]
+    pub estimate_mtb: bool,
+
     /// Cache directory
     #[clap(long, env, default_value = ".cache")]
     pub cache_dir: PathBuf,
+
+    /// Reuse the cached keys & verifier contracts of another contracts
+    /// revision (or a prefix of it)
+    ///
+    /// By default the cache is namespaced by the MTB version and the
+    /// world-id-contracts revision. `legacy` selects the unversioned cache of
+    /// older deployer versions.
+    #[clap(long, env)]
+    pub reuse_cache_from: Option<String>,
+}
+
+impl Args {
+    pub fn config_path(&self) -> eyre::Result<&Path> {
+        self.config
+            .as_deref()
+            .context("--config (or CONFIG) is required for this command")
+    }
+
+    pub fn deployment_name(&self) -> eyre::Result<&str> {
+        self.deployment_name.as_deref().context(
+            "--deployment-name (or DEPLOYMENT_NAME) is required for this command",
+        )
+    }
+
+    pub fn private_key(&self) -> eyre::Result<&PrivateKey> {
+        self.private_key.as_ref().context(
+            "--private-key (or PRIVATE_KEY) is required for this command",
+        )
+    }
+
+    pub fn rpc_url(&self) -> eyre::Result<&Url> {
+        self.rpc_url
+            .as_ref()
+            .context("--rpc-url (or RPC_URL) is required for this command")
+    }
+
+    pub fn gas_token(&self) -> GasToken {
+        GasToken {
+            symbol: self.gas_token_symbol.clone(),
+            decimals: self.gas_token_decimals,
+        }
+    }
+
+    pub fn mtb_setup_timeout(&self) -> Option<Duration> {
+        self.mtb_setup_timeout.map(Duration::from_secs)
+    }
+
+    pub fn verify_timeout(&self) -> Duration {
+        Duration::from_secs(self.verify_timeout)
+    }
+
+    pub fn tx_timeout(&self) -> Duration {
+        Duration::from_secs(self.tx_timeout)
+    }
+
+    /// The delay before each transaction, if throttling
+    pub fn tx_delay(&self) -> Option<Duration> {
+        (self.tx_delay_ms > 0).then(|| Duration::from_millis(self.tx_delay_ms))
+    }
+
+    pub fn gas_oracle(&self) -> Option<GasOracle> {
+        Some(GasOracle {
+            url: self.gas_oracle_url.clone()?,
+            headers: self.gas_oracle_headers.clone(),
+            max_fee_path: self.gas_oracle_max_fee_path.clone(),
+            priority_fee_path: self.gas_oracle_priority_fee_path.clone(),
+            decimals: self.gas_oracle_decimals,
+            timeout: Duration::from_secs(self.gas_oracle_timeout),
+        })
+    }
+
+    /// Whether `step` is bypassed with --skip-step
+    pub fn skips_step(&self, step: &str) -> bool {
+        self.skip_steps.iter().any(|skip_step| skip_step == step)
+    }
+
+    /// All the extra remappings, from the flags and the remappings file
+    pub async fn resolve_remappings(&self) -> eyre::Result<Vec<String>> {
+        let mut remappings = self.remappings.clone();
+
+        if let Some(path) = &self.remappings_file {
+            let content = tokio::fs::read_to_string(path)
+                .await
+                .with_context(|| format!("Reading {}", path.display()))?;
+
+            remappings.extend(
+                content
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty())
+                    .map(ToString::to_string),
+            );
+        }
+
+        Ok(remappings)
+    }
+
+    /// Resolves the etherscan API key from the flag, the key file or the env
+    pub async fn resolve_etherscan_api_key(
+        &self,
+    ) -> eyre::Result<Option<String>> {
+        if let Some(etherscan_api_key) = &self.etherscan_api_key {
+            return Ok(Some(etherscan_api_key.clone()));
+        }
+
+        if let Some(path) = &self.etherscan_api_key_file {
+            let content = tokio::fs::read_to_string(path)
+                .await
+                .with_context(|| format!("Reading {}", path.display()))?;
+
+            return Ok(Some(content.trim().to_string()));
+        }
+
+        Ok(std::env::var("ETHERSCAN_API_KEY").ok())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compute_root_without_deployment_args() {
+        let args = Args::try_parse_from([
+            "deployer",
+            "compute-root",
+            "--tree-depth",
+            "16",
+        ])
+        .unwrap();
+
+        assert!(matches!(args.command, Some(Command::ComputeRoot(_))));
+    }
+
+    #[test]
+    fn create_config_without_deployment_args() {
+        let args = Args::try_parse_from(["deployer", "create-config"]).unwrap();
+
+        assert!(matches!(args.command, Some(Command::CreateConfig(_))));
+    }
 }
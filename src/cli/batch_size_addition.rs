@@ -0,0 +1,56 @@
+use std::str::FromStr;
+
+use crate::deployment::mtb_utils::ProverMode;
+use crate::types::{BatchSize, GroupId};
+
+/// A single verifier to add to a group's lookup table
+///
+/// Formatted as `GROUP:MODE:SIZE`, e.g. `0:insertion:100`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchSizeAddition {
+    pub group_id: GroupId,
+    pub mode: ProverMode,
+    pub batch_size: BatchSize,
+}
+
+impl FromStr for BatchSizeAddition {
+    type Err = eyre::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(':');
+
+        let (Some(group_id), Some(mode), Some(batch_size), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            eyre::bail!("Expected a batch size like 'GROUP:MODE:SIZE'");
+        };
+
+        Ok(Self {
+            group_id: GroupId(group_id.parse()?),
+            mode: mode.parse()?,
+            batch_size: BatchSize(batch_size.parse()?),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_batch_size_addition() {
+        let addition: BatchSizeAddition = "1:deletion:10".parse().unwrap();
+
+        assert_eq!(
+            addition,
+            BatchSizeAddition {
+                group_id: GroupId(1),
+                mode: ProverMode::Deletion,
+                batch_size: BatchSize(10),
+            }
+        );
+
+        assert!("1:deletion".parse::<BatchSizeAddition>().is_err());
+        assert!("1:removal:10".parse::<BatchSizeAddition>().is_err());
+    }
+}
@@ -0,0 +1,29 @@
+use std::str::FromStr;
+
+/// An environment variable set on every forge invocation
+///
+/// Formatted as `KEY=VALUE`, e.g. `FOUNDRY_PROFILE=deploy`.
+#[derive(Debug, Clone)]
+pub struct ForgeEnv {
+    pub key: String,
+    pub value: String,
+}
+
+impl FromStr for ForgeEnv {
+    type Err = eyre::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (key, value) = s.split_once('=').ok_or_else(|| {
+            eyre::eyre!("Expected a variable like 'KEY=VALUE'")
+        })?;
+
+        if key.is_empty() {
+            eyre::bail!("Missing variable name");
+        }
+
+        Ok(Self {
+            key: key.to_string(),
+            value: value.to_string(),
+        })
+    }
+}
@@ -0,0 +1,48 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// An extra HTTP header sent with every RPC request, e.g. for authentication
+///
+/// Formatted as `Name: value`. The value is redacted unless formatted with `{:#}`.
+#[derive(Clone)]
+pub struct RpcHeader {
+    pub name: String,
+    pub value: String,
+}
+
+impl FromStr for RpcHeader {
+    type Err = eyre::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, value) = s.split_once(':').ok_or_else(|| {
+            eyre::eyre!("Expected a header like 'Name: value'")
+        })?;
+
+        let name = name.trim();
+
+        if name.is_empty() {
+            eyre::bail!("Missing header name");
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            value: value.trim().to_string(),
+        })
+    }
+}
+
+impl fmt::Display for RpcHeader {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if f.alternate() {
+            write!(f, "{}: {}", self.name, self.value)
+        } else {
+            write!(f, "{}: ***", self.name)
+        }
+    }
+}
+
+impl fmt::Debug for RpcHeader {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "RpcHeader({self})")
+    }
+}
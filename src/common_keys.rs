@@ -1,7 +1,98 @@
+use std::fmt::Debug;
+use std::str::FromStr;
 use std::sync::Arc;
 
-use ethers::prelude::*;
+use async_trait::async_trait;
+use ethers::prelude::{
+    Http, JsonRpcClient, LocalWallet, Provider, ProviderError,
+    SignerMiddleware, Ws,
+};
+use eyre::Context;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::Url;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::cli::RpcHeader;
+
+/// JSON-RPC transport picked based on the RPC url scheme
+#[derive(Debug, Clone)]
+pub enum RpcTransport {
+    Http(Http),
+    Ws(Ws),
+}
+
+impl RpcTransport {
+    pub async fn connect(
+        rpc_url: &Url,
+        headers: &[RpcHeader],
+    ) -> eyre::Result<Self> {
+        match rpc_url.scheme() {
+            "http" | "https" => {
+                let client = reqwest::Client::builder()
+                    .default_headers(header_map(headers)?)
+                    .build()?;
+
+                Ok(Self::Http(Http::new_with_client(rpc_url.clone(), client)))
+            }
+            "ws" | "wss" => {
+                if !headers.is_empty() {
+                    eyre::bail!(
+                        "RPC headers are only supported for HTTP RPC urls"
+                    );
+                }
+
+                Ok(Self::Ws(Ws::connect(rpc_url.as_str()).await?))
+            }
+            scheme => eyre::bail!("Unsupported RPC url scheme: {scheme}"),
+        }
+    }
+}
+
+pub fn header_map(headers: &[RpcHeader]) -> eyre::Result<HeaderMap> {
+    let mut header_map = HeaderMap::new();
+
+    for header in headers {
+        let name = HeaderName::from_str(&header.name)
+            .with_context(|| format!("Invalid header name {}", header.name))?;
+        let mut value =
+            HeaderValue::from_str(&header.value).with_context(|| {
+                format!("Invalid value for header {}", header.name)
+            })?;
+        value.set_sensitive(true);
+
+        header_map.append(name, value);
+    }
+
+    Ok(header_map)
+}
+
+#[async_trait]
+impl JsonRpcClient for RpcTransport {
+    type Error = ProviderError;
+
+    async fn request<T, R>(
+        &self,
+        method: &str,
+        params: T,
+    ) -> Result<R, Self::Error>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        match self {
+            Self::Http(http) => {
+                http.request(method, params).await.map_err(Into::into)
+            }
+            Self::Ws(ws) => {
+                ws.request(method, params).await.map_err(Into::into)
+            }
+        }
+    }
+}
 
 // TODO: Allow for different wallet kinds
 #[derive(Debug, Clone)]
-pub struct RpcSigner(pub Arc<SignerMiddleware<Provider<Http>, LocalWallet>>);
+pub struct RpcSigner(
+    pub Arc<SignerMiddleware<Provider<RpcTransport>, LocalWallet>>,
+);
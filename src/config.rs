@@ -1,9 +1,13 @@
 use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 
-use ethers::types::H256;
+use ethers::types::{Address, H256};
+use eyre::{Context, ContextCompat};
 use serde::{Deserialize, Serialize};
 
 use crate::deployment::mtb_utils::ProverMode;
+use crate::forge_utils::ContractSpec;
+use crate::serde_utils;
 use crate::types::{BatchSize, GroupId, TreeDepth};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -12,15 +16,273 @@ pub struct Config {
     pub misc: MiscConfig,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct MiscConfig {
-    #[serde(default)]
+    /// Hex, decimal or a preset name like `semaphore_empty`
+    #[serde(
+        default,
+        deserialize_with = "serde_utils::leaf_value::deserialize"
+    )]
     pub initial_leaf_value: H256,
+    /// Tree depth of groups which don't specify their own
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_tree_depth: Option<TreeDepth>,
+    /// Named arguments for the identity manager initializers
+    ///
+    /// Keyed by function name (e.g. `initialize`) and then by the ABI input
+    /// name. Values can refer to prior deployments by well-known names like
+    /// `insert_lookup_table` or `semaphore_verifier`.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub identity_manager_init_args: HashMap<String, HashMap<String, String>>,
+    /// Already deployed Pairing libraries keyed by chain id
+    ///
+    /// When set for the target chain the semaphore verifier is linked against
+    /// it instead of deploying a new Pairing library.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub pairing_library_addresses: HashMap<u64, Address>,
+    /// Already deployed semaphore verifiers keyed by chain id
+    ///
+    /// When set for the target chain nothing is deployed by the semaphore
+    /// verifier step and the identity managers use this verifier instead.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub semaphore_verifier_addresses: HashMap<u64, ExistingSemaphoreVerifier>,
+    /// Extra arguments passed verbatim to `mtb setup`
+    ///
+    /// E.g. a pinned powers-of-tau file. Part of the cached keys' filenames.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mtb_setup_args: Vec<String>,
+    /// The only `(tree_depth, batch_size)` pairs groups may use
+    ///
+    /// Guards against a mistyped batch size triggering a huge MTB run and
+    /// verifier deployment. No restriction when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allowed_verifiers: Option<Vec<AllowedVerifier>>,
+    /// The only chains the deployment may run against, no restriction when
+    /// unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allowed_chain_ids: Option<Vec<u64>>,
+    /// Chains on which the deployment asks for confirmation before running
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub confirm_chain_ids: Vec<u64>,
+    /// Deploy a timelock controller and hand the ownership of the router and
+    /// identity managers over to it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timelock: Option<TimelockConfig>,
+    /// Contracts deployed for each role, for forks which renamed them
+    #[serde(default, skip_serializing_if = "ContractNames::is_default")]
+    pub contract_names: ContractNames,
+    /// Read-only calls checked against the deployed contracts, failing the
+    /// deployment on a mismatch
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub assertions: Vec<AssertionConfig>,
+    /// Files with extra constructor args keyed by contract name
+    ///
+    /// Each file is a YAML list of values, passed after the args the
+    /// deployer provides itself.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub constructor_args_files: HashMap<String, PathBuf>,
+    /// Expected keccak hashes of the runtime code of key contracts
+    #[serde(default, skip_serializing_if = "CodeHashes::is_empty")]
+    pub code_hashes: CodeHashes,
+}
+
+/// A semaphore verifier deployed outside of the deployer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExistingSemaphoreVerifier {
+    pub verifier: Address,
+    /// The Pairing library the verifier is linked against
+    pub pairing: Address,
+}
+
+/// Pinned runtime code hashes - a create whose code hashes to anything else
+/// fails the deployment
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CodeHashes {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub router_impl: Option<H256>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub identity_manager_impl_v1: Option<H256>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub identity_manager_impl_v2: Option<H256>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub semaphore_verifier: Option<H256>,
+}
+
+impl CodeHashes {
+    fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// The contract spec of each role - either `Name` or
+/// `path/to/Contract.sol:Name`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ContractNames {
+    pub router: String,
+    pub router_impl: String,
+    pub identity_manager: String,
+    pub identity_manager_impl_v1: String,
+    pub identity_manager_impl_v2: String,
+    pub verifier_lookup_table: String,
+    pub semaphore_verifier: String,
+    pub pairing: String,
+}
+
+impl Default for ContractNames {
+    fn default() -> Self {
+        const SEMAPHORE_BASE: &str =
+            "./lib/semaphore/packages/contracts/contracts/base";
+
+        Self {
+            router: "WorldIDRouter".to_string(),
+            router_impl: "WorldIDRouterImplV1".to_string(),
+            identity_manager: "WorldIDIdentityManager".to_string(),
+            identity_manager_impl_v1: "WorldIDIdentityManagerImplV1"
+                .to_string(),
+            identity_manager_impl_v2: "WorldIDIdentityManagerImplV2"
+                .to_string(),
+            verifier_lookup_table: "VerifierLookupTable".to_string(),
+            semaphore_verifier: format!(
+                "{SEMAPHORE_BASE}/SemaphoreVerifier.sol:SemaphoreVerifier"
+            ),
+            pairing: format!("{SEMAPHORE_BASE}/Pairing.sol:Pairing"),
+        }
+    }
+}
+
+impl ContractNames {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    fn specs(&self) -> [(&'static str, &str); 8] {
+        [
+            ("router", &self.router),
+            ("router_impl", &self.router_impl),
+            ("identity_manager", &self.identity_manager),
+            ("identity_manager_impl_v1", &self.identity_manager_impl_v1),
+            ("identity_manager_impl_v2", &self.identity_manager_impl_v2),
+            ("verifier_lookup_table", &self.verifier_lookup_table),
+            ("semaphore_verifier", &self.semaphore_verifier),
+            ("pairing", &self.pairing),
+        ]
+    }
+
+    fn validate(&self) -> eyre::Result<()> {
+        for (role, spec) in self.specs() {
+            spec.parse::<ContractSpec>().map_err(|err| {
+                eyre::eyre!("Invalid misc.contract_names.{role}: {err}")
+            })?;
+        }
+
+        Ok(())
+    }
+
+    fn spec(spec: &str) -> ContractSpec {
+        spec.parse()
+            .expect("Contract names are validated when loading the config")
+    }
+
+    pub fn router(&self) -> ContractSpec {
+        Self::spec(&self.router)
+    }
+
+    pub fn router_impl(&self) -> ContractSpec {
+        Self::spec(&self.router_impl)
+    }
+
+    pub fn identity_manager(&self) -> ContractSpec {
+        Self::spec(&self.identity_manager)
+    }
+
+    pub fn identity_manager_impl_v1(&self) -> ContractSpec {
+        Self::spec(&self.identity_manager_impl_v1)
+    }
+
+    pub fn identity_manager_impl_v2(&self) -> ContractSpec {
+        Self::spec(&self.identity_manager_impl_v2)
+    }
+
+    pub fn verifier_lookup_table(&self) -> ContractSpec {
+        Self::spec(&self.verifier_lookup_table)
+    }
+
+    pub fn semaphore_verifier(&self) -> ContractSpec {
+        Self::spec(&self.semaphore_verifier)
+    }
+
+    pub fn pairing(&self) -> ContractSpec {
+        Self::spec(&self.pairing)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct GroupConfig {
+pub struct TimelockConfig {
+    /// Minimum delay of operations in seconds
+    pub min_delay: u64,
+    pub proposers: Vec<Address>,
+    pub executors: Vec<Address>,
+    /// Optional admin, the timelock administers itself when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub admin: Option<Address>,
+    /// Overrides the contract spec of the timelock controller
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub contract: Option<String>,
+}
+
+/// A view function of a deployed contract and the value it must return
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AssertionConfig {
+    pub contract: AssertedContract,
+    /// Group of the identity manager or lookup table, defaults to group 0
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<GroupId>,
+    pub function: String,
+    /// Parsed according to the ABI input types
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+    /// Parsed according to the ABI output type
+    pub expected: String,
+}
+
+impl AssertionConfig {
+    pub fn group_id(&self) -> GroupId {
+        self.group.unwrap_or(GroupId(0))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AssertedContract {
+    Router,
+    IdentityManager,
+    InsertLookupTable,
+    UpdateLookupTable,
+    DeleteLookupTable,
+    SemaphoreVerifier,
+}
+
+impl AssertedContract {
+    /// Whether the contract is deployed per group
+    pub fn is_per_group(&self) -> bool {
+        !matches!(self, Self::Router | Self::SemaphoreVerifier)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AllowedVerifier {
     pub tree_depth: TreeDepth,
+    pub batch_size: BatchSize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroupConfig {
+    /// Falls back to `misc.default_tree_depth` when omitted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tree_depth: Option<TreeDepth>,
     /// Which batch sizes are supported for insertion by this group
     #[serde(alias = "batch_sizes")] // For backwards compatibility
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
@@ -33,24 +295,327 @@ pub struct GroupConfig {
     pub initial_root: Option<H256>,
 }
 
+impl GroupConfig {
+    /// The effective tree depth of the group
+    ///
+    /// Always set for configs obtained through [`Config::load`], but might be
+    /// missing from e.g. the config embedded in an older report
+    pub fn tree_depth(&self) -> eyre::Result<TreeDepth> {
+        self.tree_depth.context(
+            "Missing tree depth - set tree_depth or misc.default_tree_depth",
+        )
+    }
+}
+
 impl Config {
+    pub async fn load(path: impl AsRef<Path>) -> eyre::Result<Self> {
+        let mut config: Self = serde_utils::read_deserialize(path).await?;
+
+        config.resolve_defaults();
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    /// Reads the files of `misc.constructor_args_files`
+    pub async fn load_constructor_args(
+        &self,
+    ) -> eyre::Result<HashMap<String, Vec<String>>> {
+        let mut constructor_args = HashMap::new();
+
+        for (contract_name, path) in &self.misc.constructor_args_files {
+            let args: Vec<String> = serde_utils::read_deserialize(path)
+                .await
+                .with_context(|| {
+                format!("Reading the constructor args of {contract_name}")
+            })?;
+
+            constructor_args.insert(contract_name.clone(), args);
+        }
+
+        Ok(constructor_args)
+    }
+
+    /// Applies the config-level defaults to every group
+    fn resolve_defaults(&mut self) {
+        let default_tree_depth = self.misc.default_tree_depth;
+
+        for group in self.groups.values_mut() {
+            if group.tree_depth.is_none() {
+                group.tree_depth = default_tree_depth;
+            }
+        }
+    }
+
+    pub fn validate(&self) -> eyre::Result<()> {
+        self.misc.contract_names.validate()?;
+
+        for assertion in &self.misc.assertions {
+            let group_id = assertion.group_id();
+
+            if assertion.contract.is_per_group()
+                && !self.groups.contains_key(&group_id)
+            {
+                eyre::bail!(
+                    "Assertion on {} refers to group {group_id}, which is not in the config",
+                    assertion.function
+                );
+            }
+        }
+
+        let mut group_ids: Vec<_> = self.groups.keys().copied().collect();
+        group_ids.sort();
+
+        for group_id in group_ids {
+            let group = &self.groups[&group_id];
+
+            let Some(tree_depth) = group.tree_depth else {
+                eyre::bail!(
+                    "Group {group_id} has no tree_depth and misc.default_tree_depth is not set"
+                );
+            };
+
+            let Some(allowed_verifiers) = self.misc.allowed_verifiers.as_ref()
+            else {
+                continue;
+            };
+
+            for (mode, batch_sizes) in [
+                (ProverMode::Insertion, &group.insertion_batch_sizes),
+                (ProverMode::Deletion, &group.deletion_batch_sizes),
+            ] {
+                for batch_size in batch_sizes.iter().copied() {
+                    let verifier = AllowedVerifier {
+                        tree_depth,
+                        batch_size,
+                    };
+
+                    if !allowed_verifiers.contains(&verifier) {
+                        eyre::bail!(
+                            "Group {group_id} {mode} batch size {batch_size} with tree depth {tree_depth} is not in misc.allowed_verifiers"
+                        );
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Groups without any batch size for `mode`, sorted by id
+    ///
+    /// Such a group still gets a lookup table for the mode, but no verifiers
+    /// are associated with it.
+    pub fn groups_without_batch_sizes(&self, mode: ProverMode) -> Vec<GroupId> {
+        let mut group_ids: Vec<_> = self
+            .groups
+            .iter()
+            .filter(|(_, group)| match mode {
+                ProverMode::Insertion => group.insertion_batch_sizes.is_empty(),
+                ProverMode::Deletion => group.deletion_batch_sizes.is_empty(),
+            })
+            .map(|(group_id, _)| *group_id)
+            .collect();
+        group_ids.sort();
+
+        group_ids
+    }
+
     pub fn unique_tree_depths_and_batch_sizes(
         &self,
         mode: ProverMode,
-    ) -> HashSet<(TreeDepth, BatchSize)> {
+    ) -> eyre::Result<HashSet<(TreeDepth, BatchSize)>> {
         let mut result = HashSet::new();
 
-        for group in self.groups.values() {
+        for (group_id, group) in &self.groups {
             let batch_sizes_for_mode = match mode {
                 ProverMode::Insertion => &group.insertion_batch_sizes,
                 ProverMode::Deletion => &group.deletion_batch_sizes,
             };
 
             for batch_size in batch_sizes_for_mode {
-                result.insert((group.tree_depth, *batch_size));
+                let tree_depth = group
+                    .tree_depth()
+                    .with_context(|| format!("Group {group_id}"))?;
+
+                result.insert((tree_depth, *batch_size));
             }
         }
 
-        result
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use super::*;
+
+    const DEFAULT_TREE_DEPTH: &str = indoc! { r#"
+        groups:
+          0:
+            insertion_batch_sizes:
+              - 100
+          1:
+            tree_depth: 20
+            insertion_batch_sizes:
+              - 10
+        misc:
+          default_tree_depth: 30
+    "# };
+
+    #[test]
+    fn assertion_on_unknown_group() {
+        let mut config: Config = serde_yaml::from_str(indoc! { r#"
+            groups:
+              0:
+                tree_depth: 30
+            misc:
+              assertions:
+                - contract: identity_manager
+                  group: 1
+                  function: getTreeDepth
+                  expected: "30"
+        "# })
+        .unwrap();
+
+        assert!(config.validate().is_err());
+
+        config.misc.assertions[0].group = None;
+        config.validate().unwrap();
+    }
+
+    #[test]
+    fn default_tree_depth() {
+        let mut config: Config =
+            serde_yaml::from_str(DEFAULT_TREE_DEPTH).unwrap();
+
+        config.resolve_defaults();
+        config.validate().unwrap();
+
+        assert_eq!(
+            config.groups[&GroupId(0)].tree_depth().unwrap(),
+            TreeDepth(30)
+        );
+        assert_eq!(
+            config.groups[&GroupId(1)].tree_depth().unwrap(),
+            TreeDepth(20)
+        );
+
+        let expected: HashSet<_> = [
+            (TreeDepth(30), BatchSize(100)),
+            (TreeDepth(20), BatchSize(10)),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(
+            config
+                .unique_tree_depths_and_batch_sizes(ProverMode::Insertion)
+                .unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn initial_leaf_value_forms() {
+        let leaf = |initial_leaf_value: &str| {
+            let config: Config = serde_yaml::from_str(&format!(
+                "groups: {{}}\nmisc:\n  initial_leaf_value: {initial_leaf_value}"
+            ))?;
+
+            Ok::<_, eyre::Report>(config.misc.initial_leaf_value)
+        };
+
+        assert_eq!(leaf("semaphore_empty").unwrap(), H256::zero());
+        assert_eq!(leaf("'0x10'").unwrap(), H256::from_low_u64_be(16));
+        assert_eq!(leaf("'16'").unwrap(), H256::from_low_u64_be(16));
+        assert_eq!(leaf("16").unwrap(), H256::from_low_u64_be(16));
+
+        assert!(leaf("semaphore_full").is_err());
+        // Above the SNARK scalar field
+        assert!(leaf(&format!("'0x{}'", "f".repeat(64))).is_err());
+    }
+
+    #[test]
+    fn missing_tree_depth() {
+        let mut config: Config = serde_yaml::from_str(indoc! { r#"
+            groups:
+              0:
+                insertion_batch_sizes:
+                  - 100
+            misc: {}
+        "# })
+        .unwrap();
+
+        config.resolve_defaults();
+
+        assert!(config.validate().is_err());
+
+        // E.g. the config of an older report, which isn't validated
+        assert!(config.groups[&GroupId(0)].tree_depth().is_err());
+        assert!(config
+            .unique_tree_depths_and_batch_sizes(ProverMode::Insertion)
+            .is_err());
+    }
+
+    #[test]
+    fn allowed_verifiers() {
+        let mut config: Config = serde_yaml::from_str(indoc! { r#"
+            groups:
+              0:
+                tree_depth: 30
+                insertion_batch_sizes:
+                  - 100
+                deletion_batch_sizes:
+                  - 10
+            misc:
+              allowed_verifiers:
+                - tree_depth: 30
+                  batch_size: 100
+                - tree_depth: 30
+                  batch_size: 10
+        "# })
+        .unwrap();
+
+        config.resolve_defaults();
+        config.validate().unwrap();
+
+        config
+            .groups
+            .get_mut(&GroupId(0))
+            .unwrap()
+            .insertion_batch_sizes
+            .push(BatchSize(1000));
+
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn groups_without_batch_sizes() {
+        let config: Config = serde_yaml::from_str(indoc! { r#"
+            groups:
+              0:
+                tree_depth: 30
+                insertion_batch_sizes:
+                  - 100
+                deletion_batch_sizes: []
+              1:
+                tree_depth: 30
+                deletion_batch_sizes:
+                  - 10
+            misc: {}
+        "# })
+        .unwrap();
+
+        assert_eq!(
+            config.groups_without_batch_sizes(ProverMode::Insertion),
+            vec![GroupId(1)]
+        );
+        assert_eq!(
+            config.groups_without_batch_sizes(ProverMode::Deletion),
+            vec![GroupId(0)]
+        );
     }
 }
@@ -1,60 +1,152 @@
 use std::path::PathBuf;
 use std::sync::atomic::AtomicU64;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 use ethers::prelude::SignerMiddleware;
 use ethers::providers::{Middleware, Provider};
 use ethers::signers::{Signer, Wallet};
-use eyre::ContextCompat;
+use eyre::{Context as _, ContextCompat};
+use tracing::{info, warn};
 
-use self::mtb_utils::ProverMode;
+use self::mtb_utils::{versioned_cache_dir, ProverMode};
 use self::steps::assemble_report::{self, REPORT_PATH};
 use self::steps::{
-    identity_manager, lookup_tables, semaphore_verifier, verifiers,
-    world_id_router,
+    identity_manager, lookup_tables, ownership, semaphore_verifier, timelock,
+    verifiers, world_id_router,
 };
 use crate::cli::{Args, DeploymentType};
-use crate::common_keys::RpcSigner;
+use crate::common_keys::{RpcSigner, RpcTransport};
 use crate::config::Config;
+use crate::ethers_utils::ensure_has_code;
 use crate::report::Report;
 use crate::serde_utils;
-
+use crate::types::GroupId;
+use crate::utils::{confirm, PROTECTED_CHAIN_IDS};
+
+pub mod add_batch_size;
+pub mod assertions;
+pub mod broadcast;
+pub mod check_lookup_tables;
+pub mod compute_root;
+pub mod decommission;
 pub mod deployment_context;
+pub mod discover;
+pub mod dump_config;
+pub mod generate_verifiers;
+pub mod import;
+pub mod initializer_args;
 pub mod mtb_utils;
+pub mod multi_chain;
+pub mod pause;
+pub mod plan;
+pub mod reassemble;
+pub mod router_routes;
 pub mod steps;
+pub mod verify_addresses;
+pub mod verify_all;
 
 pub const KEYS_DIR: &str = "keys";
 pub const VERIFIER_CONTRACTS_DIR: &str = "verifier_contracts";
+pub const VERIFIER_SOURCES_DIR: &str = "verifier_sources";
 
 pub use self::deployment_context::DeploymentContext;
 
-pub async fn run_deployment(cmd: Args) -> eyre::Result<()> {
-    let config: Config = serde_utils::read_deserialize(&cmd.config).await?;
+/// Whether a command sends transactions, which restricts the chains it may
+/// run against
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChainAccess {
+    Read,
+    Send,
+}
 
-    let deployment_dir = PathBuf::from(&cmd.deployment_name);
-    let cache_dir: PathBuf = deployment_dir.join(".cache");
+/// Loads the config & report and connects to the chain
+///
+/// With [`ChainAccess::Send`] the chain is checked against the allowed chains
+/// and confirmed if needed, see `check_chain`.
+pub async fn init_context(
+    cmd: Args,
+    access: ChainAccess,
+) -> eyre::Result<(Arc<DeploymentContext>, Arc<Config>)> {
+    let config = Config::load(cmd.config_path()?).await?;
+
+    let deployment_dir = PathBuf::from(cmd.deployment_name()?);
+    let cache_dir = versioned_cache_dir(
+        &deployment_dir.join(".cache"),
+        cmd.reuse_cache_from.as_deref(),
+    )
+    .await?;
 
     tokio::fs::create_dir_all(&cache_dir).await?;
 
-    let provider = Provider::try_from(cmd.rpc_url.as_str())?;
-    let chain_id = provider.get_chainid().await?;
-    let wallet = Wallet::from(cmd.private_key.key.clone())
-        .with_chain_id(chain_id.as_u64());
+    let transport =
+        RpcTransport::connect(cmd.rpc_url()?, &cmd.rpc_headers).await?;
+    let provider = Provider::new(transport);
+
+    let private_key = cmd.private_key()?.clone();
+    let rpc_url = cmd.rpc_url()?.clone();
 
+    let wallet = Wallet::from(private_key.key.clone());
     let wallet_address = wallet.address();
 
-    let signer = SignerMiddleware::new(provider, wallet);
+    if let Some(expected_deployer) = cmd.expected_deployer {
+        if expected_deployer != wallet_address {
+            eyre::bail!(
+                "Private key derives deployer {wallet_address:?}, expected {expected_deployer:?}"
+            );
+        }
+    }
+
+    let (chain_id, nonce) = match (cmd.start_nonce, cmd.chain_id) {
+        // Nothing is read from the chain, so this works offline
+        (Some(start_nonce), Some(chain_id)) => (chain_id, start_nonce),
+        (start_nonce, _) => {
+            // Both reads only depend on the private key, so they're issued
+            // together to save a round trip on high-latency endpoints
+            let (chain_id, chain_nonce) = tokio::join!(
+                provider.get_chainid(),
+                provider.get_transaction_count(wallet_address, None),
+            );
+
+            let nonce = if let Some(start_nonce) = start_nonce {
+                match chain_nonce {
+                    Ok(chain_nonce) if chain_nonce.as_u64() != start_nonce => {
+                        warn!("Start nonce {start_nonce} differs from the chain nonce {chain_nonce}");
+                    }
+                    Ok(_) => {}
+                    Err(err) => {
+                        warn!("Could not validate start nonce against the chain: {err}");
+                    }
+                }
+
+                start_nonce
+            } else {
+                chain_nonce?.as_u64()
+            };
+
+            (chain_id?.as_u64(), nonce)
+        }
+    };
 
-    let nonce = signer.get_transaction_count(wallet_address, None).await?;
+    let wallet = wallet.with_chain_id(chain_id);
+
+    let signer = SignerMiddleware::new(provider, wallet);
 
     // TODO: should eventually be replaced by some dyn Trait that can be used to sign transactions
     //       we might want to support multiple signers in the future
     let rpc_signer = Arc::new(RpcSigner(Arc::new(signer)));
 
-    let report_path = deployment_dir.join(REPORT_PATH);
+    let report_path = if cmd.report_per_chain {
+        deployment_dir.join(format!("report.{chain_id}.yml"))
+    } else {
+        deployment_dir.join(REPORT_PATH)
+    };
 
     let report = if report_path.exists() {
-        let report = serde_utils::read_deserialize(&report_path).await?;
+        let report = if cmd.strict_report {
+            serde_utils::read_deserialize_strict(&report_path).await?
+        } else {
+            serde_utils::read_deserialize(&report_path).await?
+        };
 
         let cache_path = report_path.with_extension("yml.cache");
         serde_utils::write_serialize(cache_path, &report).await?;
@@ -64,34 +156,426 @@ pub async fn run_deployment(cmd: Args) -> eyre::Result<()> {
         Report::default_with_config(&config)
     };
 
+    if let Some(report_deployer) = report.deployer {
+        if report_deployer != wallet_address {
+            eyre::bail!(
+                "Existing deployment was created by {report_deployer:?}, but the current deployer is {wallet_address:?}"
+            );
+        }
+    }
+
+    let create2_factory = match (cmd.create2_factory, report.create2_factory) {
+        (Some(factory), Some(report_factory)) if factory != report_factory => {
+            eyre::bail!(
+                "Existing deployment used the CREATE2 factory {report_factory:?}, got {factory:?}"
+            );
+        }
+        (factory, report_factory) => factory.or(report_factory),
+    };
+
+    if let Some(create2_factory) = create2_factory {
+        ensure_has_code(&rpc_signer, create2_factory)
+            .await
+            .context("Validating the CREATE2 factory")?;
+    }
+
+    let etherscan_api_key = cmd.resolve_etherscan_api_key().await?;
+    let remappings = cmd.resolve_remappings().await?;
+    let constructor_args = config.load_constructor_args().await?;
+
+    if let Some(tx_delay) = cmd.tx_delay() {
+        info!("Throttling: waiting {tx_delay:?} before every transaction");
+    }
+
+    let costs = report.costs.clone().unwrap_or_default();
+
     let context = DeploymentContext {
         deployment_dir,
         cache_dir,
-        nonce: AtomicU64::new(nonce.as_u64()),
+        report_path,
+        chain_id,
+        deployer: wallet_address,
+        nonce: Arc::new(AtomicU64::new(nonce)),
         report,
         cmd: cmd.clone(),
-        private_key: cmd.private_key,
-        rpc_url: cmd.rpc_url,
+        private_key,
+        rpc_url,
         rpc_signer,
-        etherscan_api_key: cmd.etherscan_api_key,
+        etherscan_api_key,
+        create2_factory,
+        remappings,
+        constructor_args,
+        costs: Mutex::new(costs),
+        cost_scope: Default::default(),
+        abi_cache: Default::default(),
     };
 
-    let context = Arc::new(context);
-    let config = Arc::new(config);
+    if access == ChainAccess::Send {
+        check_chain(&context, &config)?;
+    }
 
-    let insertion_verifiers = Some(
-        verifiers::deploy(
-            context.clone(),
-            config.clone(),
-            ProverMode::Insertion,
+    Ok((Arc::new(context), Arc::new(config)))
+}
+
+pub async fn run_deployment(cmd: Args) -> eyre::Result<()> {
+    if cmd.list_verifiers {
+        let config = Config::load(cmd.config_path()?).await?;
+        let cache_dir = versioned_cache_dir(
+            &PathBuf::from(cmd.deployment_name()?).join(".cache"),
+            cmd.reuse_cache_from.as_deref(),
+        )
+        .await?;
+
+        verifiers::list(&config, &cache_dir)?;
+
+        return Ok(());
+    }
+
+    if cmd.estimate_mtb {
+        let config = Config::load(cmd.config_path()?).await?;
+        let cache_dir = versioned_cache_dir(
+            &PathBuf::from(cmd.deployment_name()?).join(".cache"),
+            cmd.reuse_cache_from.as_deref(),
         )
-        .await?,
+        .await?;
+
+        return verifiers::estimate(&config, &cache_dir).await;
+    }
+
+    if let Some(addition) = cmd.add_batch_size.clone() {
+        return add_batch_size::run_add_batch_size(cmd, addition).await;
+    }
+
+    let (_chain_id, report) = deploy(cmd.clone()).await?;
+
+    // Printed rather than logged so it's shown even with --quiet
+    println!("{}", report.summary(&cmd.gas_token()));
+
+    Ok(())
+}
+
+/// Runs the deployment against the chain of `cmd.rpc_url`
+///
+/// Returns the chain id and the resulting report
+pub async fn deploy(cmd: Args) -> eyre::Result<(u64, Report)> {
+    let (context, config) =
+        init_context(cmd.clone(), ChainAccess::Send).await?;
+
+    ensure_not_decommissioned(&context.report)?;
+
+    if context.report.world_id_router.is_none() {
+        check_bootstrap_group(&config, cmd.bootstrap_group)?;
+    }
+
+    // Compiling everything up front leaves every create and ABI inspection
+    // with a warm cache instead of a compile check of its own
+    if !cmd.skip_build {
+        context
+            .forge_build()
+            .with_cwd("./world-id-contracts")
+            .run()
+            .await?;
+    }
+
+    if let Some(dump_tx_for) = cmd.dump_tx_for.as_deref() {
+        context.clear_dump(dump_tx_for).await?;
+    }
+
+    let mut outputs = StepOutputs::default();
+
+    let result =
+        run_steps(context.clone(), config.clone(), &cmd.target, &mut outputs)
+            .await;
+
+    if let Err(err) = result {
+        // A partial dump mustn't be broadcast
+        if let Some(dump_tx_for) = cmd.dump_tx_for.as_deref() {
+            if let Err(clear_err) = context.clear_dump(dump_tx_for).await {
+                warn!("Failed to remove the partial dump: {clear_err:?}");
+            }
+        }
+
+        if !cmd.no_report_on_error {
+            if let Err(report_err) =
+                flush_report(context.clone(), config, &outputs).await
+            {
+                warn!(
+                    "Failed to write the report after an error: {report_err:?}"
+                );
+            }
+        }
+
+        return Err(err);
+    }
+
+    // Dumping stops after the dumped step, short of the target, and doesn't
+    // record that step - so there might be no report yet
+    if cmd.dump_tx_for.is_some() {
+        let report = serde_utils::read_deserialize(&context.report_path)
+            .await
+            .unwrap_or_else(|_| context.report.clone());
+
+        return Ok((context.chain_id, report));
+    }
+
+    let mut report: Report =
+        serde_utils::read_deserialize(&context.report_path).await?;
+
+    assertions::check_assertions(context.as_ref(), config.as_ref(), &report)
+        .await?;
+
+    report.complete = true;
+
+    serde_utils::write_serialize(&context.report_path, &report).await?;
+
+    Ok((context.chain_id, report))
+}
+
+/// The outputs of the steps which ran so far
+#[derive(Default)]
+struct StepOutputs {
+    insertion_verifiers: Option<verifiers::Verifiers>,
+    deletion_verifiers: Option<verifiers::Verifiers>,
+    lookup_tables: Option<lookup_tables::LookupTables>,
+    semaphore_verifier: Option<semaphore_verifier::SemaphoreVerifierDeployment>,
+    identity_managers:
+        Option<identity_manager::WorldIDIdentityManagersDeployment>,
+    world_id_router: Option<world_id_router::WorldIdRouterDeployment>,
+    timelock: Option<timelock::TimelockDeployment>,
+}
+
+/// Writes the outputs of a failed deployment to the report
+///
+/// Steps which didn't run (or didn't finish) keep their intermediate results,
+/// or else the deployments of the previous report, so nothing is lost for
+/// resuming.
+/// Checks that the router can be initialized with `bootstrap_group`
+///
+/// The router routes group 0 to the bootstrap group, so a bootstrap group
+/// other than 0 conflicts with a group 0 in the config.
+fn check_bootstrap_group(
+    config: &Config,
+    bootstrap_group: GroupId,
+) -> eyre::Result<()> {
+    if !config.groups.contains_key(&bootstrap_group) {
+        eyre::bail!("Bootstrap group {bootstrap_group} is not in the config");
+    }
+
+    if bootstrap_group != GroupId(0) && config.groups.contains_key(&GroupId(0))
+    {
+        eyre::bail!(
+            "Bootstrap group {bootstrap_group} conflicts with group 0 of the config - the router would route group 0 to it until group 0 replaces the route, leave out --bootstrap-group instead"
+        );
+    }
+
+    Ok(())
+}
+
+/// Fails if the deployment was decommissioned, nothing may be deployed to it
+fn ensure_not_decommissioned(report: &Report) -> eyre::Result<()> {
+    let decommissioned = report
+        .world_id_router
+        .as_ref()
+        .is_some_and(|router| router.decommissioned);
+
+    if decommissioned {
+        eyre::bail!("The deployment has been decommissioned");
+    }
+
+    Ok(())
+}
+
+async fn flush_report(
+    context: Arc<DeploymentContext>,
+    config: Arc<Config>,
+    outputs: &StepOutputs,
+) -> eyre::Result<()> {
+    // Includes the intermediate results written by the failed step
+    let latest: Option<Report> =
+        serde_utils::read_deserialize(&context.report_path)
+            .await
+            .ok();
+
+    macro_rules! most_recent {
+        ($field:ident) => {
+            outputs
+                .$field
+                .as_ref()
+                .or(latest.as_ref().and_then(|report| report.$field.as_ref()))
+                .or(context.report.$field.as_ref())
+        };
+    }
+
+    assemble_report::assemble_report(
+        context.clone(),
+        config,
+        most_recent!(insertion_verifiers),
+        most_recent!(deletion_verifiers),
+        most_recent!(lookup_tables),
+        most_recent!(semaphore_verifier),
+        most_recent!(identity_managers),
+        most_recent!(world_id_router),
+        most_recent!(timelock),
+    )
+    .await
+}
+
+/// Flags groups which would get a lookup table for `mode` without any
+/// verifiers in it - most likely an empty batch size list by mistake
+fn check_batch_sizes(
+    config: &Config,
+    mode: ProverMode,
+    strict: bool,
+) -> eyre::Result<()> {
+    let group_ids = config.groups_without_batch_sizes(mode);
+
+    if group_ids.is_empty() {
+        return Ok(());
+    }
+
+    let group_ids = group_ids
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    if strict {
+        eyre::bail!("Groups {group_ids} have no {mode} batch sizes");
+    }
+
+    warn!(
+        "Groups {group_ids} have no {mode} batch sizes, their {mode} lookup tables will be empty"
     );
 
+    Ok(())
+}
+
+/// Refuses chains outside of the allowlists of the config and the flags, and
+/// asks for confirmation on the chains the config marks for it as well as on
+/// the [`PROTECTED_CHAIN_IDS`]
+fn check_chain(
+    context: &DeploymentContext,
+    config: &Config,
+) -> eyre::Result<()> {
+    let chain_id = context.chain_id;
+
+    if let Some(allowed_chain_ids) = config.misc.allowed_chain_ids.as_ref() {
+        if !allowed_chain_ids.contains(&chain_id) {
+            eyre::bail!(
+                "Chain {chain_id} is not in misc.allowed_chain_ids {allowed_chain_ids:?}"
+            );
+        }
+    }
+
+    let allow_chains = &context.cmd.allow_chains;
+    if !allow_chains.is_empty() && !allow_chains.contains(&chain_id) {
+        eyre::bail!(
+            "Chain {chain_id} is not allowed by --allow-chain {allow_chains:?}"
+        );
+    }
+
+    let needs_confirmation = config.misc.confirm_chain_ids.contains(&chain_id)
+        || PROTECTED_CHAIN_IDS.contains(&chain_id);
+
+    if needs_confirmation
+        && !confirm(
+            &format!(
+                "Send transactions of {} to chain {chain_id}?",
+                context.cmd.deployment_name()?
+            ),
+            context.cmd.yes,
+        )?
+    {
+        eyre::bail!("Aborted");
+    }
+
+    Ok(())
+}
+
+async fn run_steps(
+    context: Arc<DeploymentContext>,
+    config: Arc<Config>,
+    target: &DeploymentType,
+    outputs: &mut StepOutputs,
+) -> eyre::Result<()> {
+    let verifier_mode = &context.cmd.verifier_mode;
+
+    let steps = plan::plan(
+        &config,
+        &context.report,
+        target,
+        verifier_mode,
+        context.cmd.no_deletion,
+        &context.cmd.skip_steps,
+    );
+    plan::check_skipped_steps(&steps, &context.cmd.skip_steps)?;
+
+    let step = |name| plan::step(&steps, name);
+
+    let deploy_insertion_verifiers = step("insertion_verifiers").runs;
+    let deploy_deletion_verifiers = step("deletion_verifiers").runs;
+
+    for (mode, deployed) in [
+        (ProverMode::Insertion, deploy_insertion_verifiers),
+        (ProverMode::Deletion, deploy_deletion_verifiers),
+    ] {
+        if deployed {
+            check_batch_sizes(&config, mode, context.cmd.strict_batch_sizes)?;
+        }
+    }
+
+    if context.cmd.check_owners || context.cmd.strict_owners {
+        ownership::check_owners(
+            context.as_ref(),
+            &config,
+            context.cmd.strict_owners,
+        )
+        .await?;
+    }
+
+    context.set_cost_step("insertion_verifiers");
+
+    // Skipped verifiers are taken from the previous deployment
+    outputs.insertion_verifiers = if deploy_insertion_verifiers {
+        // Records every verifier as soon as it's deployed
+        let record_partial = |partial| {
+            let context = context.clone();
+            let config = config.clone();
+
+            async move {
+                assemble_report::assemble_report(
+                    context,
+                    config,
+                    Some(&partial),
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                )
+                .await
+            }
+        };
+
+        Some(
+            verifiers::deploy(
+                context.clone(),
+                config.clone(),
+                ProverMode::Insertion,
+                record_partial,
+            )
+            .await?,
+        )
+    } else {
+        context.report.insertion_verifiers.clone()
+    };
+
     assemble_report::assemble_report(
         context.clone(),
         config.clone(),
-        insertion_verifiers.as_ref(),
+        outputs.insertion_verifiers.as_ref(),
+        None,
         None,
         None,
         None,
@@ -100,24 +584,54 @@ pub async fn run_deployment(cmd: Args) -> eyre::Result<()> {
     )
     .await?;
 
-    if cmd.target == DeploymentType::InsertionVerifiers {
+    if context.cmd.dump_tx_for.as_deref() == Some("insertion_verifiers") {
         return Ok(());
     }
 
-    let deletion_verifiers = Some(
-        verifiers::deploy(
-            context.clone(),
-            config.clone(),
-            ProverMode::Deletion,
+    context.set_cost_step("deletion_verifiers");
+
+    outputs.deletion_verifiers = if deploy_deletion_verifiers {
+        let outputs = &*outputs;
+
+        let record_partial = |partial| {
+            let context = context.clone();
+            let config = config.clone();
+
+            async move {
+                assemble_report::assemble_report(
+                    context,
+                    config,
+                    outputs.insertion_verifiers.as_ref(),
+                    Some(&partial),
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                )
+                .await
+            }
+        };
+
+        Some(
+            verifiers::deploy(
+                context.clone(),
+                config.clone(),
+                ProverMode::Deletion,
+                record_partial,
+            )
+            .await?,
         )
-        .await?,
-    );
+    } else {
+        context.report.deletion_verifiers.clone()
+    };
 
     assemble_report::assemble_report(
         context.clone(),
         config.clone(),
-        insertion_verifiers.as_ref(),
-        deletion_verifiers.as_ref(),
+        outputs.insertion_verifiers.as_ref(),
+        outputs.deletion_verifiers.as_ref(),
+        None,
         None,
         None,
         None,
@@ -125,120 +639,288 @@ pub async fn run_deployment(cmd: Args) -> eyre::Result<()> {
     )
     .await?;
 
-    if cmd.target == DeploymentType::DeletionVerifiers
-        || cmd.target == DeploymentType::Verifiers
-    {
+    if context.cmd.dump_tx_for.as_deref() == Some("deletion_verifiers") {
         return Ok(());
     }
 
-    let lookup_tables = Some(
-        lookup_tables::deploy(
-            context.clone(),
-            config.clone(),
-            insertion_verifiers
-                .as_ref()
-                .context("Missing insertion verifiers")?,
-            deletion_verifiers
-                .as_ref()
-                .context("Missing deletion verifiers")?,
+    if !step("lookup_tables").reached {
+        return Ok(());
+    }
+
+    context.set_cost_step("lookup_tables");
+
+    outputs.lookup_tables = if !step("lookup_tables").runs {
+        context.report.lookup_tables.clone()
+    } else {
+        let deletion_verifiers = if context.cmd.no_deletion {
+            None
+        } else {
+            Some(
+                outputs
+                    .deletion_verifiers
+                    .as_ref()
+                    .context("Missing deletion verifiers")?,
+            )
+        };
+
+        let outputs = &*outputs;
+
+        // Records every lookup table and association as soon as it's done
+        let record_partial = |partial| {
+            let context = context.clone();
+            let config = config.clone();
+
+            async move {
+                assemble_report::assemble_report(
+                    context,
+                    config,
+                    outputs.insertion_verifiers.as_ref(),
+                    outputs.deletion_verifiers.as_ref(),
+                    Some(&partial),
+                    None,
+                    None,
+                    None,
+                    None,
+                )
+                .await
+            }
+        };
+
+        Some(
+            lookup_tables::deploy(
+                context.clone(),
+                config.clone(),
+                outputs
+                    .insertion_verifiers
+                    .as_ref()
+                    .context("Missing insertion verifiers")?,
+                deletion_verifiers,
+                record_partial,
+            )
+            .await?,
         )
-        .await?,
-    );
+    };
 
     assemble_report::assemble_report(
         context.clone(),
         config.clone(),
-        insertion_verifiers.as_ref(),
-        deletion_verifiers.as_ref(),
-        lookup_tables.as_ref(),
+        outputs.insertion_verifiers.as_ref(),
+        outputs.deletion_verifiers.as_ref(),
+        outputs.lookup_tables.as_ref(),
+        None,
         None,
         None,
         None,
     )
     .await?;
 
-    if cmd.target == DeploymentType::LookupTables {
+    if context.cmd.dump_tx_for.as_deref() == Some("lookup_tables") {
         return Ok(());
     }
 
-    let semaphore_verifier = Some(
-        semaphore_verifier::deploy(context.clone(), config.clone()).await?,
-    );
+    if !step("semaphore_verifier").reached {
+        return Ok(());
+    }
+
+    context.set_cost_step("semaphore_verifier");
+
+    outputs.semaphore_verifier = if !step("semaphore_verifier").runs {
+        context.report.semaphore_verifier.clone()
+    } else {
+        let outputs = &*outputs;
+
+        // Records the Pairing library as soon as it's deployed, so a resume
+        // doesn't redeploy it
+        let record_pairing = |partial| {
+            let context = context.clone();
+            let config = config.clone();
+
+            async move {
+                assemble_report::assemble_report(
+                    context,
+                    config,
+                    outputs.insertion_verifiers.as_ref(),
+                    outputs.deletion_verifiers.as_ref(),
+                    outputs.lookup_tables.as_ref(),
+                    Some(&partial),
+                    None,
+                    None,
+                    None,
+                )
+                .await
+            }
+        };
+
+        Some(
+            semaphore_verifier::deploy(
+                context.clone(),
+                config.clone(),
+                record_pairing,
+            )
+            .await?,
+        )
+    };
 
     assemble_report::assemble_report(
         context.clone(),
         config.clone(),
-        insertion_verifiers.as_ref(),
-        deletion_verifiers.as_ref(),
-        lookup_tables.as_ref(),
-        semaphore_verifier.as_ref(),
+        outputs.insertion_verifiers.as_ref(),
+        outputs.deletion_verifiers.as_ref(),
+        outputs.lookup_tables.as_ref(),
+        outputs.semaphore_verifier.as_ref(),
+        None,
         None,
         None,
     )
     .await?;
 
-    if cmd.target == DeploymentType::SemaphoreVerifier {
+    if context.cmd.dump_tx_for.as_deref() == Some("semaphore_verifier") {
         return Ok(());
     }
 
-    let identity_manager: Option<
-        identity_manager::WorldIDIdentityManagersDeployment,
-    > = Some(
-        identity_manager::deploy(
-            context.clone(),
-            config.clone(),
-            semaphore_verifier
-                .as_ref()
-                .context("Missing semaphore verifier")?,
-            lookup_tables.as_ref().context("Missing lookup tables")?,
+    if !step("identity_managers").reached {
+        return Ok(());
+    }
+
+    context.set_cost_step("identity_managers");
+
+    outputs.identity_managers = if !step("identity_managers").runs {
+        context.report.identity_managers.clone()
+    } else {
+        let outputs = &*outputs;
+
+        // Records implementations and groups as soon as they're deployed, so
+        // a resume doesn't redeploy them
+        let record_partial = |partial| {
+            let context = context.clone();
+            let config = config.clone();
+
+            async move {
+                assemble_report::assemble_report(
+                    context,
+                    config,
+                    outputs.insertion_verifiers.as_ref(),
+                    outputs.deletion_verifiers.as_ref(),
+                    outputs.lookup_tables.as_ref(),
+                    outputs.semaphore_verifier.as_ref(),
+                    Some(&partial),
+                    None,
+                    None,
+                )
+                .await
+            }
+        };
+
+        Some(
+            identity_manager::deploy(
+                context.clone(),
+                config.clone(),
+                outputs
+                    .semaphore_verifier
+                    .as_ref()
+                    .context("Missing semaphore verifier")?,
+                outputs
+                    .lookup_tables
+                    .as_ref()
+                    .context("Missing lookup tables")?,
+                record_partial,
+            )
+            .await?,
         )
-        .await?,
-    );
+    };
+
+    assemble_report::assemble_report(
+        context.clone(),
+        config.clone(),
+        outputs.insertion_verifiers.as_ref(),
+        outputs.deletion_verifiers.as_ref(),
+        outputs.lookup_tables.as_ref(),
+        outputs.semaphore_verifier.as_ref(),
+        outputs.identity_managers.as_ref(),
+        None,
+        None,
+    )
+    .await?;
+
+    if context.cmd.dump_tx_for.as_deref() == Some("identity_managers") {
+        return Ok(());
+    }
+
+    if !step("world_id_router").reached {
+        return Ok(());
+    }
+
+    context.set_cost_step("world_id_router");
+
+    outputs.world_id_router = if !step("world_id_router").runs {
+        context.report.world_id_router.clone()
+    } else {
+        Some(
+            world_id_router::deploy(
+                context.clone(),
+                config.clone(),
+                outputs
+                    .identity_managers
+                    .as_ref()
+                    .context("Missing identity manager")?,
+            )
+            .await?,
+        )
+    };
 
     assemble_report::assemble_report(
         context.clone(),
         config.clone(),
-        insertion_verifiers.as_ref(),
-        deletion_verifiers.as_ref(),
-        lookup_tables.as_ref(),
-        semaphore_verifier.as_ref(),
-        identity_manager.as_ref(),
+        outputs.insertion_verifiers.as_ref(),
+        outputs.deletion_verifiers.as_ref(),
+        outputs.lookup_tables.as_ref(),
+        outputs.semaphore_verifier.as_ref(),
+        outputs.identity_managers.as_ref(),
+        outputs.world_id_router.as_ref(),
         None,
     )
     .await?;
 
-    if cmd.target == DeploymentType::IdentityManager {
+    if context.cmd.dump_tx_for.as_deref() == Some("world_id_router") {
         return Ok(());
     }
 
-    let world_id_router = Some(
-        world_id_router::deploy(
+    if !step("timelock").reached {
+        return Ok(());
+    }
+
+    context.set_cost_step("timelock");
+
+    outputs.timelock = if !step("timelock").runs {
+        context.report.timelock.clone()
+    } else {
+        timelock::deploy(
             context.clone(),
             config.clone(),
-            identity_manager
+            outputs
+                .identity_managers
                 .as_ref()
                 .context("Missing identity manager")?,
+            outputs
+                .world_id_router
+                .as_ref()
+                .context("Missing world id router")?,
         )
-        .await?,
-    );
+        .await?
+    };
 
     assemble_report::assemble_report(
         context,
         config,
-        insertion_verifiers.as_ref(),
-        deletion_verifiers.as_ref(),
-        lookup_tables.as_ref(),
-        semaphore_verifier.as_ref(),
-        identity_manager.as_ref(),
-        world_id_router.as_ref(),
+        outputs.insertion_verifiers.as_ref(),
+        outputs.deletion_verifiers.as_ref(),
+        outputs.lookup_tables.as_ref(),
+        outputs.semaphore_verifier.as_ref(),
+        outputs.identity_managers.as_ref(),
+        outputs.world_id_router.as_ref(),
+        outputs.timelock.as_ref(),
     )
     .await?;
 
-    if cmd.target == DeploymentType::WorldIdRouter
-        || cmd.target == DeploymentType::Full
-    {
-        return Ok(());
-    }
-
     Ok(())
 }
@@ -0,0 +1,136 @@
+use eyre::{Context, ContextCompat};
+use tracing::info;
+
+use super::mtb_utils::{download_semaphore_mtb_binary, ProverMode};
+use super::steps::assemble_report::assemble_report;
+use super::steps::lookup_tables::associate_group_batch_size_verifier;
+use super::steps::verifiers::{self, Verifiers};
+use super::{ensure_not_decommissioned, init_context, ChainAccess};
+use crate::cli::{Args, BatchSizeAddition};
+
+/// Deploys the verifier of a single new batch size and associates it in the
+/// group's lookup table, skipping all the other steps
+pub async fn run_add_batch_size(
+    cmd: Args,
+    addition: BatchSizeAddition,
+) -> eyre::Result<()> {
+    let BatchSizeAddition {
+        group_id,
+        mode,
+        batch_size,
+    } = addition;
+
+    let (context, config) = init_context(cmd, ChainAccess::Send).await?;
+
+    ensure_not_decommissioned(&context.report)?;
+
+    let group_config = config
+        .groups
+        .get(&group_id)
+        .with_context(|| format!("Group {group_id} is not in the config"))?;
+
+    let config_batch_sizes = match mode {
+        ProverMode::Insertion => &group_config.insertion_batch_sizes,
+        ProverMode::Deletion => &group_config.deletion_batch_sizes,
+    };
+
+    // Keeps the report consistent with the config it embeds
+    if !config_batch_sizes.contains(&batch_size) {
+        eyre::bail!(
+            "Add batch size {batch_size} to the {mode} batch sizes of group {group_id} in the config first"
+        );
+    }
+
+    let mut lookup_tables = context.report.lookup_tables.clone().context(
+        "No lookup tables found in the report - run a full deployment first",
+    )?;
+
+    let group_lookup_tables =
+        lookup_tables.groups.get_mut(&group_id).with_context(|| {
+            format!("No lookup tables found for group {group_id}")
+        })?;
+
+    let lookup_table = match mode {
+        ProverMode::Insertion => group_lookup_tables.insert.as_mut(),
+        ProverMode::Deletion => group_lookup_tables.delete.as_mut(),
+    }
+    .with_context(|| format!("Group {group_id} has no {mode} lookup table"))?;
+
+    let tree_depth = group_config
+        .tree_depth()
+        .with_context(|| format!("Group {group_id}"))?;
+
+    context.set_cost_step("add_batch_size");
+    context.set_cost_group(Some(group_id));
+
+    download_semaphore_mtb_binary(&context.cache_dir).await?;
+
+    let verifier = verifiers::deploy_verifier(
+        context.as_ref(),
+        config.as_ref(),
+        mode,
+        tree_depth,
+        batch_size,
+    )
+    .await?;
+
+    let mut report_verifiers =
+        context.report.verifiers(mode).cloned().unwrap_or_default();
+
+    report_verifiers
+        .verifiers
+        .insert((tree_depth, batch_size), verifier.clone());
+
+    let lookup_abi = context
+        .inspect_abi(config.misc.contract_names.verifier_lookup_table())
+        .await?;
+
+    let verifier_address = associate_group_batch_size_verifier(
+        context.clone(),
+        lookup_abi,
+        lookup_table.deployment.address,
+        group_id,
+        tree_depth,
+        batch_size,
+        &Verifiers {
+            verifiers: maplit::hashmap! {
+                (tree_depth, batch_size) => verifier,
+            },
+        },
+    )
+    .await?;
+
+    lookup_table.entries.insert(batch_size, verifier_address);
+
+    context.set_cost_group(None);
+
+    let report = &context.report;
+
+    let (insertion_verifiers, deletion_verifiers) = match mode {
+        ProverMode::Insertion => {
+            (Some(&report_verifiers), report.deletion_verifiers.as_ref())
+        }
+        ProverMode::Deletion => {
+            (report.insertion_verifiers.as_ref(), Some(&report_verifiers))
+        }
+    };
+
+    assemble_report(
+        context.clone(),
+        config.clone(),
+        insertion_verifiers,
+        deletion_verifiers,
+        Some(&lookup_tables),
+        report.semaphore_verifier.as_ref(),
+        report.identity_managers.as_ref(),
+        report.world_id_router.as_ref(),
+        report.timelock.as_ref(),
+    )
+    .await?;
+
+    info!(
+        "Added {mode} verifier {verifier_address:?} for batch size {batch_size} to group {group_id}"
+    );
+
+    Ok(())
+}
@@ -0,0 +1,192 @@
+use ethers::abi::Token;
+use eyre::Context as _;
+use tracing::{error, info, instrument, warn};
+
+use super::initializer_args::parse_arg;
+use super::steps::identity_manager::known_addresses;
+use super::DeploymentContext;
+use crate::config::{AssertedContract, AssertionConfig, Config};
+use crate::ethers_utils::TransactionBuilder;
+use crate::forge_utils::ContractSpec;
+use crate::report::contract_deployment::ContractDeployment;
+use crate::report::Report;
+
+/// Calls the view function of every assertion in the config and errors if any
+/// of them returns something other than the expected value
+///
+/// Assertions on contracts which aren't in the report, e.g. because the
+/// target stops short of them, or which were only simulated are skipped.
+#[instrument(skip_all)]
+pub async fn check_assertions(
+    context: &DeploymentContext,
+    config: &Config,
+    report: &Report,
+) -> eyre::Result<()> {
+    let mut num_failed = 0;
+
+    for assertion in &config.misc.assertions {
+        let description = format!(
+            "{:?}.{}({})",
+            assertion.contract,
+            assertion.function,
+            assertion.args.join(", ")
+        );
+
+        let Some((deployment, spec)) =
+            asserted_contract(config, report, assertion)
+        else {
+            warn!("Skipping assertion {description}: contract not deployed");
+            continue;
+        };
+
+        if deployment.simulated {
+            warn!("Skipping assertion {description}: contract simulated");
+            continue;
+        }
+
+        let (expected, actual) =
+            call(context, report, assertion, deployment, spec)
+                .await
+                .with_context(|| format!("Checking assertion {description}"))?;
+
+        if actual == expected {
+            info!("Assertion {description} holds");
+        } else {
+            error!(
+                "Assertion {description} failed: expected {expected}, got {actual}"
+            );
+            num_failed += 1;
+        }
+    }
+
+    if num_failed > 0 {
+        eyre::bail!("{num_failed} post-deploy assertion(s) failed");
+    }
+
+    Ok(())
+}
+
+/// Returns the expected and the actual value
+async fn call(
+    context: &DeploymentContext,
+    report: &Report,
+    assertion: &AssertionConfig,
+    deployment: &ContractDeployment,
+    spec: ContractSpec,
+) -> eyre::Result<(Token, Token)> {
+    let abi = context.inspect_abi(spec).await?;
+
+    let func = abi.function(&assertion.function)?;
+
+    if func.inputs.len() != assertion.args.len() {
+        eyre::bail!(
+            "{} takes {} arguments, got {}",
+            func.name,
+            func.inputs.len(),
+            assertion.args.len()
+        );
+    }
+
+    let [output] = func.outputs.as_slice() else {
+        eyre::bail!("{} must return exactly one value", func.name);
+    };
+
+    let group_id = assertion.group_id();
+
+    let mut known_addresses = report
+        .group_lookup_tables(group_id)
+        .map(known_addresses)
+        .unwrap_or_default();
+
+    if let Some(identity_manager) = report.identity_manager_for_group(group_id)
+    {
+        known_addresses.insert("identity_manager", identity_manager);
+    }
+
+    if let Some(router) = report.router_address() {
+        known_addresses.insert("router", router);
+    }
+
+    if let Some(verifier) = report
+        .semaphore_verifier
+        .as_ref()
+        .and_then(|verifier| verifier.verifier_deployment.as_ref())
+    {
+        known_addresses.insert("semaphore_verifier", verifier.address);
+    }
+
+    let args = func
+        .inputs
+        .iter()
+        .zip(&assertion.args)
+        .map(|(input, value)| parse_arg(input, value, &known_addresses))
+        .collect::<eyre::Result<Vec<_>>>()?;
+
+    let expected = parse_arg(output, &assertion.expected, &known_addresses)?;
+
+    let actual: Token = TransactionBuilder::default()
+        .signer(context.rpc_signer.clone())
+        .abi(abi.clone())
+        .function_name(&func.name)
+        .args(args.as_slice())
+        .to(deployment.address)
+        .context(context)
+        .build()?
+        .call()
+        .await?;
+
+    Ok((expected, actual))
+}
+
+/// The deployment of the asserted contract and the spec of its ABI
+fn asserted_contract<'a>(
+    config: &Config,
+    report: &'a Report,
+    assertion: &AssertionConfig,
+) -> Option<(&'a ContractDeployment, ContractSpec)> {
+    let contract_names = &config.misc.contract_names;
+    let group_id = assertion.group_id();
+
+    let lookup_tables = report.group_lookup_tables(group_id);
+
+    match assertion.contract {
+        AssertedContract::Router => {
+            report.world_id_router.as_ref().map(|router| {
+                (&router.proxy_deployment, contract_names.router_impl())
+            })
+        }
+        AssertedContract::IdentityManager => {
+            let identity_manager = report.group_identity_manager(group_id)?;
+
+            // The proxy exposes the functions of its latest implementation
+            let spec = if identity_manager.impl_v2_deployment.is_some() {
+                contract_names.identity_manager_impl_v2()
+            } else {
+                contract_names.identity_manager_impl_v1()
+            };
+
+            Some((&identity_manager.proxy_deployment, spec))
+        }
+        AssertedContract::InsertLookupTable => {
+            lookup_tables?.insert.as_ref().map(|table| {
+                (&table.deployment, contract_names.verifier_lookup_table())
+            })
+        }
+        AssertedContract::UpdateLookupTable => {
+            lookup_tables?.update.as_ref().map(|table| {
+                (&table.deployment, contract_names.verifier_lookup_table())
+            })
+        }
+        AssertedContract::DeleteLookupTable => {
+            lookup_tables?.delete.as_ref().map(|table| {
+                (&table.deployment, contract_names.verifier_lookup_table())
+            })
+        }
+        AssertedContract::SemaphoreVerifier => report
+            .semaphore_verifier
+            .as_ref()?
+            .verifier_deployment
+            .as_ref()
+            .map(|verifier| (verifier, contract_names.semaphore_verifier())),
+    }
+}
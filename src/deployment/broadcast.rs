@@ -0,0 +1,67 @@
+use std::str::FromStr;
+
+use ethers::providers::Middleware;
+use ethers::types::Bytes;
+use eyre::Context;
+use tracing::info;
+
+use super::steps::assemble_report::assemble_report;
+use super::{init_context, ChainAccess};
+use crate::cli::{Args, BroadcastArgs};
+use crate::ethers_utils::await_confirmed;
+
+/// Sends signed raw transactions dumped by `--dump-tx-for`, in order
+///
+/// The costs of the transactions are added to the report after each one,
+/// the rest of the report is kept as is
+pub async fn run_broadcast(cmd: Args, args: BroadcastArgs) -> eyre::Result<()> {
+    let (context, config) = init_context(cmd, ChainAccess::Send).await?;
+    let report = &context.report;
+
+    let contents = tokio::fs::read_to_string(&args.file)
+        .await
+        .with_context(|| format!("Reading {}", args.file.display()))?;
+
+    for line in contents.lines().filter(|line| !line.trim().is_empty()) {
+        let raw_tx = Bytes::from_str(line.trim())
+            .with_context(|| format!("Parsing raw transaction {line}"))?;
+
+        context.throttle().await;
+
+        let tx = context
+            .rpc_signer
+            .0
+            .send_raw_transaction(raw_tx)
+            .await
+            .context("Send raw transaction")?;
+
+        let tx_hash = tx.tx_hash();
+
+        info!("Sent transaction {tx_hash:?}");
+
+        let receipt = await_confirmed(
+            &context.rpc_signer,
+            tx_hash,
+            1,
+            context.cmd.tx_timeout(),
+        )
+        .await?;
+
+        context.record_cost(&receipt);
+
+        assemble_report(
+            context.clone(),
+            config.clone(),
+            report.insertion_verifiers.as_ref(),
+            report.deletion_verifiers.as_ref(),
+            report.lookup_tables.as_ref(),
+            report.semaphore_verifier.as_ref(),
+            report.identity_managers.as_ref(),
+            report.world_id_router.as_ref(),
+            report.timelock.as_ref(),
+        )
+        .await?;
+    }
+
+    Ok(())
+}
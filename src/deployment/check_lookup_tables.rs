@@ -0,0 +1,110 @@
+use ethers::types::Address;
+use eyre::{Context, ContextCompat};
+
+use super::steps::lookup_tables::{current_verifier, LookupTable};
+use super::{init_context, ChainAccess};
+use crate::cli::Args;
+use crate::types::GroupId;
+
+/// Compares the verifiers the report records for every lookup table with the
+/// ones associated on-chain, failing on any divergence
+///
+/// Read-only - catches manual `updateVerifier` calls and stale reports. RPC
+/// failures are errors rather than divergences, so a flaky endpoint doesn't
+/// produce false findings.
+pub async fn run_check_lookup_tables(cmd: Args) -> eyre::Result<()> {
+    let (context, config) = init_context(cmd, ChainAccess::Read).await?;
+
+    let lookup_tables = context
+        .report
+        .lookup_tables
+        .as_ref()
+        .context("No lookup tables found in the report")?;
+
+    let lookup_abi = context
+        .inspect_abi(config.misc.contract_names.verifier_lookup_table())
+        .await?;
+
+    let mut group_ids: Vec<GroupId> =
+        lookup_tables.groups.keys().copied().collect();
+    group_ids.sort();
+
+    let mut num_diverged = 0;
+
+    for group_id in group_ids {
+        let group = &lookup_tables.groups[&group_id];
+
+        for (name, table) in [
+            ("insert", group.insert.as_ref()),
+            ("update", group.update.as_ref()),
+            ("delete", group.delete.as_ref()),
+        ] {
+            let Some(LookupTable {
+                deployment,
+                entries,
+            }) = table
+            else {
+                continue;
+            };
+
+            if deployment.simulated {
+                println!("group {group_id} {name}: simulated, skipped");
+                continue;
+            }
+
+            println!(
+                "group {group_id} {name} ({:?}, {} entries):",
+                deployment.address,
+                entries.len()
+            );
+
+            let mut batch_sizes: Vec<_> = entries.keys().copied().collect();
+            batch_sizes.sort();
+
+            for batch_size in batch_sizes {
+                let recorded = entries[&batch_size];
+                let on_chain = current_verifier(
+                    context.as_ref(),
+                    &lookup_abi,
+                    deployment.address,
+                    batch_size,
+                )
+                .await
+                .with_context(|| {
+                    format!(
+                        "Reading the verifier of batch size {batch_size} from group {group_id}'s {name} lookup table"
+                    )
+                })?;
+
+                if on_chain == Some(recorded) {
+                    println!("  batch size {batch_size}: {recorded:?}");
+                    continue;
+                }
+
+                num_diverged += 1;
+
+                println!(
+                    "  batch size {batch_size}: DIVERGED - report {recorded:?}, on-chain {}",
+                    describe(on_chain)
+                );
+            }
+        }
+    }
+
+    if num_diverged > 0 {
+        eyre::bail!(
+            "{num_diverged} lookup table entries diverge from the chain"
+        );
+    }
+
+    println!("All lookup table entries match the chain");
+
+    Ok(())
+}
+
+fn describe(verifier: Option<Address>) -> String {
+    match verifier {
+        Some(verifier) => format!("{verifier:?}"),
+        None => "none".to_string(),
+    }
+}
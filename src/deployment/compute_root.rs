@@ -0,0 +1,18 @@
+use ethers::types::U256;
+
+use crate::cli::ComputeRootArgs;
+use crate::utils::initial_root_hash;
+
+/// Prints the initial root the identity managers would be initialized with,
+/// computed the same way as during the deployment
+pub fn run_compute_root(args: ComputeRootArgs) -> eyre::Result<()> {
+    let leaf = args.leaf.unwrap_or_default();
+    let root = initial_root_hash(args.tree_depth, leaf);
+
+    println!("Tree depth: {}", args.tree_depth);
+    println!("Leaf:       {leaf:?}");
+    println!("Root (hex): {root:?}");
+    println!("Root (dec): {}", U256::from_big_endian(root.as_bytes()));
+
+    Ok(())
+}
@@ -0,0 +1,134 @@
+use std::sync::Arc;
+
+use eyre::ContextCompat;
+use tracing::{info, warn};
+
+use super::steps::assemble_report::assemble_report;
+use super::steps::ownership::transfer_ownership;
+use super::steps::world_id_router::{
+    remove_group_route, WorldIdRouterDeployment,
+};
+use super::{init_context, ChainAccess, DeploymentContext};
+use crate::cli::{Args, DecommissionArgs};
+use crate::config::Config;
+use crate::utils::{confirm, PROTECTED_CHAIN_IDS};
+
+pub async fn run_decommission(
+    cmd: Args,
+    args: DecommissionArgs,
+) -> eyre::Result<()> {
+    let (context, config) = init_context(cmd, ChainAccess::Send).await?;
+
+    let mut world_id_router = context
+        .report
+        .world_id_router
+        .clone()
+        .context("No world id router found in the report")?;
+
+    let assume_yes = context.cmd.yes;
+
+    if PROTECTED_CHAIN_IDS.contains(&context.chain_id) && !assume_yes {
+        eyre::bail!(
+            "Chain {} is protected - pass --yes to decommission",
+            context.chain_id
+        );
+    }
+
+    if !confirm(
+        &format!(
+            "Disable all groups of deployment {} on chain {}?",
+            context.cmd.deployment_name()?,
+            context.chain_id
+        ),
+        assume_yes,
+    )? {
+        eyre::bail!("Aborted");
+    }
+
+    context.set_cost_step("decommission");
+
+    let mut group_ids: Vec<_> =
+        world_id_router.entries.keys().copied().collect();
+    group_ids.sort();
+
+    for group_id in group_ids {
+        warn!("Disabling group {group_id}");
+
+        remove_group_route(
+            context.as_ref(),
+            config.as_ref(),
+            world_id_router.proxy_deployment.address,
+            group_id,
+        )
+        .await?;
+
+        if let Some(entry) = world_id_router.entries.remove(&group_id) {
+            world_id_router.disabled.insert(group_id, entry.target);
+        }
+
+        // Keeps the report in line with the chain if a later group fails
+        write_report(&context, &config, &world_id_router).await?;
+    }
+
+    world_id_router.decommissioned = true;
+
+    write_report(&context, &config, &world_id_router).await?;
+
+    if let Some(new_owner) = args.transfer_ownership_to {
+        transfer_ownership(
+            context.as_ref(),
+            config.misc.contract_names.router_impl(),
+            world_id_router.proxy_deployment.address,
+            new_owner,
+        )
+        .await?;
+
+        let identity_managers = context
+            .report
+            .identity_managers
+            .iter()
+            .flat_map(|deployment| deployment.groups.values());
+
+        for identity_manager in identity_managers {
+            transfer_ownership(
+                context.as_ref(),
+                config.misc.contract_names.identity_manager_impl_v1(),
+                identity_manager.proxy_deployment.address,
+                new_owner,
+            )
+            .await?;
+        }
+
+        world_id_router.pending_owner = Some(new_owner);
+
+        write_report(&context, &config, &world_id_router).await?;
+
+        warn!("The contracts use two step ownership transfers - {new_owner:?} has to call acceptOwnership() on the router and identity managers to become their owner");
+    }
+
+    info!("Deployment decommissioned");
+
+    Ok(())
+}
+
+/// Writes the report with the given router and everything else as it was
+async fn write_report(
+    context: &Arc<DeploymentContext>,
+    config: &Arc<Config>,
+    world_id_router: &WorldIdRouterDeployment,
+) -> eyre::Result<()> {
+    let report = &context.report;
+
+    assemble_report(
+        context.clone(),
+        config.clone(),
+        report.insertion_verifiers.as_ref(),
+        report.deletion_verifiers.as_ref(),
+        report.lookup_tables.as_ref(),
+        report.semaphore_verifier.as_ref(),
+        report.identity_managers.as_ref(),
+        Some(world_id_router),
+        report.timelock.as_ref(),
+    )
+    .await
+}
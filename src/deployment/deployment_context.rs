@@ -1,27 +1,66 @@
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::sync::atomic::AtomicU64;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
-use ethers::types::Address;
+use ethers::abi::{encode, Abi, Token};
+use ethers::providers::Middleware;
+use ethers::types::{Address, Bytes, TransactionReceipt, H256};
+use ethers::utils::keccak256;
+use eyre::{Context, ContextCompat};
 use reqwest::Url;
+use tokio::io::AsyncWriteExt;
+use tracing::{debug, info, warn};
 
 use crate::cli::{Args, PrivateKey};
 use crate::common_keys::RpcSigner;
+use crate::ethers_utils::await_confirmed;
+use crate::forge_utils::build::ForgeBuild;
 use crate::forge_utils::verify::ForgeVerify;
-use crate::forge_utils::{ContractSpec, ForgeCreate};
+use crate::forge_utils::{
+    ContractSpec, ForgeCreate, ForgeInspectAbi, ForgeOutput,
+};
+use crate::report::costs::{DeploymentCosts, TransactionCost};
 use crate::report::Report;
+use crate::types::GroupId;
+
+/// Where the world id contracts are built and inspected
+const WORLD_ID_CONTRACTS_DIR: &str = "./world-id-contracts";
+
+const MAINNET_CHAIN_ID: u64 = 1;
+const MAINNET_IMPL_CONFIRMATIONS: u64 = 3;
 
 #[derive(Debug)]
 pub struct DeploymentContext {
     pub deployment_dir: PathBuf,
     pub cache_dir: PathBuf,
-    pub nonce: AtomicU64,
+    pub report_path: PathBuf,
+    pub chain_id: u64,
+    pub deployer: Address,
+    pub nonce: Arc<AtomicU64>,
     pub report: Report,
     pub private_key: PrivateKey,
     pub rpc_signer: Arc<RpcSigner>,
     pub rpc_url: Url,
     pub etherscan_api_key: Option<String>,
+    /// Factory for deterministic deployments, instead of forge's default
+    pub create2_factory: Option<Address>,
+    /// Extra remappings passed to every forge invocation
+    pub remappings: Vec<String>,
+    /// Constructor args from the config, keyed by contract name
+    pub constructor_args: HashMap<String, Vec<String>>,
     pub cmd: Args,
+    pub costs: Mutex<DeploymentCosts>,
+    pub cost_scope: Mutex<CostScope>,
+    /// ABIs inspected so far, keyed by working directory and contract
+    pub abi_cache: Mutex<HashMap<(PathBuf, ContractSpec), Abi>>,
+}
+
+/// What the costs of transactions are currently attributed to
+#[derive(Debug, Default)]
+pub struct CostScope {
+    pub step: Option<&'static str>,
+    pub group_id: Option<GroupId>,
 }
 
 impl DeploymentContext {
@@ -29,15 +68,222 @@ impl DeploymentContext {
         self.nonce.fetch_add(1, std::sync::atomic::Ordering::SeqCst)
     }
 
+    /// Attributes the costs of subsequent transactions to `step`
+    pub fn set_cost_step(&self, step: &'static str) {
+        let mut cost_scope = self.cost_scope.lock().unwrap();
+
+        cost_scope.step = Some(step);
+        cost_scope.group_id = None;
+    }
+
+    /// Attributes the costs of subsequent transactions to `group_id`,
+    /// until reset with `None`
+    pub fn set_cost_group(&self, group_id: Option<GroupId>) {
+        self.cost_scope.lock().unwrap().group_id = group_id;
+    }
+
+    /// The step currently being run, if any
+    pub fn current_step(&self) -> Option<&'static str> {
+        self.cost_scope.lock().unwrap().step
+    }
+
+    /// Whether transactions of the current step are dumped for offline
+    /// signing instead of being sent
+    pub fn dumps_transactions(&self) -> bool {
+        let Some(dump_tx_for) = self.cmd.dump_tx_for.as_deref() else {
+            return false;
+        };
+
+        self.current_step() == Some(dump_tx_for)
+    }
+
+    /// Waits for `--tx-delay-ms` before a transaction is sent
+    pub async fn throttle(&self) {
+        if let Some(tx_delay) = self.cmd.tx_delay() {
+            debug!("Throttling the next transaction by {tx_delay:?}");
+            tokio::time::sleep(tx_delay).await;
+        }
+    }
+
+    pub fn dump_path(&self, step: &str) -> PathBuf {
+        self.deployment_dir.join(format!("{step}.txs"))
+    }
+
+    /// Removes the dump file of `step`, so transactions of an earlier dump
+    /// aren't broadcast along with the new ones
+    pub async fn clear_dump(&self, step: &str) -> eyre::Result<()> {
+        let dump_path = self.dump_path(step);
+
+        if dump_path.exists() {
+            tokio::fs::remove_file(&dump_path)
+                .await
+                .with_context(|| format!("Removing {}", dump_path.display()))?;
+        }
+
+        Ok(())
+    }
+
+    /// Appends a signed raw transaction to the dump file of the current step
+    pub async fn dump_transaction(&self, raw_tx: Bytes) -> eyre::Result<()> {
+        let step = self.cost_scope.lock().unwrap().step.unwrap_or_default();
+        let dump_path = self.dump_path(step);
+
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&dump_path)
+            .await?;
+
+        file.write_all(format!("{raw_tx}\n").as_bytes()).await?;
+
+        info!("Dumped transaction to {}", dump_path.display());
+
+        Ok(())
+    }
+
+    pub fn record_cost(&self, receipt: &TransactionReceipt) {
+        let gas_used = receipt.gas_used.unwrap_or_default();
+
+        let Some(effective_gas_price) = receipt.effective_gas_price else {
+            warn!(
+                "Receipt of {:?} has no effective gas price - its cost isn't accounted for",
+                receipt.transaction_hash
+            );
+            return;
+        };
+
+        let cost_scope = self.cost_scope.lock().unwrap();
+
+        self.costs.lock().unwrap().add(TransactionCost {
+            transaction_hash: receipt.transaction_hash,
+            step: cost_scope.step.map(ToString::to_string),
+            group_id: cost_scope.group_id,
+            gas_used,
+            effective_gas_price,
+            cost: gas_used * effective_gas_price,
+        });
+    }
+
+    pub async fn record_create_cost(
+        &self,
+        output: &ForgeOutput,
+    ) -> eyre::Result<()> {
+        if output.simulated {
+            return Ok(());
+        }
+
+        let receipt = self
+            .rpc_signer
+            .0
+            .get_transaction_receipt(output.transaction_hash)
+            .await?
+            .with_context(|| {
+                format!("Missing receipt for {:?}", output.transaction_hash)
+            })?;
+
+        self.record_cost(&receipt);
+
+        Ok(())
+    }
+
     pub fn cache_path(&self, path: impl AsRef<Path>) -> PathBuf {
         self.cache_dir.join(path)
     }
 
+    /// Confirmations required between deploying an implementation and
+    /// referencing it from its proxy
+    pub fn impl_confirmations(&self) -> u64 {
+        self.cmd.impl_confirmations.unwrap_or(
+            if self.chain_id == MAINNET_CHAIN_ID {
+                MAINNET_IMPL_CONFIRMATIONS
+            } else {
+                1
+            },
+        )
+    }
+
+    /// Waits until the implementation deployment is final enough that a reorg
+    /// can't invalidate its address
+    pub async fn await_impl_deployment(
+        &self,
+        impl_deployment: &ForgeOutput,
+    ) -> eyre::Result<()> {
+        if impl_deployment.simulated {
+            return Ok(());
+        }
+
+        await_confirmed(
+            &self.rpc_signer,
+            impl_deployment.transaction_hash,
+            self.impl_confirmations(),
+            self.cmd.tx_timeout(),
+        )
+        .await?;
+
+        Ok(())
+    }
+
     pub fn forge_create(&self, contract_spec: ContractSpec) -> ForgeCreate {
+        let extra_constructor_args =
+            self.constructor_args.get(&contract_spec.name).cloned();
+
+        let contract_name = contract_spec.name.clone();
+
         let mut forge_create = ForgeCreate::new(contract_spec)
             .with_private_key(self.private_key.clone())
             .with_rpc_url(self.rpc_url.to_string())
-            .with_override_nonce(self.next_nonce());
+            .with_override_nonce(self.next_nonce())
+            .with_nonce_counter(self.nonce.clone())
+            .with_receipt_address(self.cmd.use_receipt_address)
+            .with_max_retries(self.cmd.max_retries_per_tx)
+            .with_receipt_timeout(self.cmd.tx_timeout())
+            .with_legacy(self.cmd.legacy);
+
+        if let Some(tx_delay) = self.cmd.tx_delay() {
+            forge_create = forge_create.with_delay(tx_delay);
+        }
+
+        // forge broadcasts creates itself, they'd be sent for real in
+        // between the dumped transactions and never reach the report
+        if self.dumps_transactions() {
+            forge_create = forge_create.with_broadcast_refused(format!(
+                "Step {} would create {contract_name}, but creates can't be dumped - only steps whose contracts are already in the report can be dumped",
+                self.current_step().unwrap_or_default()
+            ));
+        }
+
+        if let Some(create2_factory) = self.create2_factory {
+            forge_create = forge_create.with_create2_deployer(create2_factory);
+        }
+
+        if let Some(extra_constructor_args) = extra_constructor_args {
+            forge_create = forge_create
+                .with_extra_constructor_args(extra_constructor_args);
+        }
+
+        for rpc_header in &self.cmd.rpc_headers {
+            forge_create = forge_create.with_rpc_header(rpc_header.clone());
+        }
+
+        if let Some(gas_price) = self.cmd.create_gas_price {
+            forge_create = forge_create.with_gas_price(gas_price.into());
+        }
+
+        if let Some(max_fee_per_gas) = self.cmd.create_max_fee_per_gas {
+            forge_create =
+                forge_create.with_max_fee_per_gas(max_fee_per_gas.into());
+        }
+
+        if let Some(max_priority_fee_per_gas) =
+            self.cmd.create_max_priority_fee_per_gas
+        {
+            forge_create = forge_create
+                .with_max_priority_fee_per_gas(max_priority_fee_per_gas.into());
+        }
+
+        if let Some(gas_limit) = self.cmd.create_gas_limit {
+            forge_create = forge_create.with_gas_limit(gas_limit.into());
+        }
 
         if let Some(etherscan_api_key) = self.etherscan_api_key.as_ref() {
             forge_create = forge_create
@@ -52,15 +298,160 @@ impl DeploymentContext {
             forge_create = forge_create.with_verifier_url(verifier_url.clone());
         }
 
+        for remapping in &self.remappings {
+            forge_create = forge_create.with_remapping(remapping);
+        }
+
+        for forge_env in &self.cmd.forge_env {
+            forge_create =
+                forge_create.with_env(&forge_env.key, &forge_env.value);
+        }
+
         forge_create
     }
 
+    /// Like [`Self::forge_create`], but creates the contract deterministically
+    /// if `--create2-salt` is set
+    ///
+    /// Only for contracts without an owner, as a contract created through the
+    /// CREATE2 factory would be owned by the factory. The salt is derived per
+    /// `role` and group, so identical contracts don't collide.
+    pub fn forge_create_deterministic(
+        &self,
+        contract_spec: ContractSpec,
+        role: &str,
+        group_id: Option<GroupId>,
+    ) -> ForgeCreate {
+        let forge_create = self.forge_create(contract_spec);
+
+        match self.cmd.create2_salt {
+            Some(salt) => forge_create
+                .with_salt(derive_create2_salt(salt, role, group_id)),
+            None => forge_create,
+        }
+    }
+
+    pub fn forge_build(&self) -> ForgeBuild {
+        let mut forge_build = ForgeBuild::new();
+
+        for remapping in &self.remappings {
+            forge_build = forge_build.with_remapping(remapping);
+        }
+
+        for forge_env in &self.cmd.forge_env {
+            forge_build =
+                forge_build.with_env(&forge_env.key, &forge_env.value);
+        }
+
+        forge_build
+    }
+
+    pub fn forge_inspect_abi(
+        &self,
+        contract_spec: ContractSpec,
+    ) -> ForgeInspectAbi {
+        let mut forge_inspect_abi = ForgeInspectAbi::new(contract_spec);
+
+        for remapping in &self.remappings {
+            forge_inspect_abi = forge_inspect_abi.with_remapping(remapping);
+        }
+
+        for forge_env in &self.cmd.forge_env {
+            forge_inspect_abi =
+                forge_inspect_abi.with_env(&forge_env.key, &forge_env.value);
+        }
+
+        forge_inspect_abi
+    }
+
+    /// The ABI of a world id contract, inspected once and then taken from
+    /// the cache
+    pub async fn inspect_abi(
+        &self,
+        contract_spec: ContractSpec,
+    ) -> eyre::Result<Abi> {
+        let key = (PathBuf::from(WORLD_ID_CONTRACTS_DIR), contract_spec);
+
+        if let Some(abi) = self.abi_cache.lock().unwrap().get(&key) {
+            return Ok(abi.clone());
+        }
+
+        let abi = self
+            .forge_inspect_abi(key.1.clone())
+            .with_cwd(&key.0)
+            .run()
+            .await?;
+
+        self.abi_cache.lock().unwrap().insert(key, abi.clone());
+
+        Ok(abi)
+    }
+
     pub fn forge_verify(
         &self,
         contract_spec: ContractSpec,
         address: Address,
     ) -> ForgeVerify {
-        ForgeVerify::new(contract_spec, address)
+        let mut forge_verify = ForgeVerify::new(contract_spec, address)
             .with_etherscan_api_key(self.etherscan_api_key.clone().unwrap())
+            .with_retries(self.cmd.verify_retries)
+            .with_delay(self.cmd.verify_delay)
+            .with_timeout(self.cmd.verify_timeout());
+
+        for remapping in &self.remappings {
+            forge_verify = forge_verify.with_remapping(remapping);
+        }
+
+        for forge_env in &self.cmd.forge_env {
+            forge_verify =
+                forge_verify.with_env(&forge_env.key, &forge_env.value);
+        }
+
+        forge_verify
+    }
+}
+
+/// The CREATE2 salt of a single contract, derived from `--create2-salt`
+fn derive_create2_salt(
+    salt: H256,
+    role: &str,
+    group_id: Option<GroupId>,
+) -> H256 {
+    let mut tokens = vec![
+        Token::FixedBytes(salt.as_bytes().to_vec()),
+        Token::String(role.to_string()),
+    ];
+
+    if let Some(group_id) = group_id {
+        tokens.push(Token::Uint(group_id.0.into()));
+    }
+
+    H256(keccak256(encode(&tokens)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn create2_salts_differ_per_role_and_group() {
+        let salt = H256::repeat_byte(1);
+
+        let impl_group_0 = derive_create2_salt(
+            salt,
+            "identity_manager_impl_v1",
+            Some(GroupId(0)),
+        );
+        let impl_group_1 = derive_create2_salt(
+            salt,
+            "identity_manager_impl_v1",
+            Some(GroupId(1)),
+        );
+        let pairing = derive_create2_salt(salt, "pairing", None);
+
+        assert_ne!(impl_group_0, impl_group_1);
+        assert_ne!(impl_group_0, pairing);
+        assert_ne!(pairing, salt);
+        assert_eq!(pairing, derive_create2_salt(salt, "pairing", None));
     }
 }
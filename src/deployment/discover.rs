@@ -0,0 +1,376 @@
+use std::collections::{BTreeMap, HashMap};
+
+use ethers::abi::{Abi, Detokenize, RawLog, Tokenize};
+use ethers::contract::{decode_function_data, encode_function_data};
+use ethers::prelude::SignerMiddleware;
+use ethers::providers::{Middleware, Provider};
+use ethers::signers::Wallet;
+use ethers::types::{Address, Filter, TransactionRequest, U256};
+use eyre::{Context, ContextCompat};
+use tracing::{info, warn};
+
+use super::import::{
+    ImportedAddresses, ImportedGroup, ImportedIdentityManager,
+    ImportedVerifier, ImportedWorldIdRouter,
+};
+use crate::cli::{Args, DiscoverArgs};
+use crate::common_keys::{RpcSigner, RpcTransport};
+use crate::config::{Config, ContractNames, GroupConfig, MiscConfig};
+use crate::ethers_utils::{
+    ensure_has_code, initialized_version, proxy_implementation,
+};
+use crate::forge_utils::{ContractSpec, ForgeInspectAbi};
+use crate::serde_utils;
+use crate::types::{BatchSize, GroupId, TreeDepth};
+
+/// Events of the lookup table which name a batch size as their first param
+const LOOKUP_TABLE_EVENTS: [&str; 2] = ["VerifierAdded", "VerifierUpdated"];
+
+/// Reconstructs the config and the addresses of a deployment made outside of
+/// the deployer, starting from its router
+///
+/// Read-only. The addresses are written in the format of `import`, which
+/// then seeds the report.
+pub async fn run_discover(cmd: Args, args: DiscoverArgs) -> eyre::Result<()> {
+    let transport =
+        RpcTransport::connect(cmd.rpc_url()?, &cmd.rpc_headers).await?;
+    let wallet = Wallet::from(cmd.private_key()?.key.clone());
+    let signer = RpcSigner(
+        SignerMiddleware::new(Provider::new(transport), wallet).into(),
+    );
+
+    let abis = Abis::inspect(&cmd).await?;
+
+    let router = args.router;
+    ensure_has_code(&signer, router)
+        .await
+        .context("Validating the router")?;
+
+    let group_count: U256 =
+        view(&signer, &abis.router_impl, router, "groupCount", ()).await?;
+
+    let mut groups = HashMap::new();
+    let mut imported_groups = HashMap::new();
+    let mut insertion_verifiers = BTreeMap::new();
+    let mut deletion_verifiers = BTreeMap::new();
+
+    for group_id in 0..group_count.as_u64() {
+        // `routeFor` reverts for disabled groups
+        let Ok(identity_manager) = view::<Address>(
+            &signer,
+            &abis.router_impl,
+            router,
+            "routeFor",
+            group_id,
+        )
+        .await
+        else {
+            warn!("Group {group_id} is disabled on the router, skipping it");
+            continue;
+        };
+
+        let group_id = GroupId(group_id as usize);
+
+        info!("Discovering group {group_id} at {identity_manager:?}");
+
+        let group =
+            discover_group(&signer, &abis, identity_manager, args.from_block)
+                .await
+                .with_context(|| format!("Discovering group {group_id}"))?;
+
+        for (verifiers, entries) in [
+            (&mut insertion_verifiers, &group.insert_entries),
+            (&mut deletion_verifiers, &group.delete_entries),
+        ] {
+            for (batch_size, verifier) in entries {
+                let key = (group.tree_depth, *batch_size);
+                let existing = *verifiers.entry(key).or_insert(*verifier);
+
+                if existing != *verifier {
+                    warn!(
+                        "Group {group_id} uses {verifier:?} for tree depth {} and batch size {batch_size}, but {existing:?} was found first",
+                        group.tree_depth
+                    );
+                }
+            }
+        }
+
+        groups.insert(
+            group_id,
+            GroupConfig {
+                tree_depth: Some(group.tree_depth),
+                insertion_batch_sizes: group
+                    .insert_entries
+                    .keys()
+                    .copied()
+                    .collect(),
+                deletion_batch_sizes: group
+                    .delete_entries
+                    .keys()
+                    .copied()
+                    .collect(),
+                initial_root: None,
+            },
+        );
+
+        imported_groups.insert(group_id, group.imported);
+    }
+
+    let config = Config {
+        groups,
+        misc: MiscConfig::default(),
+    };
+
+    let imported = ImportedAddresses {
+        insertion_verifiers: imported_verifiers(insertion_verifiers),
+        deletion_verifiers: imported_verifiers(deletion_verifiers),
+        semaphore_verifier: None,
+        groups: imported_groups,
+        world_id_router: Some(ImportedWorldIdRouter {
+            proxy: router,
+            impl_v1: proxy_implementation(&signer, router).await?,
+        }),
+    };
+
+    serde_utils::write_serialize(&args.config_out, &config).await?;
+    serde_utils::write_serialize(&args.addresses_out, &imported).await?;
+
+    println!(
+        "Discovered {} groups behind router {router:?}",
+        config.groups.len()
+    );
+    println!("Config:    {}", args.config_out.display());
+    println!("Addresses: {}", args.addresses_out.display());
+
+    Ok(())
+}
+
+struct Abis {
+    router_impl: Abi,
+    identity_manager_impl_v1: Abi,
+    identity_manager_impl_v2: Abi,
+    verifier_lookup_table: Abi,
+}
+
+impl Abis {
+    async fn inspect(cmd: &Args) -> eyre::Result<Self> {
+        let contract_names = ContractNames::default();
+        let remappings = cmd.resolve_remappings().await?;
+
+        let inspect = |contract_spec: ContractSpec| {
+            let mut inspect_abi = ForgeInspectAbi::new(contract_spec)
+                .with_cwd("./world-id-contracts");
+
+            for remapping in &remappings {
+                inspect_abi = inspect_abi.with_remapping(remapping);
+            }
+
+            for forge_env in &cmd.forge_env {
+                inspect_abi =
+                    inspect_abi.with_env(&forge_env.key, &forge_env.value);
+            }
+
+            inspect_abi
+        };
+
+        Ok(Self {
+            router_impl: inspect(contract_names.router_impl()).run().await?,
+            identity_manager_impl_v1: inspect(
+                contract_names.identity_manager_impl_v1(),
+            )
+            .run()
+            .await?,
+            identity_manager_impl_v2: inspect(
+                contract_names.identity_manager_impl_v2(),
+            )
+            .run()
+            .await?,
+            verifier_lookup_table: inspect(
+                contract_names.verifier_lookup_table(),
+            )
+            .run()
+            .await?,
+        })
+    }
+}
+
+/// A group as found on-chain
+struct DiscoveredGroup {
+    tree_depth: TreeDepth,
+    insert_entries: BTreeMap<BatchSize, Address>,
+    delete_entries: BTreeMap<BatchSize, Address>,
+    imported: ImportedGroup,
+}
+
+async fn discover_group(
+    signer: &RpcSigner,
+    abis: &Abis,
+    identity_manager: Address,
+    from_block: u64,
+) -> eyre::Result<DiscoveredGroup> {
+    let v1_abi = &abis.identity_manager_impl_v1;
+
+    let tree_depth: U256 =
+        view(signer, v1_abi, identity_manager, "getTreeDepth", ()).await?;
+    let insert_lookup_table: Address = view(
+        signer,
+        v1_abi,
+        identity_manager,
+        "getRegisterIdentitiesVerifierLookupTable",
+        (),
+    )
+    .await?;
+    let update_lookup_table: Address = view(
+        signer,
+        v1_abi,
+        identity_manager,
+        "getIdentityUpdateVerifierLookupTable",
+        (),
+    )
+    .await?;
+
+    // Only V2 identity managers support deletions
+    let version = initialized_version(signer, identity_manager).await?;
+    let delete_lookup_table: Option<Address> = if version >= 2 {
+        Some(
+            view(
+                signer,
+                &abis.identity_manager_impl_v2,
+                identity_manager,
+                "getDeleteIdentitiesVerifierLookupTable",
+                (),
+            )
+            .await?,
+        )
+    } else {
+        None
+    };
+
+    let insert_entries =
+        lookup_table_entries(signer, abis, insert_lookup_table, from_block)
+            .await?;
+    let delete_entries = match delete_lookup_table {
+        Some(delete_lookup_table) => {
+            lookup_table_entries(signer, abis, delete_lookup_table, from_block)
+                .await?
+        }
+        None => BTreeMap::new(),
+    };
+
+    let implementation = proxy_implementation(signer, identity_manager).await?;
+
+    Ok(DiscoveredGroup {
+        tree_depth: TreeDepth(tree_depth.as_usize()),
+        insert_entries,
+        delete_entries,
+        imported: ImportedGroup {
+            insert_lookup_table: Some(insert_lookup_table),
+            update_lookup_table: Some(update_lookup_table),
+            delete_lookup_table,
+            identity_manager: Some(ImportedIdentityManager {
+                proxy: identity_manager,
+                impl_v1: (version < 2).then_some(implementation),
+                impl_v2: (version >= 2).then_some(implementation),
+            }),
+        },
+    })
+}
+
+/// The verifier of every batch size a lookup table currently has one for
+///
+/// The lookup table can't be enumerated, so the batch sizes are taken from
+/// its events and then checked against its current state.
+async fn lookup_table_entries(
+    signer: &RpcSigner,
+    abis: &Abis,
+    lookup_table: Address,
+    from_block: u64,
+) -> eyre::Result<BTreeMap<BatchSize, Address>> {
+    let abi = &abis.verifier_lookup_table;
+
+    let mut batch_sizes = vec![];
+
+    for event_name in LOOKUP_TABLE_EVENTS {
+        let event = abi.event(event_name)?;
+
+        let filter = Filter::new()
+            .address(lookup_table)
+            .topic0(event.signature())
+            .from_block(from_block);
+
+        let logs = signer.0.get_logs(&filter).await.with_context(|| {
+            format!("Fetching {event_name} events of {lookup_table:?}")
+        })?;
+
+        for log in logs {
+            let log = event.parse_log(RawLog {
+                topics: log.topics,
+                data: log.data.to_vec(),
+            })?;
+
+            let batch_size = log
+                .params
+                .first()
+                .and_then(|param| param.value.clone().into_uint())
+                .with_context(|| {
+                    format!("{event_name} event without a batch size")
+                })?;
+
+            batch_sizes.push(BatchSize(batch_size.as_usize()));
+        }
+    }
+
+    let mut entries = BTreeMap::new();
+
+    for batch_size in batch_sizes {
+        // `getVerifierFor` reverts for batch sizes which were disabled
+        if let Ok(verifier) = view::<Address>(
+            signer,
+            abi,
+            lookup_table,
+            "getVerifierFor",
+            batch_size.0 as u64,
+        )
+        .await
+        {
+            entries.insert(batch_size, verifier);
+        }
+    }
+
+    Ok(entries)
+}
+
+fn imported_verifiers(
+    verifiers: BTreeMap<(TreeDepth, BatchSize), Address>,
+) -> Vec<ImportedVerifier> {
+    verifiers
+        .into_iter()
+        .map(|((tree_depth, batch_size), address)| ImportedVerifier {
+            tree_depth,
+            batch_size,
+            address,
+        })
+        .collect()
+}
+
+/// Calls a view function of `to`
+async fn view<R: Detokenize>(
+    signer: &RpcSigner,
+    abi: &Abi,
+    to: Address,
+    function_name: &str,
+    args: impl Tokenize,
+) -> eyre::Result<R> {
+    let func = abi.function(function_name)?;
+    let call_data = encode_function_data(func, args)?;
+
+    let tx = TransactionRequest::new().to(to).data(call_data).into();
+
+    let output = signer
+        .0
+        .call(&tx, None)
+        .await
+        .with_context(|| format!("Calling {function_name} on {to:?}"))?;
+
+    Ok(decode_function_data(func, output, false)?)
+}
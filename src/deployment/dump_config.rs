@@ -0,0 +1,20 @@
+use crate::cli::{Args, ConfigFormat, DumpConfigArgs};
+use crate::config::Config;
+
+/// Prints the config after resolving aliases and defaults, i.e. the way the
+/// deployment sees it
+pub async fn run_dump_config(
+    cmd: Args,
+    args: DumpConfigArgs,
+) -> eyre::Result<()> {
+    let config = Config::load(cmd.config_path()?).await?;
+
+    let content = match args.format {
+        ConfigFormat::Yaml => serde_yaml::to_string(&config)?,
+        ConfigFormat::Json => serde_json::to_string_pretty(&config)?,
+    };
+
+    println!("{content}");
+
+    Ok(())
+}
@@ -0,0 +1,72 @@
+use std::path::PathBuf;
+
+use eyre::{Context, ContextCompat};
+use tracing::info;
+
+use super::mtb_utils::{
+    download_semaphore_mtb_binary, versioned_cache_dir, ProverMode,
+};
+use super::steps::verifiers::generate_verifier;
+use crate::cli::{Args, GenerateVerifiersArgs};
+use crate::config::Config;
+
+/// Generates the verifier contracts of every configured tree depth and batch
+/// size and copies them to the output directory, without touching the chain
+///
+/// Keys and contracts are generated into the deployment's cache, so they're
+/// reused by a later deployment.
+pub async fn run_generate_verifiers(
+    cmd: Args,
+    args: GenerateVerifiersArgs,
+) -> eyre::Result<()> {
+    let config = Config::load(cmd.config_path()?).await?;
+    let cache_dir = versioned_cache_dir(
+        &PathBuf::from(cmd.deployment_name()?).join(".cache"),
+        cmd.reuse_cache_from.as_deref(),
+    )
+    .await?;
+
+    tokio::fs::create_dir_all(&cache_dir).await?;
+    tokio::fs::create_dir_all(&args.out_dir)
+        .await
+        .with_context(|| format!("Creating {}", args.out_dir.display()))?;
+
+    download_semaphore_mtb_binary(&cache_dir).await?;
+
+    for mode in [ProverMode::Insertion, ProverMode::Deletion] {
+        let mut entries: Vec<_> = config
+            .unique_tree_depths_and_batch_sizes(mode)?
+            .into_iter()
+            .collect();
+        entries.sort();
+
+        for (tree_depth, batch_size) in entries {
+            let generated = generate_verifier(
+                &config,
+                &cache_dir,
+                cmd.mtb_setup_timeout(),
+                mode,
+                tree_depth,
+                batch_size,
+            )
+            .await?;
+
+            let file_name = generated
+                .verifier_contract
+                .file_name()
+                .context("Missing verifier contract file name")?;
+            let out_path = args.out_dir.join(file_name);
+
+            tokio::fs::copy(&generated.verifier_contract, &out_path)
+                .await
+                .with_context(|| format!("Writing {}", out_path.display()))?;
+
+            info!(
+                "Generated the {mode} verifier for tree depth {tree_depth} and batch size {batch_size}"
+            );
+            println!("{}", out_path.display());
+        }
+    }
+
+    Ok(())
+}
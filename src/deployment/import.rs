@@ -0,0 +1,292 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use ethers::types::Address;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use super::router_routes::read_routes;
+use super::steps::assemble_report::{assemble_report, REPORT_PATH};
+use super::steps::identity_manager::{
+    WorldIDIdentityManagersDeployment, WorldIdIdentityManagerDeployment,
+};
+use super::steps::lookup_tables::{
+    GroupLookupTables, LookupTable, LookupTables,
+};
+use super::steps::semaphore_verifier::SemaphoreVerifierDeployment;
+use super::steps::verifiers::{VerifierDeployment, Verifiers};
+use super::steps::world_id_router::WorldIdRouterDeployment;
+use super::{init_context, ChainAccess, DeploymentContext};
+use crate::cli::{Args, ImportArgs};
+use crate::ethers_utils::{ensure_has_code, initialized_version};
+use crate::report::contract_deployment::ContractDeployment;
+use crate::serde_utils;
+use crate::types::{BatchSize, GroupId, TreeDepth};
+
+/// Addresses of a deployment made outside of the deployer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportedAddresses {
+    #[serde(default)]
+    pub insertion_verifiers: Vec<ImportedVerifier>,
+    #[serde(default)]
+    pub deletion_verifiers: Vec<ImportedVerifier>,
+    #[serde(default)]
+    pub semaphore_verifier: Option<ImportedSemaphoreVerifier>,
+    #[serde(default)]
+    pub groups: HashMap<GroupId, ImportedGroup>,
+    #[serde(default)]
+    pub world_id_router: Option<ImportedWorldIdRouter>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportedVerifier {
+    pub tree_depth: TreeDepth,
+    pub batch_size: BatchSize,
+    pub address: Address,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportedSemaphoreVerifier {
+    pub verifier: Address,
+    pub pairing: Address,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportedGroup {
+    pub insert_lookup_table: Option<Address>,
+    pub update_lookup_table: Option<Address>,
+    pub delete_lookup_table: Option<Address>,
+    pub identity_manager: Option<ImportedIdentityManager>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportedIdentityManager {
+    pub proxy: Address,
+    pub impl_v1: Option<Address>,
+    pub impl_v2: Option<Address>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportedWorldIdRouter {
+    pub proxy: Address,
+    pub impl_v1: Address,
+}
+
+/// Creates a report for an existing deployment from a file of addresses,
+/// so that subsequent runs treat those contracts as already deployed
+pub async fn run_import(cmd: Args, args: ImportArgs) -> eyre::Result<()> {
+    let report_path = PathBuf::from(cmd.deployment_name()?).join(REPORT_PATH);
+    if report_path.exists() {
+        eyre::bail!("A report already exists at {}", report_path.display());
+    }
+
+    let imported: ImportedAddresses =
+        serde_utils::read_deserialize(&args.file).await?;
+
+    let (context, config) = init_context(cmd, ChainAccess::Read).await?;
+
+    let insertion_verifiers =
+        import_verifiers(context.as_ref(), &imported.insertion_verifiers)
+            .await?;
+    let deletion_verifiers =
+        import_verifiers(context.as_ref(), &imported.deletion_verifiers)
+            .await?;
+
+    let semaphore_verifier = match imported.semaphore_verifier.as_ref() {
+        Some(semaphore_verifier) => Some(SemaphoreVerifierDeployment {
+            verifier_deployment: Some(
+                import_contract(context.as_ref(), semaphore_verifier.verifier)
+                    .await?,
+            ),
+            pairing_deployment: import_contract(
+                context.as_ref(),
+                semaphore_verifier.pairing,
+            )
+            .await?,
+        }),
+        None => None,
+    };
+
+    let mut lookup_tables = LookupTables::default();
+    let mut identity_managers = WorldIDIdentityManagersDeployment::default();
+
+    for (group_id, group) in &imported.groups {
+        let group_lookup_tables = GroupLookupTables {
+            insert: import_lookup_table(
+                context.as_ref(),
+                group.insert_lookup_table,
+            )
+            .await?,
+            update: import_lookup_table(
+                context.as_ref(),
+                group.update_lookup_table,
+            )
+            .await?,
+            delete: import_lookup_table(
+                context.as_ref(),
+                group.delete_lookup_table,
+            )
+            .await?,
+        };
+
+        lookup_tables.groups.insert(*group_id, group_lookup_tables);
+
+        if let Some(identity_manager) = group.identity_manager.as_ref() {
+            let deployment = WorldIdIdentityManagerDeployment {
+                impl_v1_deployment: import_optional_contract(
+                    context.as_ref(),
+                    identity_manager.impl_v1,
+                )
+                .await?,
+                impl_v2_deployment: import_optional_contract(
+                    context.as_ref(),
+                    identity_manager.impl_v2,
+                )
+                .await?,
+                proxy_deployment: import_contract(
+                    context.as_ref(),
+                    identity_manager.proxy,
+                )
+                .await?,
+                initialized_version: Some(
+                    initialized_version(
+                        &context.rpc_signer,
+                        identity_manager.proxy,
+                    )
+                    .await?,
+                ),
+            };
+
+            identity_managers.groups.insert(*group_id, deployment);
+        }
+    }
+
+    let world_id_router = match imported.world_id_router.as_ref() {
+        Some(world_id_router) => {
+            let proxy_deployment =
+                import_contract(context.as_ref(), world_id_router.proxy)
+                    .await?;
+            let impl_v1_deployment =
+                import_contract(context.as_ref(), world_id_router.impl_v1)
+                    .await?;
+
+            // The routes are taken from the chain, so that later runs only
+            // change the ones which differ from the config
+            let entries =
+                read_routes(context.as_ref(), &config, world_id_router.proxy)
+                    .await?
+                    .into_iter()
+                    .filter_map(|(group_id, route)| {
+                        Some((group_id, route?.into()))
+                    })
+                    .collect();
+
+            Some(WorldIdRouterDeployment {
+                impl_v1_deployment,
+                proxy_deployment,
+                entries,
+                disabled: HashMap::new(),
+                decommissioned: false,
+                bootstrap_group: None,
+                pending_owner: None,
+                initialized_version: Some(
+                    initialized_version(
+                        &context.rpc_signer,
+                        world_id_router.proxy,
+                    )
+                    .await?,
+                ),
+            })
+        }
+        None => None,
+    };
+
+    let lookup_tables =
+        (!lookup_tables.groups.is_empty()).then_some(lookup_tables);
+    let identity_managers =
+        (!identity_managers.groups.is_empty()).then_some(identity_managers);
+
+    assemble_report(
+        context.clone(),
+        config,
+        insertion_verifiers.as_ref(),
+        deletion_verifiers.as_ref(),
+        lookup_tables.as_ref(),
+        semaphore_verifier.as_ref(),
+        identity_managers.as_ref(),
+        world_id_router.as_ref(),
+        None,
+    )
+    .await?;
+
+    info!("Imported deployment into {}", context.report_path.display());
+
+    Ok(())
+}
+
+async fn import_contract(
+    context: &DeploymentContext,
+    address: Address,
+) -> eyre::Result<ContractDeployment> {
+    ensure_has_code(&context.rpc_signer, address).await?;
+
+    Ok(ContractDeployment {
+        address,
+        simulated: false,
+        source_commit: None,
+        code_hash: None,
+        block_number: None,
+        salt: None,
+    })
+}
+
+async fn import_optional_contract(
+    context: &DeploymentContext,
+    address: Option<Address>,
+) -> eyre::Result<Option<ContractDeployment>> {
+    match address {
+        Some(address) => Ok(Some(import_contract(context, address).await?)),
+        None => Ok(None),
+    }
+}
+
+/// Lookup table entries are left empty - the lookup tables step checks the
+/// associated verifiers on-chain and only updates the ones which differ
+async fn import_lookup_table(
+    context: &DeploymentContext,
+    address: Option<Address>,
+) -> eyre::Result<Option<LookupTable>> {
+    Ok(import_optional_contract(context, address)
+        .await?
+        .map(|deployment| LookupTable {
+            deployment,
+            entries: HashMap::new(),
+        }))
+}
+
+async fn import_verifiers(
+    context: &DeploymentContext,
+    imported: &[ImportedVerifier],
+) -> eyre::Result<Option<Verifiers>> {
+    if imported.is_empty() {
+        return Ok(None);
+    }
+
+    let mut verifiers = Verifiers::default();
+
+    for verifier in imported {
+        let deployment = import_contract(context, verifier.address).await?;
+
+        verifiers.verifiers.insert(
+            (verifier.tree_depth, verifier.batch_size),
+            VerifierDeployment {
+                deployment,
+                tree_depth: Some(verifier.tree_depth),
+                source_hash: None,
+                keys_hash: None,
+            },
+        );
+    }
+
+    Ok(Some(verifiers))
+}
@@ -0,0 +1,263 @@
+use std::collections::HashMap;
+
+use ethers::abi::token::{LenientTokenizer, Tokenizer};
+use ethers::abi::{Function, Param, Token};
+use ethers::types::{Address, Bytes};
+use eyre::Context;
+
+/// Encodes a call to `func` matching its ABI inputs at runtime
+///
+/// Each argument is taken from `overrides` by its ABI input name and falls back
+/// to the `defaults` provided by the deployment step, keyed by ABI input name
+/// as well. Override values are either one of the well-known names in
+/// `known_addresses` (e.g. `insert_lookup_table`) or a literal parsed according
+/// to the input's type.
+///
+/// Errors if a default or an override names an argument `func` doesn't have,
+/// so renamed parameters are never silently assigned the wrong value.
+pub fn encode_initializer_call(
+    func: &Function,
+    mut defaults: HashMap<&str, Token>,
+    overrides: Option<&HashMap<String, String>>,
+    known_addresses: &HashMap<&str, Address>,
+) -> eyre::Result<Bytes> {
+    let has_input =
+        |name: &str| func.inputs.iter().any(|input| input.name == name);
+
+    for name in defaults.keys() {
+        if !has_input(name) {
+            eyre::bail!(
+                "Function {} has no argument named {name} - the deployer \
+                 doesn't support this version of the contract",
+                func.name
+            );
+        }
+    }
+
+    if let Some(overrides) = overrides {
+        for name in overrides.keys() {
+            if !has_input(name) {
+                eyre::bail!(
+                    "Function {} has no argument named {name}",
+                    func.name
+                );
+            }
+        }
+    }
+
+    let mut tokens = Vec::with_capacity(func.inputs.len());
+
+    for input in &func.inputs {
+        let default = defaults.remove(input.name.as_str());
+        let value = overrides.and_then(|overrides| overrides.get(&input.name));
+
+        let token = match (value, default) {
+            (Some(value), _) => parse_arg(input, value, known_addresses)
+                .with_context(|| {
+                    format!("Parsing argument {} of {}", input.name, func.name)
+                })?,
+            (None, Some(default)) => default,
+            (None, None) => eyre::bail!(
+                "Missing value for argument {} of {} - set it in the config",
+                input.name,
+                func.name
+            ),
+        };
+
+        tokens.push(token);
+    }
+
+    Ok(func.encode_input(&tokens)?.into())
+}
+
+/// Decodes `call_data` of `func` into a human readable
+/// `name(input = value, ...)`
+pub fn describe_call(
+    func: &Function,
+    call_data: &[u8],
+) -> eyre::Result<String> {
+    let input = call_data
+        .get(4..)
+        .ok_or_else(|| eyre::eyre!("Call data is missing the selector"))?;
+
+    let tokens = func
+        .decode_input(input)
+        .with_context(|| format!("Decoding call data of {}", func.name))?;
+
+    let args: Vec<_> = func
+        .inputs
+        .iter()
+        .zip(&tokens)
+        .map(|(input, token)| {
+            format!("{} = {}", input.name, format_token(token))
+        })
+        .collect();
+
+    Ok(format!("{}({})", func.name, args.join(", ")))
+}
+
+fn format_token(token: &Token) -> String {
+    match token {
+        Token::Address(address) => format!("{address:?}"),
+        Token::Uint(value) | Token::Int(value) => value.to_string(),
+        token => token.to_string(),
+    }
+}
+
+/// Parses `value` according to the type of `input`, resolving the names of
+/// `known_addresses`
+pub fn parse_arg(
+    input: &Param,
+    value: &str,
+    known_addresses: &HashMap<&str, Address>,
+) -> eyre::Result<Token> {
+    if let Some(address) = known_addresses.get(value) {
+        return Ok(Token::Address(*address));
+    }
+
+    Ok(LenientTokenizer::tokenize(&input.kind, value)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::abi::Abi;
+    use ethers::types::U256;
+
+    use super::*;
+
+    const ABI: &str = r#"[{
+        "type": "function",
+        "name": "initialize",
+        "stateMutability": "nonpayable",
+        "inputs": [
+            { "name": "treeDepth", "type": "uint8" },
+            { "name": "verifier", "type": "address" },
+            { "name": "newParam", "type": "uint256" }
+        ],
+        "outputs": []
+    }]"#;
+
+    #[test]
+    fn overrides_and_known_addresses() {
+        let abi: Abi = serde_json::from_str(ABI).unwrap();
+        let func = abi.function("initialize").unwrap();
+
+        let verifier = Address::repeat_byte(0x11);
+        let known_addresses = maplit::hashmap! {
+            "semaphore_verifier" => verifier,
+        };
+
+        let overrides = maplit::hashmap! {
+            "verifier".to_string() => "semaphore_verifier".to_string(),
+            "newParam".to_string() => "42".to_string(),
+        };
+
+        let actual = encode_initializer_call(
+            func,
+            maplit::hashmap! {
+                "treeDepth" => Token::Uint(U256::from(30)),
+                "verifier" => Token::Address(Address::zero()),
+            },
+            Some(&overrides),
+            &known_addresses,
+        )
+        .unwrap();
+
+        let expected = func
+            .encode_input(&[
+                Token::Uint(U256::from(30)),
+                Token::Address(verifier),
+                Token::Uint(U256::from(42)),
+            ])
+            .unwrap();
+
+        assert_eq!(actual.to_vec(), expected);
+    }
+
+    #[test]
+    fn describe_initializer_call() {
+        let abi: Abi = serde_json::from_str(ABI).unwrap();
+        let func = abi.function("initialize").unwrap();
+
+        let call_data = func
+            .encode_input(&[
+                Token::Uint(U256::from(30)),
+                Token::Address(Address::repeat_byte(0x11)),
+                Token::Uint(U256::from(42)),
+            ])
+            .unwrap();
+
+        assert_eq!(
+            describe_call(func, &call_data).unwrap(),
+            "initialize(treeDepth = 30, verifier = 0x1111111111111111111111111111111111111111, newParam = 42)"
+        );
+    }
+
+    #[test]
+    fn missing_new_argument() {
+        let abi: Abi = serde_json::from_str(ABI).unwrap();
+        let func = abi.function("initialize").unwrap();
+
+        let result = encode_initializer_call(
+            func,
+            maplit::hashmap! {
+                "treeDepth" => Token::Uint(U256::from(30)),
+                "verifier" => Token::Address(Address::zero()),
+            },
+            None,
+            &HashMap::new(),
+        );
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn defaults_are_matched_by_name() {
+        let abi: Abi = serde_json::from_str(ABI).unwrap();
+        let func = abi.function("initialize").unwrap();
+
+        let verifier = Address::repeat_byte(0x11);
+
+        let actual = encode_initializer_call(
+            func,
+            maplit::hashmap! {
+                "newParam" => Token::Uint(U256::from(42)),
+                "verifier" => Token::Address(verifier),
+                "treeDepth" => Token::Uint(U256::from(30)),
+            },
+            None,
+            &HashMap::new(),
+        )
+        .unwrap();
+
+        let expected = func
+            .encode_input(&[
+                Token::Uint(U256::from(30)),
+                Token::Address(verifier),
+                Token::Uint(U256::from(42)),
+            ])
+            .unwrap();
+
+        assert_eq!(actual.to_vec(), expected);
+    }
+
+    #[test]
+    fn unknown_default() {
+        let abi: Abi = serde_json::from_str(ABI).unwrap();
+        let func = abi.function("initialize").unwrap();
+
+        let result = encode_initializer_call(
+            func,
+            maplit::hashmap! {
+                "treeDepth" => Token::Uint(U256::from(30)),
+                "verifier" => Token::Address(Address::zero()),
+                "newParam" => Token::Uint(U256::from(42)),
+                "renamedParam" => Token::Uint(U256::from(1)),
+            },
+            None,
+            &HashMap::new(),
+        );
+
+        assert!(result.is_err());
+    }
+}
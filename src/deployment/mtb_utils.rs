@@ -1,28 +1,165 @@
 use std::ffi::OsStr;
 use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
+use ethers::utils::{hex, keccak256};
+use eyre::Context;
+use serde::{Deserialize, Serialize};
 use strum::{Display, EnumString};
-use tracing::instrument;
+use tracing::{info, instrument, warn};
 
-use crate::config::Config;
-use crate::deployment::DeploymentContext;
+use super::{KEYS_DIR, VERIFIER_CONTRACTS_DIR, VERIFIER_SOURCES_DIR};
+use crate::serde_utils;
 use crate::types::{BatchSize, TreeDepth};
+use crate::utils::git_head;
 
 pub const MTB_BIN: &str = "mtb";
+pub const MTB_VERSION: &str = "1.2.1";
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumString, Display)]
+/// Length of the contracts revision in cache directory names
+const CACHE_REV_LEN: usize = 12;
+
+/// `--reuse-cache-from` value selecting the unversioned cache of older
+/// deployer versions
+pub const LEGACY_CACHE: &str = "legacy";
+
+/// Rough number of constraints per inserted or deleted leaf and tree level
+const CONSTRAINTS_PER_LEAF_LEVEL: u64 = 600;
+
+/// Rough memory the setup needs per constraint
+const SETUP_BYTES_PER_CONSTRAINT: u64 = 4 * 1024;
+
+/// Rough duration of the setup per constraint, until setups were recorded
+const SETUP_NANOS_PER_CONSTRAINT: u64 = 300_000;
+
+/// Rough size of the keys per constraint, until setups were recorded
+const KEYS_BYTES_PER_CONSTRAINT: u64 = 512;
+
+/// Rough size of an exported verifier contract - it only holds the
+/// verifying key, so it barely depends on the tree depth and batch size
+pub const ESTIMATED_VERIFIER_CONTRACT_BYTES: u64 = 16 * 1024;
+
+/// File in the keys directory recording the past setups
+const SETUP_TIMINGS_FILE: &str = "setup_timings.yml";
+
+/// Serializes MTB downloads & runs, so concurrent (e.g. multi-chain)
+/// deployments sharing a cache don't write the same files at the same time
+static MTB_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    EnumString,
+    Display,
+    Serialize,
+    Deserialize,
+)]
 #[strum(serialize_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
 pub enum ProverMode {
     Insertion,
     Deletion,
 }
 
+/// The cache directory for the current MTB version and contracts revision
+///
+/// Keys and verifier contracts generated by other versions are kept in their
+/// own directories instead of being reused. `reuse_cache_from` selects the
+/// cache of another contracts revision (or a prefix of it) instead, or the
+/// unversioned cache with [`LEGACY_CACHE`].
+pub async fn versioned_cache_dir(
+    cache_dir: &Path,
+    reuse_cache_from: Option<&str>,
+) -> eyre::Result<PathBuf> {
+    let prefix = format!("mtb-{MTB_VERSION}_");
+
+    if reuse_cache_from == Some(LEGACY_CACHE) {
+        return Ok(cache_dir.to_path_buf());
+    }
+
+    if let Some(rev) = reuse_cache_from {
+        let rev: String = rev.chars().take(CACHE_REV_LEN).collect();
+        let name_prefix = format!("{prefix}{rev}");
+
+        let mut matches = vec![];
+        let mut entries = tokio::fs::read_dir(cache_dir)
+            .await
+            .with_context(|| format!("Reading {}", cache_dir.display()))?;
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name().to_string_lossy().into_owned();
+
+            if name.starts_with(&name_prefix) {
+                matches.push(entry.path());
+            }
+        }
+
+        return match matches.as_slice() {
+            [dir] => Ok(dir.clone()),
+            [] => eyre::bail!("No cache found for revision {rev}"),
+            _ => eyre::bail!("Revision {rev} matches multiple caches"),
+        };
+    }
+
+    let rev = match git_head("./world-id-contracts").await {
+        Ok(rev) => rev,
+        Err(err) => {
+            warn!("Failed to read the contracts revision: {err}");
+            "unversioned".to_string()
+        }
+    };
+
+    let rev = &rev[..rev.len().min(CACHE_REV_LEN)];
+    let versioned = cache_dir.join(format!("{prefix}{rev}"));
+
+    migrate_legacy_cache(cache_dir, &versioned).await?;
+
+    Ok(versioned)
+}
+
+/// Moves the keys and verifier contracts of an unversioned cache into the
+/// versioned one, unless it exists already - so upgrading the deployer
+/// doesn't regenerate them
+async fn migrate_legacy_cache(
+    cache_dir: &Path,
+    versioned: &Path,
+) -> eyre::Result<()> {
+    if versioned.exists() {
+        return Ok(());
+    }
+
+    for dir in [KEYS_DIR, VERIFIER_CONTRACTS_DIR, VERIFIER_SOURCES_DIR] {
+        let legacy = cache_dir.join(dir);
+
+        if !legacy.exists() {
+            continue;
+        }
+
+        tokio::fs::create_dir_all(versioned).await?;
+
+        info!(
+            "Moving the unversioned {} into {}",
+            legacy.display(),
+            versioned.display()
+        );
+
+        tokio::fs::rename(&legacy, versioned.join(dir))
+            .await
+            .with_context(|| format!("Migrating {}", legacy.display()))?;
+    }
+
+    Ok(())
+}
+
 #[instrument(skip_all)]
 pub async fn download_semaphore_mtb_binary(
-    context: &DeploymentContext,
-    _config: &Config,
+    cache_dir: impl AsRef<Path>,
 ) -> eyre::Result<()> {
-    let mtb_bin = context.cache_path(MTB_BIN);
+    let mtb_bin = cache_dir.as_ref().join(MTB_BIN);
+
+    let _guard = MTB_LOCK.lock().await;
 
     if mtb_bin.exists() {
         return Ok(());
@@ -51,7 +188,6 @@ pub async fn download_semaphore_mtb_binary(
 
     const MTB_RELEASES_URL: &str =
         "https://github.com/worldcoin/semaphore-mtb/releases/download";
-    const MTB_VERSION: &str = "1.2.1";
 
     let url = format!("{MTB_RELEASES_URL}/{MTB_VERSION}/mtb-{os}-{arch}");
 
@@ -83,32 +219,165 @@ pub async fn download_semaphore_mtb_binary(
     Ok(())
 }
 
-#[instrument(skip(mtb_binary, keys_dir))]
+/// Estimated memory needed to set up the keys for `tree_depth` and
+/// `batch_size`
+///
+/// This is only an order of magnitude, the actual usage depends on the MTB
+/// version and the mode.
+pub fn estimated_setup_memory(
+    tree_depth: TreeDepth,
+    batch_size: BatchSize,
+) -> u64 {
+    estimated_constraints(tree_depth, batch_size) * SETUP_BYTES_PER_CONSTRAINT
+}
+
+fn estimated_constraints(tree_depth: TreeDepth, batch_size: BatchSize) -> u64 {
+    batch_size.0 as u64 * tree_depth.0 as u64 * CONSTRAINTS_PER_LEAF_LEVEL
+}
+
+/// How long a past MTB setup took and how large its keys were
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SetupTiming {
+    pub mode: ProverMode,
+    pub tree_depth: TreeDepth,
+    pub batch_size: BatchSize,
+    pub duration_secs: f64,
+    pub keys_bytes: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SetupEstimate {
+    pub duration: Duration,
+    pub keys_bytes: u64,
+    /// Whether the estimate is based on recorded setups rather than the
+    /// built-in model
+    pub from_timings: bool,
+}
+
+/// Estimates the duration and keys size of a setup
+///
+/// Scales the recorded setup of the same mode which is closest in size, as
+/// both grow roughly linearly with the number of constraints. Without
+/// recorded setups a rough built-in model is used.
+pub fn estimate_setup(
+    tree_depth: TreeDepth,
+    batch_size: BatchSize,
+    mode: ProverMode,
+    timings: &[SetupTiming],
+) -> SetupEstimate {
+    let constraints = estimated_constraints(tree_depth, batch_size);
+
+    let closest = timings
+        .iter()
+        .filter(|timing| timing.mode == mode)
+        .min_by_key(|timing| {
+            estimated_constraints(timing.tree_depth, timing.batch_size)
+                .abs_diff(constraints)
+        });
+
+    match closest {
+        Some(timing) => {
+            let ratio = constraints as f64
+                / estimated_constraints(timing.tree_depth, timing.batch_size)
+                    as f64;
+
+            SetupEstimate {
+                duration: Duration::from_secs_f64(timing.duration_secs * ratio),
+                keys_bytes: (timing.keys_bytes as f64 * ratio) as u64,
+                from_timings: true,
+            }
+        }
+        None => SetupEstimate {
+            duration: Duration::from_nanos(
+                constraints * SETUP_NANOS_PER_CONSTRAINT,
+            ),
+            keys_bytes: constraints * KEYS_BYTES_PER_CONSTRAINT,
+            from_timings: false,
+        },
+    }
+}
+
+/// The setups recorded in `keys_dir`, empty if there are none
+pub async fn read_setup_timings(
+    keys_dir: impl AsRef<Path>,
+) -> eyre::Result<Vec<SetupTiming>> {
+    let path = keys_dir.as_ref().join(SETUP_TIMINGS_FILE);
+
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+
+    serde_utils::read_deserialize(path).await
+}
+
+/// Records a setup, replacing an earlier one with the same parameters
+async fn record_setup_timing(
+    keys_dir: &Path,
+    timing: SetupTiming,
+) -> eyre::Result<()> {
+    let mut timings = read_setup_timings(keys_dir).await?;
+
+    timings.retain(|recorded| {
+        (recorded.mode, recorded.tree_depth, recorded.batch_size)
+            != (timing.mode, timing.tree_depth, timing.batch_size)
+    });
+    timings.push(timing);
+
+    serde_utils::write_serialize(keys_dir.join(SETUP_TIMINGS_FILE), timings)
+        .await
+}
+
+/// Warns if the setup for `tree_depth` and `batch_size` likely needs more
+/// memory than the machine has
+fn check_setup_memory(tree_depth: TreeDepth, batch_size: BatchSize) {
+    let mem_info = match sys_info::mem_info() {
+        Ok(mem_info) => mem_info,
+        Err(err) => {
+            warn!("Failed to read the available memory: {err}");
+            return;
+        }
+    };
+
+    let estimated = estimated_setup_memory(tree_depth, batch_size);
+    // Reported in KiB
+    let total = mem_info.total * 1024;
+
+    if estimated > total {
+        warn!(
+            "The MTB setup for tree depth {tree_depth} and batch size {batch_size} likely needs around {} GiB of memory, but only {} GiB are available",
+            estimated >> 30,
+            total >> 30,
+        );
+    }
+}
+
+#[instrument(skip(mtb_binary, keys_dir, timeout))]
 pub async fn generate_keys(
     mtb_binary: impl AsRef<OsStr>,
     keys_dir: impl AsRef<Path>,
     tree_depth: TreeDepth,
     batch_size: BatchSize,
     mode: ProverMode,
+    setup_args: &[String],
+    timeout: Option<Duration>,
 ) -> eyre::Result<PathBuf> {
-    let filename = match mode {
-        ProverMode::Deletion => {
-            deletion_keys_file_format(tree_depth, batch_size)
-        }
-        ProverMode::Insertion => {
-            insertion_keys_file_format(tree_depth, batch_size)
-        }
-    };
+    let filename = keys_filename(tree_depth, batch_size, mode, setup_args);
 
     let mode_str = mode.to_string();
 
     let keys_file = keys_dir.as_ref().join(filename);
 
+    let _guard = MTB_LOCK.lock().await;
+
     if keys_file.exists() {
         return Ok(keys_file);
     }
 
-    let output = tokio::process::Command::new(mtb_binary)
+    check_setup_memory(tree_depth, batch_size);
+
+    let start = Instant::now();
+
+    let child = tokio::process::Command::new(mtb_binary)
         .arg("setup")
         .arg("--tree-depth")
         .arg(tree_depth.to_string())
@@ -118,18 +387,64 @@ pub async fn generate_keys(
         .arg(&keys_file)
         .arg("--mode")
         .arg(&mode_str)
-        .spawn()?
-        .wait_with_output()
-        .await?;
+        .args(setup_args)
+        .kill_on_drop(true)
+        .spawn()?;
+
+    let output = child.wait_with_output();
+
+    let output = match timeout {
+        Some(timeout) => match tokio::time::timeout(timeout, output).await {
+            Ok(output) => output,
+            Err(_) => {
+                // The child is killed once its future is dropped
+                remove_partial_keys(&keys_file).await?;
+
+                eyre::bail!(
+                    "MTB setup for tree depth {tree_depth} and batch size {batch_size} timed out after {}s - raise --mtb-setup-timeout or run it on a machine with more resources and place the keys in the cache",
+                    timeout.as_secs()
+                );
+            }
+        },
+        None => output.await,
+    }?;
 
     if !output.status.success() {
+        remove_partial_keys(&keys_file).await?;
+
         let error = String::from_utf8_lossy(&output.stderr);
         eyre::bail!("Failed to generate verifier contract: {error}");
     }
 
+    let keys_bytes = tokio::fs::metadata(&keys_file).await?.len();
+
+    let timing = SetupTiming {
+        mode,
+        tree_depth,
+        batch_size,
+        duration_secs: start.elapsed().as_secs_f64(),
+        keys_bytes,
+    };
+
+    if let Err(err) = record_setup_timing(keys_dir.as_ref(), timing).await {
+        warn!("Failed to record the duration of the MTB setup: {err:?}");
+    }
+
     Ok(keys_file)
 }
 
+/// Removes what a failed MTB setup left behind, so it isn't mistaken for
+/// cached keys
+async fn remove_partial_keys(keys_file: &Path) -> eyre::Result<()> {
+    if keys_file.exists() {
+        tokio::fs::remove_file(keys_file).await.with_context(|| {
+            format!("Removing partial keys {}", keys_file.display())
+        })?;
+    }
+
+    Ok(())
+}
+
 #[instrument(skip(mtb_binary, keys_file, verifier_contracts_dir))]
 pub async fn generate_verifier_contract(
     mtb_binary: impl AsRef<OsStr>,
@@ -138,20 +453,17 @@ pub async fn generate_verifier_contract(
     tree_depth: TreeDepth,
     batch_size: BatchSize,
     mode: ProverMode,
+    setup_args: &[String],
 ) -> eyre::Result<PathBuf> {
     let keys_file = keys_file.as_ref();
 
-    let filename = match mode {
-        ProverMode::Deletion => {
-            deletion_verifier_contract_filename(tree_depth, batch_size)
-        }
-        ProverMode::Insertion => {
-            insertion_verifier_contract_filename(tree_depth, batch_size)
-        }
-    };
+    let filename =
+        verifier_contract_filename(tree_depth, batch_size, mode, setup_args);
 
     let verifier_contract = verifier_contracts_dir.as_ref().join(filename);
 
+    let _guard = MTB_LOCK.lock().await;
+
     if verifier_contract.exists() {
         return Ok(verifier_contract);
     }
@@ -174,30 +486,155 @@ pub async fn generate_verifier_contract(
     Ok(verifier_contract)
 }
 
+pub fn keys_filename(
+    tree_depth: TreeDepth,
+    batch_size: BatchSize,
+    mode: ProverMode,
+    setup_args: &[String],
+) -> PathBuf {
+    let suffix = setup_args_suffix(setup_args);
+
+    match mode {
+        ProverMode::Deletion => {
+            deletion_keys_file_format(tree_depth, batch_size, &suffix)
+        }
+        ProverMode::Insertion => {
+            insertion_keys_file_format(tree_depth, batch_size, &suffix)
+        }
+    }
+}
+
+pub fn verifier_contract_filename(
+    tree_depth: TreeDepth,
+    batch_size: BatchSize,
+    mode: ProverMode,
+    setup_args: &[String],
+) -> PathBuf {
+    let suffix = setup_args_suffix(setup_args);
+
+    match mode {
+        ProverMode::Deletion => {
+            deletion_verifier_contract_filename(tree_depth, batch_size, &suffix)
+        }
+        ProverMode::Insertion => insertion_verifier_contract_filename(
+            tree_depth, batch_size, &suffix,
+        ),
+    }
+}
+
+/// Distinguishes keys generated with extra `mtb setup` arguments, so changing
+/// them regenerates the keys instead of reusing cached ones
+///
+/// Empty without extra arguments, so existing (and custom) keys keep their names
+fn setup_args_suffix(setup_args: &[String]) -> String {
+    if setup_args.is_empty() {
+        return String::new();
+    }
+
+    let hash = keccak256(setup_args.join("\0"));
+
+    format!("_{}", hex::encode(&hash[..4]))
+}
+
 fn insertion_keys_file_format(
     tree_depth: TreeDepth,
     batch_size: BatchSize,
+    suffix: &str,
 ) -> PathBuf {
-    PathBuf::from(format!("keys_insertion_{tree_depth}_{batch_size}"))
+    PathBuf::from(format!("keys_insertion_{tree_depth}_{batch_size}{suffix}"))
 }
 
 fn deletion_keys_file_format(
     tree_depth: TreeDepth,
     batch_size: BatchSize,
+    suffix: &str,
 ) -> PathBuf {
-    PathBuf::from(format!("keys_deletion_{tree_depth}_{batch_size}"))
+    PathBuf::from(format!("keys_deletion_{tree_depth}_{batch_size}{suffix}"))
 }
 
 fn insertion_verifier_contract_filename(
     tree_depth: TreeDepth,
     batch_size: BatchSize,
+    suffix: &str,
 ) -> PathBuf {
-    PathBuf::from(format!("insertion_{tree_depth}_{batch_size}.sol"))
+    PathBuf::from(format!("insertion_{tree_depth}_{batch_size}{suffix}.sol"))
 }
 
 fn deletion_verifier_contract_filename(
     tree_depth: TreeDepth,
     batch_size: BatchSize,
+    suffix: &str,
 ) -> PathBuf {
-    PathBuf::from(format!("deletion_{tree_depth}_{batch_size}.sol"))
+    PathBuf::from(format!("deletion_{tree_depth}_{batch_size}{suffix}.sol"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn estimates_scale_recorded_setups() {
+        let timings = [
+            SetupTiming {
+                mode: ProverMode::Insertion,
+                tree_depth: TreeDepth(30),
+                batch_size: BatchSize(100),
+                duration_secs: 60.0,
+                keys_bytes: 1000,
+            },
+            SetupTiming {
+                mode: ProverMode::Deletion,
+                tree_depth: TreeDepth(30),
+                batch_size: BatchSize(10),
+                duration_secs: 1.0,
+                keys_bytes: 10,
+            },
+        ];
+
+        let estimate = estimate_setup(
+            TreeDepth(30),
+            BatchSize(200),
+            ProverMode::Insertion,
+            &timings,
+        );
+
+        assert_eq!(
+            estimate,
+            SetupEstimate {
+                duration: Duration::from_secs(120),
+                keys_bytes: 2000,
+                from_timings: true,
+            }
+        );
+
+        let estimate = estimate_setup(
+            TreeDepth(30),
+            BatchSize(200),
+            ProverMode::Insertion,
+            &[],
+        );
+
+        assert!(!estimate.from_timings);
+    }
+
+    #[tokio::test]
+    async fn legacy_cache_is_migrated() {
+        let cache_dir = tempfile::tempdir().unwrap();
+        let legacy_keys = cache_dir.path().join(KEYS_DIR);
+        std::fs::create_dir_all(&legacy_keys).unwrap();
+        std::fs::write(legacy_keys.join("keys"), "keys").unwrap();
+
+        let versioned = cache_dir.path().join("mtb-1.2.1_0123456789ab");
+        migrate_legacy_cache(cache_dir.path(), &versioned)
+            .await
+            .unwrap();
+
+        assert!(!legacy_keys.exists());
+        assert!(versioned.join(KEYS_DIR).join("keys").exists());
+
+        let legacy = versioned_cache_dir(cache_dir.path(), Some(LEGACY_CACHE))
+            .await
+            .unwrap();
+        assert_eq!(legacy, cache_dir.path());
+    }
 }
@@ -0,0 +1,216 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use ethers::types::Address;
+use futures::StreamExt;
+use tracing::error;
+
+use super::deploy;
+use crate::cli::{Args, MultiChainArgs};
+use crate::report::costs::GasToken;
+use crate::report::Report;
+
+/// Deploys the config to every chain in `args`, with bounded parallelism
+pub async fn run_multi_chain(
+    cmd: Args,
+    args: MultiChainArgs,
+) -> eyre::Result<()> {
+    if cmd.start_nonce.is_some() {
+        eyre::bail!("--start-nonce can't be used for multi-chain deployments");
+    }
+
+    let gas_token = cmd.gas_token();
+
+    let deployments = args.rpc_urls.into_iter().map(|rpc_url| {
+        let mut cmd = cmd.clone();
+        cmd.rpc_url = Some(rpc_url.clone());
+        cmd.report_per_chain = true;
+
+        async move {
+            // Only the host, the url itself might contain an API key
+            let rpc_host = rpc_url.host_str().unwrap_or_default().to_string();
+
+            (rpc_host, deploy(cmd).await)
+        }
+    });
+
+    let results: Vec<_> = futures::stream::iter(deployments)
+        .buffer_unordered(args.parallelism.max(1))
+        .collect()
+        .await;
+
+    let mut reports = collect_reports(results, &gas_token)?;
+
+    reports.sort_by_key(|(chain_id, _)| *chain_id);
+
+    let divergent = print_address_matrix(&reports);
+
+    if !divergent.is_empty() && !args.allow_divergent_addresses {
+        eyre::bail!(
+            "Contract addresses differ between chains: {}",
+            divergent.join(", ")
+        );
+    }
+
+    Ok(())
+}
+
+/// Prints the outcome of every chain's deployment and returns the reports of
+/// the successful ones, failing if any chain failed
+fn collect_reports(
+    results: Vec<(String, eyre::Result<(u64, Report)>)>,
+    gas_token: &GasToken,
+) -> eyre::Result<Vec<(u64, Report)>> {
+    let num_chains = results.len();
+    let mut num_failed = 0;
+    let mut reports = vec![];
+
+    // Printed rather than logged so it's shown even with --quiet
+    println!("Multi-chain deployment summary:");
+
+    for (rpc_host, result) in results {
+        match result {
+            Ok((chain_id, report)) => {
+                println!(
+                    "Chain {chain_id} ({rpc_host}):\n{}",
+                    report.summary(gas_token)
+                );
+
+                reports.push((chain_id, report));
+            }
+            Err(err) => {
+                num_failed += 1;
+
+                error!("Deployment via {rpc_host} failed: {err:?}");
+                println!("Deployment via {rpc_host} failed");
+            }
+        }
+    }
+
+    if num_failed > 0 {
+        eyre::bail!("{num_failed} of {num_chains} chain deployments failed");
+    }
+
+    Ok(reports)
+}
+
+/// Prints every contract's address per chain and returns the names of the
+/// contracts whose address differs between chains
+fn print_address_matrix(reports: &[(u64, Report)]) -> Vec<String> {
+    let addresses: Vec<_> = reports
+        .iter()
+        .map(|(chain_id, report)| (*chain_id, report.contract_addresses()))
+        .collect();
+
+    println!("Addresses by chain:");
+
+    let mut divergent = vec![];
+
+    for name in contract_names(&addresses) {
+        let divergence_marker = if is_divergent(&addresses, &name) {
+            divergent.push(name.clone());
+            " (DIVERGENT)"
+        } else {
+            ""
+        };
+
+        println!("  {name}{divergence_marker}:");
+
+        for (chain_id, chain_addresses) in &addresses {
+            match chain_addresses.get(&name) {
+                Some(address) => println!("    {chain_id}: {address:?}"),
+                None => println!("    {chain_id}: -"),
+            }
+        }
+    }
+
+    divergent
+}
+
+fn contract_names(
+    addresses: &[(u64, BTreeMap<String, Address>)],
+) -> BTreeSet<String> {
+    addresses
+        .iter()
+        .flat_map(|(_, addresses)| addresses.keys().cloned())
+        .collect()
+}
+
+/// Whether the contract is missing on some chain or deployed at different
+/// addresses
+fn is_divergent(
+    addresses: &[(u64, BTreeMap<String, Address>)],
+    name: &str,
+) -> bool {
+    let per_chain: BTreeSet<_> = addresses
+        .iter()
+        .map(|(_, addresses)| addresses.get(name))
+        .collect();
+
+    per_chain.len() > 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn gas_token() -> GasToken {
+        GasToken {
+            symbol: "ETH".to_string(),
+            decimals: 18,
+        }
+    }
+
+    #[test]
+    fn failing_chain() {
+        let config: Config =
+            serde_yaml::from_str("groups: {}\nmisc: {}").unwrap();
+        let report = Report::default_with_config(&config);
+
+        let all_succeeded = collect_reports(
+            vec![
+                ("a".to_string(), Ok((1, report.clone()))),
+                ("b".to_string(), Ok((10, report.clone()))),
+            ],
+            &gas_token(),
+        )
+        .unwrap();
+
+        assert_eq!(all_succeeded.len(), 2);
+
+        let one_failed = collect_reports(
+            vec![
+                ("a".to_string(), Ok((1, report))),
+                ("b".to_string(), Err(eyre::eyre!("deployment failed"))),
+            ],
+            &gas_token(),
+        );
+
+        assert!(one_failed.is_err());
+    }
+
+    #[test]
+    fn divergent_addresses() {
+        let router = Address::repeat_byte(1);
+
+        let addresses = vec![
+            (
+                1,
+                maplit::btreemap! {
+                    "router".to_string() => router,
+                    "verifier".to_string() => Address::repeat_byte(2),
+                },
+            ),
+            (
+                10,
+                maplit::btreemap! {
+                    "router".to_string() => router,
+                    "verifier".to_string() => Address::repeat_byte(3),
+                },
+            ),
+        ];
+
+        assert!(!is_divergent(&addresses, "router"));
+        assert!(is_divergent(&addresses, "verifier"));
+    }
+}
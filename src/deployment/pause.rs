@@ -0,0 +1,103 @@
+use ethers::types::Address;
+use tracing::{info, instrument};
+
+use super::{init_context, ChainAccess, DeploymentContext};
+use crate::cli::Args;
+use crate::ethers_utils::TransactionBuilder;
+use crate::forge_utils::ContractSpec;
+
+/// Pauses (or unpauses) the router and identity managers of the report
+///
+/// Fails if the contracts don't implement pausing.
+pub async fn run_pause(cmd: Args, paused: bool) -> eyre::Result<()> {
+    let (context, config) = init_context(cmd, ChainAccess::Send).await?;
+
+    let contract_names = &config.misc.contract_names;
+
+    context.set_cost_step(if paused { "pause" } else { "unpause" });
+
+    let mut targets = vec![];
+
+    if let Some(router) = context.report.router_address() {
+        targets.push((contract_names.router_impl(), router));
+    }
+
+    if let Some(identity_managers) = context.report.identity_managers.as_ref() {
+        let mut group_ids: Vec<_> =
+            identity_managers.groups.keys().copied().collect();
+        group_ids.sort();
+
+        for group_id in group_ids {
+            let identity_manager = &identity_managers.groups[&group_id];
+
+            let impl_spec = if identity_manager.impl_v2_deployment.is_some() {
+                contract_names.identity_manager_impl_v2()
+            } else {
+                contract_names.identity_manager_impl_v1()
+            };
+
+            targets
+                .push((impl_spec, identity_manager.proxy_deployment.address));
+        }
+    }
+
+    if targets.is_empty() {
+        eyre::bail!("No router or identity managers found in the report");
+    }
+
+    for (impl_spec, address) in targets {
+        set_paused(context.as_ref(), impl_spec, address, paused).await?;
+    }
+
+    Ok(())
+}
+
+#[instrument(skip(context))]
+async fn set_paused(
+    context: &DeploymentContext,
+    impl_spec: ContractSpec,
+    address: Address,
+    paused: bool,
+) -> eyre::Result<()> {
+    let impl_abi = context.inspect_abi(impl_spec.clone()).await?;
+
+    let function_name = if paused { "pause" } else { "unpause" };
+
+    if impl_abi.function(function_name).is_err() {
+        eyre::bail!("{impl_spec} doesn't implement {function_name}()");
+    }
+
+    // Not every pausable contract exposes its state
+    if impl_abi.function("paused").is_ok() {
+        let currently_paused: bool = TransactionBuilder::default()
+            .signer(context.rpc_signer.clone())
+            .abi(impl_abi.clone())
+            .function_name("paused")
+            .args(())
+            .to(address)
+            .context(context)
+            .build()?
+            .call()
+            .await?;
+
+        if currently_paused == paused {
+            info!("{address:?} is already {function_name}d");
+            return Ok(());
+        }
+    }
+
+    info!("Calling {function_name}() on {address:?}");
+
+    TransactionBuilder::default()
+        .signer(context.rpc_signer.clone())
+        .abi(impl_abi)
+        .function_name(function_name)
+        .args(())
+        .to(address)
+        .context(context)
+        .build()?
+        .send()
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,315 @@
+use std::fmt::Write as _;
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+use super::steps::assemble_report::REPORT_PATH;
+use crate::cli::{Args, DeploymentType, PlanArgs, PlanFormat, VerifierMode};
+use crate::config::Config;
+use crate::report::Report;
+use crate::serde_utils;
+
+/// A step of the deployment and whether the current run would execute it
+#[derive(Debug, Clone, Serialize)]
+pub struct PlannedStep {
+    pub name: &'static str,
+    /// Steps whose outputs this step consumes
+    pub depends_on: Vec<&'static str>,
+    /// Whether the run gets to this step at all - `--target` stops the run
+    /// before the steps it doesn't include
+    pub reached: bool,
+    /// Whether the step deploys anything, otherwise its outputs are taken
+    /// from the report
+    pub runs: bool,
+    /// Whether the report already holds outputs of this step, which a run
+    /// reuses
+    pub in_report: bool,
+}
+
+/// Writes the step graph of the deployment, annotated with which steps a run
+/// with the current target, config & report would execute
+pub async fn run_plan(cmd: Args, args: PlanArgs) -> eyre::Result<()> {
+    let config = Config::load(cmd.config_path()?).await?;
+
+    let report_path = PathBuf::from(cmd.deployment_name()?).join(REPORT_PATH);
+    let report = if report_path.exists() {
+        serde_utils::read_deserialize(&report_path).await?
+    } else {
+        Report::default_with_config(&config)
+    };
+
+    let steps = plan(
+        &config,
+        &report,
+        &cmd.target,
+        &cmd.verifier_mode,
+        cmd.no_deletion,
+        &cmd.skip_steps,
+    );
+
+    let content = match args.format {
+        PlanFormat::Dot => to_dot(&steps),
+        PlanFormat::Json => serde_json::to_string_pretty(&steps)?,
+    };
+
+    match args.output {
+        Some(output) => tokio::fs::write(output, content).await?,
+        None => println!("{content}"),
+    }
+
+    Ok(())
+}
+
+/// Decides which steps a run reaches and executes - `run_steps` gates its
+/// steps on this, so the plan is exactly what a run does
+pub fn plan(
+    config: &Config,
+    report: &Report,
+    target: &DeploymentType,
+    verifier_mode: &VerifierMode,
+    no_deletion: bool,
+    skip_steps: &[String],
+) -> Vec<PlannedStep> {
+    let verifiers_only = matches!(
+        target,
+        DeploymentType::InsertionVerifiers
+            | DeploymentType::DeletionVerifiers
+            | DeploymentType::Verifiers
+    );
+
+    let lookup_tables = !verifiers_only;
+    let semaphore_verifier =
+        lookup_tables && *target != DeploymentType::LookupTables;
+    let identity_managers =
+        semaphore_verifier && *target != DeploymentType::SemaphoreVerifier;
+    let world_id_router =
+        identity_managers && *target != DeploymentType::IdentityManager;
+    let timelock = world_id_router && *target != DeploymentType::WorldIdRouter;
+
+    let step = |name: &'static str,
+                depends_on: &[&'static str],
+                reached: bool,
+                runs: bool,
+                in_report| PlannedStep {
+        name,
+        depends_on: depends_on.to_vec(),
+        reached,
+        runs: reached
+            && runs
+            && !skip_steps.iter().any(|skip_step| skip_step == name),
+        in_report,
+    };
+
+    // The verifiers of a mode which isn't deployed are taken from the report
+    vec![
+        step(
+            "insertion_verifiers",
+            &[],
+            true,
+            *verifier_mode != VerifierMode::Deletion
+                && *target != DeploymentType::DeletionVerifiers,
+            report.insertion_verifiers.is_some(),
+        ),
+        step(
+            "deletion_verifiers",
+            &[],
+            true,
+            *verifier_mode != VerifierMode::Insertion
+                && *target != DeploymentType::InsertionVerifiers
+                && !no_deletion,
+            report.deletion_verifiers.is_some(),
+        ),
+        step(
+            "lookup_tables",
+            &["insertion_verifiers", "deletion_verifiers"],
+            lookup_tables,
+            true,
+            report.lookup_tables.is_some(),
+        ),
+        step(
+            "semaphore_verifier",
+            &[],
+            semaphore_verifier,
+            true,
+            report.semaphore_verifier.is_some(),
+        ),
+        step(
+            "identity_managers",
+            &["semaphore_verifier", "lookup_tables"],
+            identity_managers,
+            true,
+            report.identity_managers.is_some(),
+        ),
+        step(
+            "world_id_router",
+            &["identity_managers"],
+            world_id_router,
+            true,
+            report.world_id_router.is_some(),
+        ),
+        step(
+            "timelock",
+            &["identity_managers", "world_id_router"],
+            timelock,
+            config.misc.timelock.is_some(),
+            report.timelock.is_some(),
+        ),
+    ]
+}
+
+/// The planned step named `name`
+///
+/// Panics on an unknown name, the names are the fixed ones of [`plan`]
+pub fn step<'a>(steps: &'a [PlannedStep], name: &str) -> &'a PlannedStep {
+    steps
+        .iter()
+        .find(|step| step.name == name)
+        .unwrap_or_else(|| panic!("Unknown step {name}"))
+}
+
+/// Fails if a step which runs depends on a step of `skip_steps` without
+/// outputs in the report, since there'd be nothing to take its outputs from
+pub fn check_skipped_steps(
+    steps: &[PlannedStep],
+    skip_steps: &[String],
+) -> eyre::Result<()> {
+    for step in steps.iter().filter(|step| step.runs) {
+        for dependency in &step.depends_on {
+            let skipped =
+                skip_steps.iter().any(|skip_step| skip_step == dependency);
+            let in_report = steps
+                .iter()
+                .any(|step| step.name == *dependency && step.in_report);
+
+            if skipped && !in_report {
+                eyre::bail!(
+                    "Step {dependency} is skipped, but {} depends on it and the report has no {dependency} to take instead",
+                    step.name
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Renders the steps as a Graphviz digraph - skipped steps are dashed and
+/// steps with outputs in the report are filled
+pub fn to_dot(steps: &[PlannedStep]) -> String {
+    let mut dot = String::from("digraph deployment {\n    rankdir=LR;\n");
+
+    for step in steps {
+        let mut style = vec![];
+        if !step.runs {
+            style.push("dashed");
+        }
+        if step.in_report {
+            style.push("filled");
+        }
+
+        let status = if step.runs { "run" } else { "skip" };
+
+        writeln!(
+            dot,
+            "    {} [label=\"{}\\n({status})\", style=\"{}\"];",
+            step.name,
+            step.name,
+            style.join(","),
+        )
+        .unwrap();
+    }
+
+    for step in steps {
+        for dependency in &step.depends_on {
+            writeln!(dot, "    {dependency} -> {};", step.name).unwrap();
+        }
+    }
+
+    dot.push_str("}\n");
+
+    dot
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use super::*;
+
+    #[test]
+    fn lookup_tables_target() {
+        let config: Config = serde_yaml::from_str(indoc! { r#"
+            groups:
+              0:
+                tree_depth: 30
+                insertion_batch_sizes:
+                  - 100
+            misc: {}
+        "# })
+        .unwrap();
+
+        let report = Report::default_with_config(&config);
+
+        let steps = plan(
+            &config,
+            &report,
+            &DeploymentType::LookupTables,
+            &VerifierMode::Insertion,
+            false,
+            &[],
+        );
+
+        let runs: Vec<_> = steps
+            .iter()
+            .filter(|step| step.runs)
+            .map(|step| step.name)
+            .collect();
+
+        assert_eq!(runs, ["insertion_verifiers", "lookup_tables"]);
+        assert!(!step(&steps, "semaphore_verifier").reached);
+    }
+
+    #[test]
+    fn skipped_steps_need_outputs_in_the_report() {
+        let config: Config = serde_yaml::from_str(indoc! { r#"
+            groups:
+              0:
+                tree_depth: 30
+                insertion_batch_sizes:
+                  - 100
+            misc: {}
+        "# })
+        .unwrap();
+
+        let mut report = Report::default_with_config(&config);
+        let skip_steps = ["semaphore_verifier".to_string()];
+
+        let plan = |report: &Report| {
+            plan(
+                &config,
+                report,
+                &DeploymentType::Full,
+                &VerifierMode::Both,
+                false,
+                &skip_steps,
+            )
+        };
+
+        let steps = plan(&report);
+        let semaphore_verifier = step(&steps, "semaphore_verifier");
+        assert!(semaphore_verifier.reached);
+        assert!(!semaphore_verifier.runs);
+
+        assert!(check_skipped_steps(&steps, &skip_steps).is_err());
+
+        report.semaphore_verifier = Some(
+            serde_yaml::from_str(indoc! { r#"
+                pairing_deployment:
+                  address: "0x1111111111111111111111111111111111111111"
+            "# })
+            .unwrap(),
+        );
+
+        check_skipped_steps(&plan(&report), &skip_steps).unwrap();
+    }
+}
@@ -0,0 +1,43 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use tracing::info;
+
+use super::steps::assemble_report::{assemble_report, REPORT_PATH};
+use super::{init_context, ChainAccess};
+use crate::cli::Args;
+
+/// Rewrites the report from its own contents without deploying anything
+///
+/// Useful to normalize an old report after the report format changed
+pub async fn run_reassemble(cmd: Args) -> eyre::Result<()> {
+    let report_path = PathBuf::from(cmd.deployment_name()?).join(REPORT_PATH);
+    if !report_path.exists() {
+        eyre::bail!("No report found at {}", report_path.display());
+    }
+
+    let (context, _config) = init_context(cmd, ChainAccess::Read).await?;
+
+    let report = &context.report;
+
+    // The report's own config, so the rewritten report describes the same
+    // deployment regardless of later config changes
+    let config = Arc::new(report.config.clone());
+
+    assemble_report(
+        context.clone(),
+        config,
+        report.insertion_verifiers.as_ref(),
+        report.deletion_verifiers.as_ref(),
+        report.lookup_tables.as_ref(),
+        report.semaphore_verifier.as_ref(),
+        report.identity_managers.as_ref(),
+        report.world_id_router.as_ref(),
+        report.timelock.as_ref(),
+    )
+    .await?;
+
+    info!("Report reassembled");
+
+    Ok(())
+}
@@ -0,0 +1,82 @@
+use std::collections::BTreeMap;
+
+use ethers::types::{Address, U256};
+use eyre::ContextCompat;
+
+use super::{init_context, ChainAccess, DeploymentContext};
+use crate::cli::{Args, RouterRoutesArgs};
+use crate::config::Config;
+use crate::ethers_utils::TransactionBuilder;
+use crate::types::GroupId;
+
+/// Prints the route of every group as read from the router on-chain
+pub async fn run_router_routes(
+    cmd: Args,
+    args: RouterRoutesArgs,
+) -> eyre::Result<()> {
+    let (context, config) = init_context(cmd, ChainAccess::Read).await?;
+
+    let router = match args.router {
+        Some(router) => router,
+        None => context.report.router_address().context(
+            "No world id router found in the report - pass --router",
+        )?,
+    };
+
+    let routes = read_routes(context.as_ref(), &config, router).await?;
+
+    println!("Router {router:?} ({} groups):", routes.len());
+
+    for (group_id, route) in routes {
+        match route {
+            Some(address) => println!("  group {group_id} -> {address:?}"),
+            None => println!("  group {group_id} -> disabled"),
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads the route of every group from the router on-chain
+///
+/// Disabled groups have no route
+pub async fn read_routes(
+    context: &DeploymentContext,
+    config: &Config,
+    router: Address,
+) -> eyre::Result<BTreeMap<GroupId, Option<Address>>> {
+    let impl_abi = context
+        .inspect_abi(config.misc.contract_names.router_impl())
+        .await?;
+
+    let group_count: U256 = TransactionBuilder::default()
+        .signer(context.rpc_signer.clone())
+        .abi(impl_abi.clone())
+        .function_name("groupCount")
+        .args(())
+        .to(router)
+        .context(context)
+        .build()?
+        .call()
+        .await?;
+
+    let mut routes = BTreeMap::new();
+
+    for group_id in 0..group_count.as_u64() {
+        // `routeFor` reverts for disabled groups
+        let route: eyre::Result<Address> = TransactionBuilder::default()
+            .signer(context.rpc_signer.clone())
+            .abi(impl_abi.clone())
+            .function_name("routeFor")
+            .args(group_id)
+            .to(router)
+            .context(context)
+            .build()?
+            .call()
+            .await;
+
+        routes.insert(GroupId(group_id as usize), route.ok());
+    }
+
+    Ok(routes)
+}
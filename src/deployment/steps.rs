@@ -1,6 +1,8 @@
 pub mod assemble_report;
 pub mod identity_manager;
 pub mod lookup_tables;
+pub mod ownership;
 pub mod semaphore_verifier;
+pub mod timelock;
 pub mod verifiers;
 pub mod world_id_router;
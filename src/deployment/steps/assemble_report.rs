@@ -5,6 +5,7 @@ use tracing::instrument;
 use super::identity_manager::WorldIDIdentityManagersDeployment;
 use super::lookup_tables::LookupTables;
 use super::semaphore_verifier::SemaphoreVerifierDeployment;
+use super::timelock::TimelockDeployment;
 use super::verifiers::Verifiers;
 use super::world_id_router::WorldIdRouterDeployment;
 use crate::config::Config;
@@ -24,19 +25,68 @@ pub async fn assemble_report(
     semaphore_verifier: Option<&SemaphoreVerifierDeployment>,
     identity_managers: Option<&WorldIDIdentityManagersDeployment>,
     world_id_router: Option<&WorldIdRouterDeployment>,
+    timelock: Option<&TimelockDeployment>,
 ) -> eyre::Result<()> {
+    // The dumped transactions haven't been sent, so the report mustn't
+    // claim their effects
+    if context.dumps_transactions() {
+        return Ok(());
+    }
+
+    let costs = context.costs.lock().unwrap().clone();
+
+    // Outside of a step (e.g. when reassembling) the progress is kept as is,
+    // while a step in progress means the deployment isn't complete (yet)
+    let current_step = context.current_step();
+    let last_step = current_step
+        .map(ToString::to_string)
+        .unwrap_or_else(|| context.report.last_step.clone());
+    let complete = current_step.is_none() && context.report.complete;
+
     let report = Report {
         config: config.as_ref().clone(),
+        deployer: Some(context.deployer),
+        create2_factory: context.create2_factory,
+        complete,
+        last_step,
         insertion_verifiers: insertion_verifiers.cloned(),
         deletion_verifiers: deletion_verifiers.cloned(),
         lookup_tables: lookup_tables.cloned(),
         semaphore_verifier: semaphore_verifier.cloned(),
         identity_managers: identity_managers.cloned(),
         world_id_router: world_id_router.cloned(),
+        timelock: timelock.cloned(),
+        costs: (!costs.total.is_zero()).then_some(costs),
     };
 
-    let path = context.deployment_dir.join(REPORT_PATH);
-    serde_utils::write_serialize(path, report).await?;
+    serde_utils::write_serialize(&context.report_path, &report).await?;
+
+    if context.cmd.group_reports {
+        write_group_reports(&context, &report).await?;
+    }
+
+    Ok(())
+}
+
+/// Writes the per group views of `report` - these are regenerated on every
+/// assembly, the combined report stays the source of truth
+async fn write_group_reports(
+    context: &DeploymentContext,
+    report: &Report,
+) -> eyre::Result<()> {
+    for group_report in report.group_reports()? {
+        let group_id = group_report.group_id;
+
+        let file_name = if context.cmd.report_per_chain {
+            format!("group-{group_id}.{}.yml", context.chain_id)
+        } else {
+            format!("group-{group_id}.yml")
+        };
+
+        let path = context.report_path.with_file_name(file_name);
+
+        serde_utils::write_serialize(path, group_report).await?;
+    }
 
     Ok(())
 }
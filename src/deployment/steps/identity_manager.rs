@@ -1,24 +1,57 @@
 use std::collections::HashMap;
+use std::future::Future;
 use std::sync::Arc;
 
-use ethers::prelude::encode_function_data;
-use ethers::types::U256;
-use eyre::ContextCompat;
+use ethers::abi::{Function, Token};
+use ethers::types::{Address, U256};
+use ethers::utils::hex;
+use eyre::{Context, ContextCompat};
 use serde::{Deserialize, Serialize};
-use tracing::{info, instrument};
+use tracing::{debug, info, instrument};
 
-use super::lookup_tables::LookupTables;
+use super::lookup_tables::{GroupLookupTables, LookupTables};
 use super::semaphore_verifier::SemaphoreVerifierDeployment;
 use crate::config::Config;
+use crate::deployment::initializer_args::{
+    describe_call, encode_initializer_call,
+};
 use crate::deployment::DeploymentContext;
-use crate::ethers_utils::TransactionBuilder;
-use crate::forge_utils::{ContractSpec, ForgeInspectAbi};
+use crate::ethers_utils::{
+    initialized_version, proxy_implementation, TransactionBuilder,
+};
 use crate::report::contract_deployment::ContractDeployment;
 use crate::types::GroupId;
 
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
 pub struct WorldIDIdentityManagersDeployment {
     pub groups: HashMap<GroupId, WorldIdIdentityManagerDeployment>,
+    /// V1 implementations of groups whose proxy isn't deployed yet, so a
+    /// resume doesn't deploy them again
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub pending_impls: HashMap<GroupId, ContractDeployment>,
+}
+
+impl WorldIDIdentityManagersDeployment {
+    /// Drops the groups which are no longer in the config and returns their
+    /// ids, sorted
+    fn drop_removed_groups(&mut self, config: &Config) -> Vec<GroupId> {
+        let mut removed: Vec<_> = self
+            .groups
+            .keys()
+            .chain(self.pending_impls.keys())
+            .filter(|&group_id| !config.groups.contains_key(group_id))
+            .copied()
+            .collect();
+        removed.sort();
+        removed.dedup();
+
+        for group_id in &removed {
+            self.groups.remove(group_id);
+            self.pending_impls.remove(group_id);
+        }
+
+        removed
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -28,23 +61,29 @@ pub struct WorldIdIdentityManagerDeployment {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub impl_v2_deployment: Option<ContractDeployment>,
     pub proxy_deployment: ContractDeployment,
+    /// The version the proxy was initialized to, as read from the chain
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub initialized_version: Option<u8>,
 }
 
 #[instrument(skip_all)]
-async fn deploy_world_id_identity_manager_for_group(
+async fn deploy_world_id_identity_manager_for_group<R, RF>(
     context: &DeploymentContext,
     config: &Config,
     group_id: GroupId,
     semaphore_verifier_deployment: &SemaphoreVerifierDeployment,
     lookup_tables: &LookupTables,
-) -> eyre::Result<WorldIdIdentityManagerDeployment> {
-    if let Some(deployment) = context
-        .report
-        .identity_managers
-        .as_ref()
-        .and_then(|g| g.groups.get(&group_id))
-    {
-        if deployment.impl_v1_deployment.is_some()
+    record_impl: R,
+) -> eyre::Result<WorldIdIdentityManagerDeployment>
+where
+    R: FnOnce(ContractDeployment) -> RF,
+    RF: Future<Output = eyre::Result<()>>,
+{
+    if let Some(deployment) = context.report.group_identity_manager(group_id) {
+        if deployment.impl_v2_deployment.is_none() && context.cmd.no_deletion {
+            info!("Existing world id identity manager deployment found for group {:?}. Keeping it at v1.", group_id);
+            return Ok(deployment.clone());
+        } else if deployment.impl_v1_deployment.is_some()
             && deployment.impl_v2_deployment.is_none()
         {
             info!("Existing world id identity manager deployment found for group {:?}. Upgrading to v2.", group_id);
@@ -64,27 +103,135 @@ async fn deploy_world_id_identity_manager_for_group(
         }
     }
 
-    let group_config = config
-        .groups
-        .get(&group_id)
-        .context("Missing group id in config")?;
+    let previous_impl = context
+        .report
+        .identity_managers
+        .as_ref()
+        .and_then(|managers| managers.pending_impls.get(&group_id));
 
-    let identity_manager_spec = ContractSpec::name("WorldIDIdentityManager");
-    let impl_spec = ContractSpec::name("WorldIDIdentityManagerImplV1");
+    let deployment = deploy_resumable(
+        previous_impl,
+        || deploy_impl_v1(context, config, group_id),
+        |impl_v1_address| {
+            deploy_proxy(
+                context,
+                config,
+                group_id,
+                semaphore_verifier_deployment,
+                lookup_tables,
+                impl_v1_address,
+            )
+        },
+        record_impl,
+    )
+    .await?;
+
+    if context.cmd.no_deletion {
+        return Ok(deployment);
+    }
 
+    upgrade_v1_to_v2(context, config, group_id, lookup_tables, &deployment)
+        .await
+}
+
+/// Deploys the V1 implementation and then the proxy initialized with it,
+/// reusing an implementation a previous (failed) run already deployed
+///
+/// The implementation is passed to `record_impl` as soon as it's deployed, so
+/// it isn't redeployed if deploying the proxy fails.
+async fn deploy_resumable<I, IF, P, PF, R, RF>(
+    previous_impl: Option<&ContractDeployment>,
+    deploy_impl: I,
+    deploy_proxy: P,
+    record_impl: R,
+) -> eyre::Result<WorldIdIdentityManagerDeployment>
+where
+    I: FnOnce() -> IF,
+    IF: Future<Output = eyre::Result<ContractDeployment>>,
+    P: FnOnce(Address) -> PF,
+    PF: Future<Output = eyre::Result<ContractDeployment>>,
+    R: FnOnce(ContractDeployment) -> RF,
+    RF: Future<Output = eyre::Result<()>>,
+{
+    let impl_v1_deployment = match previous_impl {
+        Some(previous_impl) => {
+            info!("Reusing the implementation {:?}", previous_impl.address);
+            previous_impl.clone()
+        }
+        None => {
+            let impl_v1_deployment = deploy_impl().await?;
+
+            record_impl(impl_v1_deployment.clone()).await?;
+
+            impl_v1_deployment
+        }
+    };
+
+    let proxy_deployment = deploy_proxy(impl_v1_deployment.address).await?;
+
+    Ok(WorldIdIdentityManagerDeployment {
+        impl_v1_deployment: Some(impl_v1_deployment),
+        impl_v2_deployment: None,
+        proxy_deployment,
+        initialized_version: None,
+    })
+}
+
+#[instrument(skip_all)]
+async fn deploy_impl_v1(
+    context: &DeploymentContext,
+    config: &Config,
+    group_id: GroupId,
+) -> eyre::Result<ContractDeployment> {
     let impl_v1_deployment = context
-        .forge_create(impl_spec.clone())
+        .forge_create_deterministic(
+            config.misc.contract_names.identity_manager_impl_v1(),
+            "identity_manager_impl_v1",
+            Some(group_id),
+        )
         .with_cwd("./world-id-contracts")
+        .with_expected_code_hash(
+            config.misc.code_hashes.identity_manager_impl_v1,
+        )
         .run()
         .await?;
 
-    let impl_abi = ForgeInspectAbi::new(impl_spec.clone())
-        .with_cwd("./world-id-contracts")
-        .run()
-        .await?;
+    context.record_create_cost(&impl_v1_deployment).await?;
+
+    context.await_impl_deployment(&impl_v1_deployment).await?;
+
+    Ok(impl_v1_deployment.into())
+}
+
+#[instrument(skip(
+    context,
+    config,
+    semaphore_verifier_deployment,
+    lookup_tables
+))]
+async fn deploy_proxy(
+    context: &DeploymentContext,
+    config: &Config,
+    group_id: GroupId,
+    semaphore_verifier_deployment: &SemaphoreVerifierDeployment,
+    lookup_tables: &LookupTables,
+    impl_v1_address: Address,
+) -> eyre::Result<ContractDeployment> {
+    let group_config = config
+        .groups
+        .get(&group_id)
+        .context("Missing group id in config")?;
+    let tree_depth = group_config
+        .tree_depth()
+        .with_context(|| format!("Group {group_id}"))?;
+
+    let identity_manager_spec = config.misc.contract_names.identity_manager();
+    let impl_spec = config.misc.contract_names.identity_manager_impl_v1();
+
+    let impl_abi = context.inspect_abi(impl_spec.clone()).await?;
 
     let initial_root = crate::utils::initial_root_hash(
-        group_config.tree_depth,
+        tree_depth,
         config.misc.initial_leaf_value,
     );
 
@@ -116,55 +263,106 @@ async fn deploy_world_id_identity_manager_for_group(
 
     let initialize_func = impl_abi.function("initialize")?;
 
-    let call_data = encode_function_data(
+    let mut known_addresses = known_addresses(group_lookup_tables);
+    known_addresses.insert(
+        "semaphore_verifier",
+        semaphore_verifier_deployment.verifier_address()?,
+    );
+
+    let call_data = encode_initializer_call(
         initialize_func,
-        (
-            group_config.tree_depth.0 as u8,
-            initial_root_u256,
-            insert_lookup_table_address,
-            update_lookup_table_address,
-            semaphore_verifier_deployment.verifier_deployment.address,
-        ),
+        maplit::hashmap! {
+            "treeDepth" => Token::Uint(U256::from(tree_depth.0 as u8)),
+            "initialRoot" => Token::Uint(initial_root_u256),
+            "_batchInsertionVerifiers" =>
+                Token::Address(insert_lookup_table_address),
+            "_batchUpdateVerifiers" =>
+                Token::Address(update_lookup_table_address),
+            "_semaphoreVerifier" => Token::Address(
+                semaphore_verifier_deployment.verifier_address()?
+            ),
+        },
+        config.misc.identity_manager_init_args.get("initialize"),
+        &known_addresses,
     )?;
 
+    print_init_call(context, group_id, initialize_func, &call_data)?;
+
     let proxy_deployment = context
         .forge_create(identity_manager_spec)
         .with_cwd("./world-id-contracts")
-        .with_constructor_arg(format!("{:?}", impl_v1_deployment.deployed_to))
+        .with_constructor_arg(format!("{impl_v1_address:?}"))
         .with_constructor_arg(call_data)
         .run()
         .await?;
 
-    let deployment = WorldIdIdentityManagerDeployment {
-        impl_v1_deployment: Some(impl_v1_deployment.into()),
-        impl_v2_deployment: None,
-        proxy_deployment: proxy_deployment.into(),
-    };
+    context.record_create_cost(&proxy_deployment).await?;
 
-    upgrade_v1_to_v2(context, config, group_id, lookup_tables, &deployment)
-        .await
+    Ok(proxy_deployment.into())
 }
 
 #[instrument(skip_all)]
 async fn upgrade_v1_to_v2(
     context: &DeploymentContext,
-    _config: &Config,
+    config: &Config,
     group_id: GroupId,
     lookup_tables: &LookupTables,
     v1_deployment: &WorldIdIdentityManagerDeployment,
 ) -> eyre::Result<WorldIdIdentityManagerDeployment> {
-    let impl_v2_spec = ContractSpec::name("WorldIDIdentityManagerImplV2");
+    let proxy_deployment = &v1_deployment.proxy_deployment;
+
+    // `initializeV2` reverts if it was already called, e.g. by a previous run
+    // which failed before the upgrade was recorded
+    if !proxy_deployment.simulated {
+        let version =
+            initialized_version(&context.rpc_signer, proxy_deployment.address)
+                .await?;
+
+        if version >= 2 {
+            let implementation = proxy_implementation(
+                &context.rpc_signer,
+                proxy_deployment.address,
+            )
+            .await?;
+
+            info!("Identity manager of group {group_id} is already initialized to version {version} with implementation {implementation:?}, skipping the upgrade");
+
+            return Ok(WorldIdIdentityManagerDeployment {
+                impl_v1_deployment: None,
+                impl_v2_deployment: Some(ContractDeployment {
+                    address: implementation,
+                    simulated: false,
+                    source_commit: None,
+                    code_hash: None,
+                    block_number: None,
+                    salt: None,
+                }),
+                proxy_deployment: proxy_deployment.clone(),
+                initialized_version: Some(version),
+            });
+        }
+    }
+
+    let impl_v2_spec = config.misc.contract_names.identity_manager_impl_v2();
 
     let impl_v2_deployment = context
-        .forge_create(impl_v2_spec.clone())
+        .forge_create_deterministic(
+            impl_v2_spec.clone(),
+            "identity_manager_impl_v2",
+            Some(group_id),
+        )
         .with_cwd("./world-id-contracts")
+        .with_expected_code_hash(
+            config.misc.code_hashes.identity_manager_impl_v2,
+        )
         .run()
         .await?;
 
-    let impl_abi = ForgeInspectAbi::new(impl_v2_spec.clone())
-        .with_cwd("./world-id-contracts")
-        .run()
-        .await?;
+    context.record_create_cost(&impl_v2_deployment).await?;
+
+    context.await_impl_deployment(&impl_v2_deployment).await?;
+
+    let impl_abi = context.inspect_abi(impl_v2_spec.clone()).await?;
 
     let group_lookup_tables =
         lookup_tables.groups.get(&group_id).with_context(|| {
@@ -180,8 +378,17 @@ async fn upgrade_v1_to_v2(
 
     let initialize_v2_func = impl_abi.function("initializeV2")?;
 
-    let call_data =
-        encode_function_data(initialize_v2_func, delete_lookup_table_address)?;
+    let call_data = encode_initializer_call(
+        initialize_v2_func,
+        maplit::hashmap! {
+            "_batchDeletionVerifiers" =>
+                Token::Address(delete_lookup_table_address),
+        },
+        config.misc.identity_manager_init_args.get("initializeV2"),
+        &known_addresses(group_lookup_tables),
+    )?;
+
+    print_init_call(context, group_id, initialize_v2_func, &call_data)?;
 
     let signer = &context.rpc_signer;
 
@@ -202,35 +409,185 @@ async fn upgrade_v1_to_v2(
         impl_v2_deployment: Some(impl_v2_deployment.into()),
         // We preserve the proxy
         proxy_deployment: v1_deployment.proxy_deployment.clone(),
+        initialized_version: None,
     })
 }
 
-pub async fn deploy(
+/// Shows the decoded initializer arguments before they're baked into the
+/// proxy - printed with `--print-init-calldata`, logged at debug otherwise
+fn print_init_call(
+    context: &DeploymentContext,
+    group_id: GroupId,
+    func: &Function,
+    call_data: &[u8],
+) -> eyre::Result<()> {
+    let call = describe_call(func, call_data)?;
+
+    if context.cmd.print_init_calldata {
+        println!("Group {group_id} identity manager {call}");
+        println!("  call data: 0x{}", hex::encode(call_data));
+    } else {
+        debug!("Group {group_id} identity manager {call}");
+    }
+
+    Ok(())
+}
+
+/// Points the identity manager at the semaphore verifier of this deployment,
+/// e.g. after the semaphore verifier was redeployed
+///
+/// Only sends a transaction if the current verifier differs
+#[instrument(skip(context, config))]
+async fn ensure_semaphore_verifier(
+    context: &DeploymentContext,
+    config: &Config,
+    group_id: GroupId,
+    identity_manager_address: Address,
+    semaphore_verifier_address: Address,
+) -> eyre::Result<()> {
+    let impl_abi = context
+        .inspect_abi(config.misc.contract_names.identity_manager_impl_v1())
+        .await?;
+
+    let current_verifier: Address = TransactionBuilder::default()
+        .signer(context.rpc_signer.clone())
+        .abi(impl_abi.clone())
+        .function_name("getSemaphoreVerifier")
+        .args(())
+        .to(identity_manager_address)
+        .context(context)
+        .build()?
+        .call()
+        .await?;
+
+    if current_verifier == semaphore_verifier_address {
+        return Ok(());
+    }
+
+    info!("Updating the semaphore verifier of group {group_id} from {current_verifier:?} to {semaphore_verifier_address:?}");
+
+    TransactionBuilder::default()
+        .signer(context.rpc_signer.clone())
+        .abi(impl_abi)
+        .function_name("setSemaphoreVerifier")
+        .args(semaphore_verifier_address)
+        .to(identity_manager_address)
+        .context(context)
+        .build()?
+        .send()
+        .await?;
+
+    Ok(())
+}
+
+/// Addresses which can be referenced by name in the initializer args config
+pub fn known_addresses(
+    group_lookup_tables: &GroupLookupTables,
+) -> HashMap<&'static str, Address> {
+    let mut known_addresses = HashMap::new();
+
+    let tables = [
+        ("insert_lookup_table", &group_lookup_tables.insert),
+        ("update_lookup_table", &group_lookup_tables.update),
+        ("delete_lookup_table", &group_lookup_tables.delete),
+    ];
+
+    for (name, table) in tables {
+        if let Some(table) = table {
+            known_addresses.insert(name, table.deployment.address);
+        }
+    }
+
+    known_addresses
+}
+
+/// Deploys the identity manager of every group
+///
+/// `record_partial` is passed the deployments so far whenever an
+/// implementation or a group is done, so a resume continues from there.
+///
+/// Groups removed from the config are left out of the result, so the router
+/// step disables their routes.
+pub async fn deploy<R, RF>(
     context: Arc<DeploymentContext>,
     config: Arc<Config>,
     semaphore_verifier_deployment: &SemaphoreVerifierDeployment,
     lookup_tables: &LookupTables,
-) -> eyre::Result<WorldIDIdentityManagersDeployment> {
+    record_partial: R,
+) -> eyre::Result<WorldIDIdentityManagersDeployment>
+where
+    R: Fn(WorldIDIdentityManagersDeployment) -> RF,
+    RF: Future<Output = eyre::Result<()>>,
+{
     let mut groups = HashMap::new();
 
+    // Keeps the groups of the previous report which weren't handled yet
+    let mut partial =
+        context.report.identity_managers.clone().unwrap_or_default();
+
+    let removed_groups = partial.drop_removed_groups(config.as_ref());
+    if !removed_groups.is_empty() {
+        for group_id in &removed_groups {
+            info!("Removing the identity manager of group {group_id}");
+        }
+
+        record_partial(partial.clone()).await?;
+    }
+
     for group_id in config.groups.keys().copied() {
-        let group_deployment = deploy_world_id_identity_manager_for_group(
+        context.set_cost_group(Some(group_id));
+
+        let mut group_deployment = deploy_world_id_identity_manager_for_group(
             context.as_ref(),
             config.as_ref(),
             group_id,
             semaphore_verifier_deployment,
             lookup_tables,
+            |impl_v1_deployment| {
+                partial.pending_impls.insert(group_id, impl_v1_deployment);
+                record_partial(partial.clone())
+            },
         )
         .await?;
 
+        if !group_deployment.proxy_deployment.simulated {
+            ensure_semaphore_verifier(
+                context.as_ref(),
+                config.as_ref(),
+                group_id,
+                group_deployment.proxy_deployment.address,
+                semaphore_verifier_deployment.verifier_address()?,
+            )
+            .await?;
+
+            group_deployment.initialized_version = Some(
+                initialized_version(
+                    &context.rpc_signer,
+                    group_deployment.proxy_deployment.address,
+                )
+                .await?,
+            );
+        }
+
+        partial.pending_impls.remove(&group_id);
+        partial.groups.insert(group_id, group_deployment.clone());
+        record_partial(partial.clone()).await?;
+
         groups.insert(group_id, group_deployment);
     }
 
-    Ok(WorldIDIdentityManagersDeployment { groups })
+    context.set_cost_group(None);
+
+    Ok(WorldIDIdentityManagersDeployment {
+        groups,
+        pending_impls: HashMap::new(),
+    })
 }
 
 #[cfg(test)]
 mod tests {
+    use std::sync::Mutex;
+
     use ethers::types::H160;
     use indoc::indoc;
 
@@ -251,7 +608,13 @@ mod tests {
             impl_v2_deployment: None,
             proxy_deployment: ContractDeployment {
                 address: H160::zero(),
+                simulated: false,
+                source_commit: None,
+                code_hash: None,
+                block_number: None,
+                salt: None,
             },
+            initialized_version: None,
         };
 
         let serialized_actual = serde_yaml::to_string(&actual).unwrap();
@@ -259,4 +622,91 @@ mod tests {
         assert_eq!(actual, expected);
         assert_eq!(serialized_actual, ONLY_PROXY_DEPLOYMENT);
     }
+
+    fn deployment(byte: u8) -> ContractDeployment {
+        ContractDeployment {
+            address: Address::repeat_byte(byte),
+            simulated: false,
+            source_commit: None,
+            code_hash: None,
+            block_number: None,
+            salt: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn resume_after_proxy_failure() {
+        let recorded = Mutex::new(None);
+
+        let result = deploy_resumable(
+            None,
+            || async { Ok(deployment(1)) },
+            |_| async { eyre::bail!("proxy deployment failed") },
+            |impl_v1_deployment| {
+                *recorded.lock().unwrap() = Some(impl_v1_deployment);
+                async { Ok(()) }
+            },
+        )
+        .await;
+
+        assert!(result.is_err());
+
+        let recorded_impl = recorded.into_inner().unwrap().unwrap();
+        assert_eq!(recorded_impl, deployment(1));
+
+        let resumed = deploy_resumable(
+            Some(&recorded_impl),
+            || async { panic!("the implementation is redeployed") },
+            |impl_v1_address| async move {
+                assert_eq!(impl_v1_address, Address::repeat_byte(1));
+                Ok(deployment(2))
+            },
+            |_| async { panic!("nothing new to record") },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(resumed.impl_v1_deployment, Some(deployment(1)));
+        assert_eq!(resumed.proxy_deployment, deployment(2));
+    }
+
+    #[test]
+    fn drop_removed_groups() {
+        let config: Config = serde_yaml::from_str(indoc! { r#"
+            groups:
+              0:
+                tree_depth: 30
+              2:
+                tree_depth: 30
+            misc: {}
+        "# })
+        .unwrap();
+
+        let group = |byte| WorldIdIdentityManagerDeployment {
+            impl_v1_deployment: Some(deployment(byte)),
+            impl_v2_deployment: None,
+            proxy_deployment: deployment(byte + 1),
+            initialized_version: Some(1),
+        };
+
+        let mut deployments = WorldIDIdentityManagersDeployment {
+            groups: HashMap::from([
+                (GroupId(0), group(1)),
+                (GroupId(1), group(3)),
+            ]),
+            pending_impls: HashMap::from([(GroupId(2), deployment(5))]),
+        };
+
+        let removed = deployments.drop_removed_groups(&config);
+
+        assert_eq!(removed, vec![GroupId(1)]);
+        assert_eq!(deployments.groups, HashMap::from([(GroupId(0), group(1))]));
+        assert_eq!(
+            deployments.pending_impls,
+            HashMap::from([(GroupId(2), deployment(5))])
+        );
+
+        // Nothing left to remove once the report is updated
+        assert!(deployments.drop_removed_groups(&config).is_empty());
+    }
 }
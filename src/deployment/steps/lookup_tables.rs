@@ -1,16 +1,17 @@
 use std::collections::{HashMap, HashSet};
+use std::future::Future;
 use std::sync::Arc;
 
 use ethers::types::Address;
-use eyre::ContextCompat;
+use eyre::{Context, ContextCompat};
 use serde::{Deserialize, Serialize};
 use tracing::{info, instrument, warn};
 
-use super::verifiers::Verifiers;
+use super::verifiers::{VerifierDeployment, Verifiers};
 use crate::config::{Config, GroupConfig};
+use crate::deployment::mtb_utils::ProverMode;
 use crate::deployment::DeploymentContext;
 use crate::ethers_utils::TransactionBuilder;
-use crate::forge_utils::{ContractSpec, ForgeInspectAbi};
 use crate::report::contract_deployment::ContractDeployment;
 use crate::types::{BatchSize, GroupId, TreeDepth};
 
@@ -39,26 +40,32 @@ pub struct LookupTable {
 #[instrument(skip_all)]
 async fn deploy_lookup_table(
     context: &DeploymentContext,
+    config: &Config,
 ) -> eyre::Result<ContractDeployment> {
     let insert_lookup_table = context
-        .forge_create(ContractSpec::name("VerifierLookupTable"))
+        .forge_create(config.misc.contract_names.verifier_lookup_table())
         .with_cwd("./world-id-contracts")
         .run()
         .await?;
 
+    context.record_create_cost(&insert_lookup_table).await?;
+
     Ok(insert_lookup_table.into())
 }
 
-#[instrument(skip(context))]
-async fn deploy_lookup_tables(
+#[instrument(skip(context, config, record_partial))]
+async fn deploy_lookup_tables<R, RF>(
     context: Arc<DeploymentContext>,
+    config: &Config,
     group_id: GroupId,
-) -> eyre::Result<GroupLookupTables> {
-    let mut lookup_tables = if let Some(lookup_tables) = context
-        .report
-        .lookup_tables
-        .as_ref()
-        .and_then(|tables| tables.groups.get(&group_id))
+    record_partial: &mut R,
+) -> eyre::Result<GroupLookupTables>
+where
+    R: FnMut(GroupId, GroupLookupTables) -> RF,
+    RF: Future<Output = eyre::Result<()>>,
+{
+    let mut lookup_tables = if let Some(lookup_tables) =
+        context.report.group_lookup_tables(group_id)
     {
         info!("Found existing lookup tables for group {group_id}");
         lookup_tables.clone()
@@ -69,42 +76,60 @@ async fn deploy_lookup_tables(
 
     if lookup_tables.insert.is_none() {
         lookup_tables.insert = Some(LookupTable {
-            deployment: deploy_lookup_table(context.as_ref()).await?,
+            deployment: deploy_lookup_table(context.as_ref(), config).await?,
             entries: HashMap::new(),
         });
+        record_partial(group_id, lookup_tables.clone()).await?;
     }
 
     if lookup_tables.update.is_none() {
         lookup_tables.update = Some(LookupTable {
-            deployment: deploy_lookup_table(context.as_ref()).await?,
+            deployment: deploy_lookup_table(context.as_ref(), config).await?,
             entries: HashMap::new(),
         });
+        record_partial(group_id, lookup_tables.clone()).await?;
     }
 
-    if lookup_tables.delete.is_none() {
+    if lookup_tables.delete.is_none() && !context.cmd.no_deletion {
         lookup_tables.delete = Some(LookupTable {
-            deployment: deploy_lookup_table(context.as_ref()).await?,
+            deployment: deploy_lookup_table(context.as_ref(), config).await?,
             entries: HashMap::new(),
         });
+        record_partial(group_id, lookup_tables.clone()).await?;
     }
 
     Ok(lookup_tables)
 }
 
 #[instrument(skip(context, verifier_abi, verifiers))]
-async fn associate_group_batch_size_verifier(
+pub async fn associate_group_batch_size_verifier(
     context: Arc<DeploymentContext>,
     verifier_abi: ethers::abi::Abi,
     lookup_table_address: Address,
     group_id: GroupId,
-    tree_depth: TreeDepth,
+    group_tree_depth: TreeDepth,
     batch_size: BatchSize,
     verifiers: &Verifiers,
 ) -> eyre::Result<Address> {
     let verifier = verifiers
         .verifiers
-        .get(&(tree_depth, batch_size))
-        .with_context(|| format!("Failed to get verifier for batch size {batch_size} and tree_depth {tree_depth}"))?;
+        .get(&(group_tree_depth, batch_size))
+        .with_context(|| format!("Failed to get verifier for batch size {batch_size} and tree_depth {group_tree_depth}"))?;
+
+    check_verifier_tree_depth(verifier, group_id, group_tree_depth)?;
+
+    let current_verifier = current_verifier(
+        context.as_ref(),
+        &verifier_abi,
+        lookup_table_address,
+        batch_size,
+    )
+    .await?;
+
+    if current_verifier == Some(verifier.deployment.address) {
+        info!("Verifier for batch size {batch_size} is already associated on-chain");
+        return Ok(verifier.deployment.address);
+    }
 
     let signer = &context.rpc_signer;
 
@@ -122,123 +147,205 @@ async fn associate_group_batch_size_verifier(
     Ok(verifier.deployment.address)
 }
 
+/// Errors if the verifier was recorded as generated for another tree depth
+/// than the group's, e.g. in a hand-edited report
+fn check_verifier_tree_depth(
+    verifier: &VerifierDeployment,
+    group_id: GroupId,
+    group_tree_depth: TreeDepth,
+) -> eyre::Result<()> {
+    match verifier.tree_depth {
+        Some(verifier_tree_depth)
+            if verifier_tree_depth != group_tree_depth =>
+        {
+            eyre::bail!(
+                "Verifier {:?} was generated for tree depth {verifier_tree_depth}, but group {group_id} has tree depth {group_tree_depth}",
+                verifier.deployment.address
+            )
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Reads the verifier currently associated with `batch_size` on-chain
+///
+/// `getVerifierFor` reverts for batch sizes without a verifier, so a revert
+/// (or the zero address) means there's none. Any other failure, e.g. of the
+/// RPC, is an error.
+pub async fn current_verifier(
+    context: &DeploymentContext,
+    lookup_abi: &ethers::abi::Abi,
+    lookup_table_address: Address,
+    batch_size: BatchSize,
+) -> eyre::Result<Option<Address>> {
+    let verifier: Option<Address> = TransactionBuilder::default()
+        .signer(context.rpc_signer.clone())
+        .abi(lookup_abi.clone())
+        .function_name("getVerifierFor")
+        .args(batch_size.0 as u64)
+        .to(lookup_table_address)
+        .context(context)
+        .build()?
+        .call_unless_reverted()
+        .await?;
+
+    Ok(verifier.filter(|verifier| !verifier.is_zero()))
+}
+
+/// Deploys the lookup tables of every group and associates the configured
+/// verifiers
+///
+/// `record_partial` is passed the lookup tables so far whenever a table is
+/// deployed or a verifier associated, so a resume continues from there.
 #[instrument(name = "lookup_tables", skip_all)]
-pub async fn deploy(
+pub async fn deploy<R, RF>(
     context: Arc<DeploymentContext>,
     config: Arc<Config>,
     insertion_verifiers: &Verifiers,
-    deletion_verifiers: &Verifiers,
-) -> eyre::Result<LookupTables> {
+    deletion_verifiers: Option<&Verifiers>,
+    record_partial: R,
+) -> eyre::Result<LookupTables>
+where
+    R: Fn(LookupTables) -> RF,
+    RF: Future<Output = eyre::Result<()>>,
+{
     let mut by_group = HashMap::new();
 
+    // Keeps the groups of the previous report which weren't handled yet
+    let mut partial = context.report.lookup_tables.clone().unwrap_or_default();
+    let mut record_group = |group_id, group_lookup_tables| {
+        partial.groups.insert(group_id, group_lookup_tables);
+        record_partial(partial.clone())
+    };
+
     for group in config.groups.keys() {
-        let lookup_tables =
-            deploy_lookup_tables(context.clone(), *group).await?;
+        context.set_cost_group(Some(*group));
+
+        let lookup_tables = deploy_lookup_tables(
+            context.clone(),
+            &config,
+            *group,
+            &mut record_group,
+        )
+        .await?;
 
         by_group.insert(*group, lookup_tables);
     }
 
-    let lookup_abi =
-        ForgeInspectAbi::new(ContractSpec::name("VerifierLookupTable"))
-            .with_cwd("./world-id-contracts")
-            .run()
-            .await?;
+    let lookup_abi = context
+        .inspect_abi(config.misc.contract_names.verifier_lookup_table())
+        .await?;
 
     // New or existing verifiers
     for (group_id, group_config) in &config.groups {
-        let group = by_group.get(group_id).unwrap();
-
         let group_id = *group_id;
+        let group = by_group.get_mut(&group_id).unwrap();
 
-        let mut insert_updates = HashMap::new();
-        let mut delete_updates = HashMap::new();
+        context.set_cost_group(Some(group_id));
 
-        if let Some(insert) = group.insert.as_ref() {
+        if group.insert.is_some() {
             let config_batch_sizes: HashSet<_> =
                 group_config.insertion_batch_sizes.iter().copied().collect();
 
-            insert_updates = update_lookup_table(
+            update_lookup_table(
                 context.clone(),
                 insertion_verifiers,
                 group_id,
                 group_config,
-                insert,
+                group,
+                ProverMode::Insertion,
                 &config_batch_sizes,
                 &lookup_abi,
+                &mut record_group,
             )
             .await?;
         }
 
-        if let Some(delete) = group.delete.as_ref() {
+        if deletion_verifiers.is_none()
+            && !group_config.deletion_batch_sizes.is_empty()
+        {
+            warn!("Ignoring the deletion batch sizes of group {group_id} due to --no-deletion");
+        }
+
+        // Without deletion verifiers (i.e. --no-deletion) the delete lookup
+        // table is left alone
+        if let (true, Some(deletion_verifiers)) =
+            (group.delete.is_some(), deletion_verifiers)
+        {
             let config_batch_sizes: HashSet<_> =
                 group_config.deletion_batch_sizes.iter().copied().collect();
 
-            delete_updates = update_lookup_table(
+            update_lookup_table(
                 context.clone(),
                 deletion_verifiers,
                 group_id,
                 group_config,
-                delete,
+                group,
+                ProverMode::Deletion,
                 &config_batch_sizes,
                 &lookup_abi,
+                &mut record_group,
             )
             .await?;
         }
-
-        for ((group_id, batch_size), address) in insert_updates {
-            by_group
-                .get_mut(&group_id)
-                .unwrap()
-                .insert
-                .as_mut()
-                .unwrap()
-                .entries
-                .insert(batch_size, address);
-        }
-
-        for ((group_id, batch_size), address) in delete_updates {
-            by_group
-                .get_mut(&group_id)
-                .unwrap()
-                .delete
-                .as_mut()
-                .unwrap()
-                .entries
-                .insert(batch_size, address);
-        }
     }
 
+    context.set_cost_group(None);
+
     Ok(LookupTables { groups: by_group })
 }
 
-async fn update_lookup_table(
+/// The lookup table of `group_lookup_tables` for verifiers of `mode`
+fn lookup_table_mut(
+    group_lookup_tables: &mut GroupLookupTables,
+    mode: ProverMode,
+) -> Option<&mut LookupTable> {
+    match mode {
+        ProverMode::Insertion => group_lookup_tables.insert.as_mut(),
+        ProverMode::Deletion => group_lookup_tables.delete.as_mut(),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn update_lookup_table<R, RF>(
     context: Arc<DeploymentContext>,
     verifiers: &Verifiers,
     group_id: GroupId,
     group_config: &GroupConfig,
-    table: &LookupTable,
+    group_lookup_tables: &mut GroupLookupTables,
+    mode: ProverMode,
     config_batch_sizes: &HashSet<BatchSize>,
     lookup_abi: &ethers::abi::Abi,
-) -> eyre::Result<HashMap<(GroupId, BatchSize), Address>> {
+    record_partial: &mut R,
+) -> eyre::Result<()>
+where
+    R: FnMut(GroupId, GroupLookupTables) -> RF,
+    RF: Future<Output = eyre::Result<()>>,
+{
+    let table =
+        lookup_table_mut(group_lookup_tables, mode).with_context(|| {
+            format!("Group {group_id} has no {mode} lookup table")
+        })?;
+
     let report_batch_sizes =
         table.entries.keys().copied().collect::<HashSet<_>>();
 
-    let batch_sizes_to_add_or_update =
-        config_batch_sizes.difference(&report_batch_sizes);
     let batch_sizes_to_disable =
         report_batch_sizes.difference(config_batch_sizes);
 
-    info!("Going to update batch sizes for group {group_id}: {batch_sizes_to_add_or_update:?}");
+    // The report might be stale relative to the chain, so every configured
+    // batch size is checked on-chain and only updated when it differs
+    info!("Going to check batch sizes for group {group_id}: {config_batch_sizes:?}");
     for batch_size_to_disable in batch_sizes_to_disable {
         warn!("Insertion batch size {batch_size_to_disable} for group {group_id} will not be disabled - remove it manually");
     }
 
     let table_deployment_address = table.deployment.address;
+    let tree_depth = group_config
+        .tree_depth()
+        .with_context(|| format!("Group {group_id}"))?;
 
-    let mut updates = HashMap::new();
-
-    for batch_size in batch_sizes_to_add_or_update {
-        let tree_depth = group_config.tree_depth;
+    for batch_size in config_batch_sizes {
         let batch_size = *batch_size;
 
         let address = associate_group_batch_size_verifier(
@@ -252,8 +359,50 @@ async fn update_lookup_table(
         )
         .await?;
 
-        updates.insert((group_id, batch_size), address);
+        lookup_table_mut(group_lookup_tables, mode)
+            .context("Missing lookup table")?
+            .entries
+            .insert(batch_size, address);
+        record_partial(group_id, group_lookup_tables.clone()).await?;
     }
 
-    Ok(updates)
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verifier_of_another_tree_depth() {
+        let verifier = VerifierDeployment {
+            deployment: ContractDeployment {
+                address: Address::repeat_byte(1),
+                simulated: false,
+                source_commit: None,
+                code_hash: None,
+                block_number: None,
+                salt: None,
+            },
+            tree_depth: Some(TreeDepth(20)),
+            source_hash: None,
+            keys_hash: None,
+        };
+
+        check_verifier_tree_depth(&verifier, GroupId(0), TreeDepth(20))
+            .unwrap();
+        assert!(check_verifier_tree_depth(
+            &verifier,
+            GroupId(0),
+            TreeDepth(30)
+        )
+        .is_err());
+
+        let unrecorded = VerifierDeployment {
+            tree_depth: None,
+            ..verifier
+        };
+        check_verifier_tree_depth(&unrecorded, GroupId(0), TreeDepth(30))
+            .unwrap();
+    }
 }
@@ -0,0 +1,174 @@
+use ethers::types::Address;
+use tracing::{info, instrument, warn};
+
+use crate::config::Config;
+use crate::deployment::DeploymentContext;
+use crate::ethers_utils::TransactionBuilder;
+use crate::forge_utils::ContractSpec;
+use crate::report::contract_deployment::ContractDeployment;
+
+/// Starts the transfer of ownership of `address` to `new_owner`
+///
+/// The contracts use a two step ownership transfer, so `new_owner` has to
+/// accept the ownership for it to take effect.
+#[instrument(skip(context))]
+pub async fn transfer_ownership(
+    context: &DeploymentContext,
+    impl_spec: ContractSpec,
+    address: Address,
+    new_owner: Address,
+) -> eyre::Result<()> {
+    warn!("Proposing {new_owner:?} as the owner of {address:?}");
+
+    let impl_abi = context.inspect_abi(impl_spec).await?;
+
+    TransactionBuilder::default()
+        .signer(context.rpc_signer.clone())
+        .abi(impl_abi)
+        .function_name("transferOwnership")
+        .args(new_owner)
+        .to(address)
+        .context(context)
+        .build()?
+        .send()
+        .await?;
+
+    Ok(())
+}
+
+/// Reads the current and the pending owner of `address`
+#[instrument(skip(context))]
+pub async fn owners(
+    context: &DeploymentContext,
+    impl_spec: ContractSpec,
+    address: Address,
+) -> eyre::Result<(Address, Address)> {
+    let impl_abi = context.inspect_abi(impl_spec).await?;
+
+    let owner: Address = TransactionBuilder::default()
+        .signer(context.rpc_signer.clone())
+        .abi(impl_abi.clone())
+        .function_name("owner")
+        .args(())
+        .to(address)
+        .context(context)
+        .build()?
+        .call()
+        .await?;
+
+    let pending_owner: Address = TransactionBuilder::default()
+        .signer(context.rpc_signer.clone())
+        .abi(impl_abi)
+        .function_name("pendingOwner")
+        .args(())
+        .to(address)
+        .context(context)
+        .build()?
+        .call()
+        .await?;
+
+    Ok((owner, pending_owner))
+}
+
+/// Checks up front that the deployer owns the contracts of the report, since
+/// the deployment may call owner-only functions on them (e.g.
+/// `updateVerifier`, `updateGroup` or `upgradeToAndCall`)
+///
+/// Contracts which aren't in the report yet are created by the deployer and
+/// so owned by it. Fails instead of warning if `strict`.
+#[instrument(skip_all)]
+pub async fn check_owners(
+    context: &DeploymentContext,
+    config: &Config,
+    strict: bool,
+) -> eyre::Result<()> {
+    let report = &context.report;
+    let contract_names = &config.misc.contract_names;
+
+    let mut contracts: Vec<(String, ContractSpec, &ContractDeployment)> =
+        vec![];
+
+    if let Some(router) = report.world_id_router.as_ref() {
+        contracts.push((
+            "router".to_string(),
+            contract_names.router_impl(),
+            &router.proxy_deployment,
+        ));
+    }
+
+    if let Some(identity_managers) = report.identity_managers.as_ref() {
+        let mut group_ids: Vec<_> =
+            identity_managers.groups.keys().copied().collect();
+        group_ids.sort();
+
+        for group_id in group_ids {
+            contracts.push((
+                format!("identity manager of group {group_id}"),
+                contract_names.identity_manager_impl_v1(),
+                &identity_managers.groups[&group_id].proxy_deployment,
+            ));
+        }
+    }
+
+    if let Some(lookup_tables) = report.lookup_tables.as_ref() {
+        let mut group_ids: Vec<_> =
+            lookup_tables.groups.keys().copied().collect();
+        group_ids.sort();
+
+        for group_id in group_ids {
+            let group = &lookup_tables.groups[&group_id];
+
+            for (name, table) in [
+                ("insert", group.insert.as_ref()),
+                ("update", group.update.as_ref()),
+                ("delete", group.delete.as_ref()),
+            ] {
+                if let Some(table) = table {
+                    contracts.push((
+                        format!("{name} lookup table of group {group_id}"),
+                        contract_names.verifier_lookup_table(),
+                        &table.deployment,
+                    ));
+                }
+            }
+        }
+    }
+
+    let mut not_owned = vec![];
+
+    for (name, impl_spec, deployment) in contracts {
+        if deployment.simulated {
+            continue;
+        }
+
+        let (owner, _) = owners(context, impl_spec, deployment.address).await?;
+
+        if owner != context.deployer {
+            not_owned.push(format!(
+                "{name} at {:?} is owned by {owner:?}",
+                deployment.address
+            ));
+        }
+    }
+
+    if not_owned.is_empty() {
+        info!("The deployer owns every contract of the report");
+        return Ok(());
+    }
+
+    let not_owned = not_owned.join(", ");
+
+    if strict {
+        eyre::bail!(
+            "The deployer {:?} doesn't own all contracts of the report: {not_owned}",
+            context.deployer
+        );
+    }
+
+    warn!(
+        "The deployer {:?} doesn't own all contracts of the report, owner-only calls on them will revert: {not_owned}",
+        context.deployer
+    );
+
+    Ok(())
+}
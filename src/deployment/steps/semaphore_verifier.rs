@@ -1,84 +1,350 @@
+use std::future::Future;
 use std::sync::Arc;
 
 use ethers::types::Address;
+use eyre::{Context, ContextCompat};
 use serde::{Deserialize, Serialize};
-use tracing::instrument;
+use tracing::{info, instrument};
 
-use crate::config::Config;
+use crate::config::{Config, ExistingSemaphoreVerifier};
 use crate::deployment::DeploymentContext;
-use crate::forge_utils::{ContractSpec, ExternalDep};
+use crate::ethers_utils::{code_hash, ensure_has_code};
+use crate::forge_utils::ExternalDep;
 use crate::report::contract_deployment::ContractDeployment;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SemaphoreVerifierDeployment {
-    pub verifier_deployment: ContractDeployment,
+    /// Unset while only the Pairing library of the step has been deployed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verifier_deployment: Option<ContractDeployment>,
     pub pairing_deployment: ContractDeployment,
 }
 
+impl SemaphoreVerifierDeployment {
+    /// Address of the verifier, fails if the step didn't finish
+    pub fn verifier_address(&self) -> eyre::Result<Address> {
+        let verifier_deployment = self
+            .verifier_deployment
+            .as_ref()
+            .context("The semaphore verifier hasn't been deployed")?;
+
+        Ok(verifier_deployment.address)
+    }
+}
+
+/// Records a semaphore verifier from the config without deploying anything
+///
+/// The verifier's code has to match `code_hashes.semaphore_verifier`, if set,
+/// like a deployed one
+#[instrument(skip_all)]
+async fn use_existing_semaphore_verifier(
+    context: &DeploymentContext,
+    config: &Config,
+    existing: ExistingSemaphoreVerifier,
+) -> eyre::Result<SemaphoreVerifierDeployment> {
+    info!(
+        "Using configured semaphore verifier at {:?} (Pairing library at {:?})",
+        existing.verifier, existing.pairing
+    );
+
+    check_previous_verifier(
+        context.report.semaphore_verifier.as_ref(),
+        existing.verifier,
+    )?;
+
+    let verifier_code_hash = code_hash(&context.rpc_signer, existing.verifier)
+        .await
+        .context("Validating configured semaphore verifier")?;
+    ensure_has_code(&context.rpc_signer, existing.pairing)
+        .await
+        .context("Validating configured Pairing library")?;
+
+    if let Some(expected) = config.misc.code_hashes.semaphore_verifier {
+        if verifier_code_hash != expected {
+            eyre::bail!(
+                "Code of the configured semaphore verifier at {:?} hashes to {verifier_code_hash:?}, expected {expected:?}",
+                existing.verifier
+            );
+        }
+    }
+
+    let existing_deployment = |address, code_hash| ContractDeployment {
+        address,
+        simulated: false,
+        source_commit: None,
+        code_hash,
+        block_number: None,
+        salt: None,
+    };
+
+    Ok(SemaphoreVerifierDeployment {
+        verifier_deployment: Some(existing_deployment(
+            existing.verifier,
+            Some(verifier_code_hash),
+        )),
+        pairing_deployment: existing_deployment(existing.pairing, None),
+    })
+}
+
+/// Errors if the report already has a different semaphore verifier than the
+/// configured one, which would otherwise be replaced silently
+fn check_previous_verifier(
+    previous: Option<&SemaphoreVerifierDeployment>,
+    verifier: Address,
+) -> eyre::Result<()> {
+    let Some(previous) =
+        previous.and_then(|previous| previous.verifier_deployment.as_ref())
+    else {
+        return Ok(());
+    };
+
+    if previous.address != verifier {
+        eyre::bail!(
+            "The report has the semaphore verifier {:?}, but {verifier:?} is configured for this chain - remove semaphore_verifier from the report to switch to it",
+            previous.address
+        );
+    }
+
+    Ok(())
+}
+
 #[instrument(skip_all)]
 async fn deploy_semaphore_pairing_library(
     context: &DeploymentContext,
+    config: &Config,
 ) -> eyre::Result<ContractDeployment> {
-    if let Some(previous_deployment) =
-        context.report.semaphore_verifier.as_ref()
+    if let Some(address) = config
+        .misc
+        .pairing_library_addresses
+        .get(&context.chain_id)
+        .copied()
     {
-        return Ok(previous_deployment.pairing_deployment.clone());
+        info!("Using configured Pairing library at {address:?}");
+
+        ensure_has_code(&context.rpc_signer, address)
+            .await
+            .context("Validating configured Pairing library")?;
+
+        return Ok(ContractDeployment {
+            address,
+            simulated: false,
+            source_commit: None,
+            code_hash: None,
+            block_number: None,
+            salt: None,
+        });
     }
 
-    let contract_spec = ContractSpec::name("Pairing");
+    let contract_spec = config.misc.contract_names.pairing();
 
     let output = context
-        .forge_create(contract_spec)
+        .forge_create_deterministic(contract_spec, "pairing", None)
         .with_cwd("./world-id-contracts")
         .no_verify()
         .run()
         .await?;
 
+    context.record_create_cost(&output).await?;
+
     Ok(output.into())
 }
 
 #[instrument(skip_all)]
 async fn deploy_semaphore_verifier(
     context: &DeploymentContext,
+    config: &Config,
     pairing_address: Address,
 ) -> eyre::Result<ContractDeployment> {
-    if let Some(previous_deployment) =
-        context.report.semaphore_verifier.as_ref()
-    {
-        return Ok(previous_deployment.verifier_deployment.clone());
-    }
-
-    let contract_spec: ContractSpec = ContractSpec::name("SemaphoreVerifier");
+    let contract_names = &config.misc.contract_names;
 
     let output = context
-        .forge_create(contract_spec)
+        .forge_create_deterministic(
+            contract_names.semaphore_verifier(),
+            "semaphore_verifier",
+            None,
+        )
         .with_cwd("./world-id-contracts")
-        .with_external_dep(ExternalDep::path_name_address(
-            "./lib/semaphore/packages/contracts/contracts/base/Pairing.sol",
-            "Pairing",
-            pairing_address,
-        ))
+        .with_expected_code_hash(config.misc.code_hashes.semaphore_verifier)
+        .with_external_dep(ExternalDep {
+            contract_spec: contract_names.pairing(),
+            address: pairing_address,
+        })
         .no_verify()
         .run()
         .await?;
 
+    context.record_create_cost(&output).await?;
+
     Ok(output.into())
 }
 
-pub async fn deploy(
+pub async fn deploy<R, RF>(
     context: Arc<DeploymentContext>,
-    _config: Arc<Config>,
-) -> eyre::Result<SemaphoreVerifierDeployment> {
-    let pairing_deployment =
-        deploy_semaphore_pairing_library(context.as_ref()).await?;
+    config: Arc<Config>,
+    record_partial: R,
+) -> eyre::Result<SemaphoreVerifierDeployment>
+where
+    R: FnOnce(SemaphoreVerifierDeployment) -> RF,
+    RF: Future<Output = eyre::Result<()>>,
+{
+    if let Some(existing) = config
+        .misc
+        .semaphore_verifier_addresses
+        .get(&context.chain_id)
+        .copied()
+    {
+        return use_existing_semaphore_verifier(
+            context.as_ref(),
+            config.as_ref(),
+            existing,
+        )
+        .await;
+    }
+
+    deploy_resumable(
+        context.report.semaphore_verifier.as_ref(),
+        || deploy_semaphore_pairing_library(context.as_ref(), config.as_ref()),
+        |pairing_address| {
+            deploy_semaphore_verifier(
+                context.as_ref(),
+                config.as_ref(),
+                pairing_address,
+            )
+        },
+        record_partial,
+    )
+    .await
+}
 
-    let pairing_address = pairing_deployment.address;
+/// Deploys the Pairing library and then the verifier linked against it,
+/// reusing whatever a previous (possibly failed) run already deployed
+///
+/// The Pairing library is passed to `record_partial` as soon as it's
+/// deployed, so it isn't redeployed if deploying the verifier fails.
+async fn deploy_resumable<P, PF, V, VF, R, RF>(
+    previous: Option<&SemaphoreVerifierDeployment>,
+    deploy_pairing: P,
+    deploy_verifier: V,
+    record_partial: R,
+) -> eyre::Result<SemaphoreVerifierDeployment>
+where
+    P: FnOnce() -> PF,
+    PF: Future<Output = eyre::Result<ContractDeployment>>,
+    V: FnOnce(Address) -> VF,
+    VF: Future<Output = eyre::Result<ContractDeployment>>,
+    R: FnOnce(SemaphoreVerifierDeployment) -> RF,
+    RF: Future<Output = eyre::Result<()>>,
+{
+    let pairing_deployment = match previous {
+        Some(previous) => previous.pairing_deployment.clone(),
+        None => {
+            let pairing_deployment = deploy_pairing().await?;
 
-    let verifier_deployment =
-        deploy_semaphore_verifier(context.as_ref(), pairing_address).await?;
+            record_partial(SemaphoreVerifierDeployment {
+                verifier_deployment: None,
+                pairing_deployment: pairing_deployment.clone(),
+            })
+            .await?;
+
+            pairing_deployment
+        }
+    };
+
+    let verifier_deployment = match previous
+        .and_then(|previous| previous.verifier_deployment.clone())
+    {
+        Some(verifier_deployment) => verifier_deployment,
+        None => {
+            info!("Deploying the semaphore verifier");
+            deploy_verifier(pairing_deployment.address).await?
+        }
+    };
 
     Ok(SemaphoreVerifierDeployment {
-        verifier_deployment,
+        verifier_deployment: Some(verifier_deployment),
         pairing_deployment,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    fn deployment(byte: u8) -> ContractDeployment {
+        ContractDeployment {
+            address: Address::repeat_byte(byte),
+            simulated: false,
+            source_commit: None,
+            code_hash: None,
+            block_number: None,
+            salt: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn resume_after_verifier_failure() {
+        let recorded = Mutex::new(None);
+
+        let result = deploy_resumable(
+            None,
+            || async { Ok(deployment(1)) },
+            |_| async { eyre::bail!("verifier deployment failed") },
+            |partial| {
+                *recorded.lock().unwrap() = Some(partial);
+                async { Ok(()) }
+            },
+        )
+        .await;
+
+        assert!(result.is_err());
+
+        let partial = recorded.into_inner().unwrap().unwrap();
+        assert_eq!(partial.pairing_deployment, deployment(1));
+        assert!(partial.verifier_deployment.is_none());
+
+        let resumed = deploy_resumable(
+            Some(&partial),
+            || async { panic!("the pairing library is redeployed") },
+            |pairing_address| async move {
+                assert_eq!(pairing_address, Address::repeat_byte(1));
+                Ok(deployment(2))
+            },
+            |_| async { panic!("nothing new to record") },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(resumed.pairing_deployment, deployment(1));
+        assert_eq!(resumed.verifier_deployment, Some(deployment(2)));
+    }
+
+    #[test]
+    fn configured_verifier_differs_from_the_report() {
+        let previous = SemaphoreVerifierDeployment {
+            verifier_deployment: Some(deployment(0x22)),
+            pairing_deployment: deployment(0x11),
+        };
+
+        check_previous_verifier(None, Address::repeat_byte(0x33)).unwrap();
+        check_previous_verifier(Some(&previous), Address::repeat_byte(0x22))
+            .unwrap();
+        assert!(check_previous_verifier(
+            Some(&previous),
+            Address::repeat_byte(0x33)
+        )
+        .is_err());
+
+        // Only the Pairing library was deployed by a previous run
+        let pairing_only = SemaphoreVerifierDeployment {
+            verifier_deployment: None,
+            ..previous
+        };
+        check_previous_verifier(
+            Some(&pairing_only),
+            Address::repeat_byte(0x33),
+        )
+        .unwrap();
+    }
+}
@@ -0,0 +1,154 @@
+use std::sync::Arc;
+
+use ethers::types::Address;
+use serde::{Deserialize, Serialize};
+use tracing::{info, instrument, warn};
+
+use super::identity_manager::WorldIDIdentityManagersDeployment;
+use super::ownership::{owners, transfer_ownership};
+use super::world_id_router::WorldIdRouterDeployment;
+use crate::config::{Config, TimelockConfig};
+use crate::deployment::DeploymentContext;
+use crate::forge_utils::ContractSpec;
+use crate::report::contract_deployment::ContractDeployment;
+
+pub const TIMELOCK_SPEC: &str = "./lib/openzeppelin-contracts/contracts/governance/TimelockController.sol:TimelockController";
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TimelockDeployment {
+    pub deployment: ContractDeployment,
+    pub min_delay: u64,
+    pub proposers: Vec<Address>,
+    pub executors: Vec<Address>,
+    pub admin: Address,
+}
+
+#[instrument(skip_all)]
+async fn deploy_timelock(
+    context: &DeploymentContext,
+    timelock_config: &TimelockConfig,
+) -> eyre::Result<TimelockDeployment> {
+    let admin = timelock_config.admin.unwrap_or_default();
+
+    if let Some(previous_deployment) = context.report.timelock.as_ref() {
+        if previous_deployment.min_delay != timelock_config.min_delay
+            || previous_deployment.proposers != timelock_config.proposers
+            || previous_deployment.executors != timelock_config.executors
+            || previous_deployment.admin != admin
+        {
+            warn!("The timelock config changed since the timelock was deployed - the deployed timelock is kept");
+        }
+
+        return Ok(previous_deployment.clone());
+    }
+
+    let contract_spec: ContractSpec = timelock_config
+        .contract
+        .as_deref()
+        .unwrap_or(TIMELOCK_SPEC)
+        .parse()?;
+
+    let output = context
+        .forge_create(contract_spec)
+        .with_cwd("./world-id-contracts")
+        .with_constructor_arg(timelock_config.min_delay)
+        .with_constructor_arg(format_addresses(&timelock_config.proposers))
+        .with_constructor_arg(format_addresses(&timelock_config.executors))
+        .with_constructor_arg(format!("{admin:?}"))
+        .run()
+        .await?;
+
+    context.record_create_cost(&output).await?;
+
+    Ok(TimelockDeployment {
+        deployment: output.into(),
+        min_delay: timelock_config.min_delay,
+        proposers: timelock_config.proposers.clone(),
+        executors: timelock_config.executors.clone(),
+        admin,
+    })
+}
+
+/// Hands ownership of `deployment` over to the timelock, unless it's already
+/// owned by (or pending transfer to) the timelock
+#[instrument(skip(context))]
+async fn ensure_owned_by_timelock(
+    context: &DeploymentContext,
+    impl_spec: ContractSpec,
+    deployment: &ContractDeployment,
+    timelock: Address,
+) -> eyre::Result<()> {
+    // Simulated contracts don't exist on-chain yet
+    if !deployment.simulated {
+        let (owner, pending_owner) =
+            owners(context, impl_spec.clone(), deployment.address).await?;
+
+        if owner == timelock {
+            return Ok(());
+        }
+
+        if pending_owner == timelock {
+            info!(
+                "Ownership of {:?} is pending acceptance by the timelock",
+                deployment.address
+            );
+            return Ok(());
+        }
+    }
+
+    transfer_ownership(context, impl_spec, deployment.address, timelock).await
+}
+
+#[instrument(name = "timelock", skip_all)]
+pub async fn deploy(
+    context: Arc<DeploymentContext>,
+    config: Arc<Config>,
+    identity_managers: &WorldIDIdentityManagersDeployment,
+    world_id_router: &WorldIdRouterDeployment,
+) -> eyre::Result<Option<TimelockDeployment>> {
+    let Some(timelock_config) = config.misc.timelock.as_ref() else {
+        return Ok(context.report.timelock.clone());
+    };
+
+    let timelock = deploy_timelock(context.as_ref(), timelock_config).await?;
+    let timelock_address = timelock.deployment.address;
+
+    ensure_owned_by_timelock(
+        context.as_ref(),
+        config.misc.contract_names.router_impl(),
+        &world_id_router.proxy_deployment,
+        timelock_address,
+    )
+    .await?;
+
+    let mut group_ids: Vec<_> =
+        identity_managers.groups.keys().copied().collect();
+    group_ids.sort();
+
+    for group_id in group_ids {
+        context.set_cost_group(Some(group_id));
+
+        ensure_owned_by_timelock(
+            context.as_ref(),
+            config.misc.contract_names.identity_manager_impl_v1(),
+            &identity_managers.groups[&group_id].proxy_deployment,
+            timelock_address,
+        )
+        .await?;
+    }
+
+    context.set_cost_group(None);
+
+    warn!("The contracts use two step ownership transfers - the timelock {timelock_address:?} has to schedule and execute acceptOwnership() on the router and identity managers");
+
+    Ok(Some(timelock))
+}
+
+fn format_addresses(addresses: &[Address]) -> String {
+    let addresses: Vec<_> = addresses
+        .iter()
+        .map(|address| format!("{address:?}"))
+        .collect();
+
+    format!("[{}]", addresses.join(","))
+}
@@ -1,19 +1,31 @@
 use std::collections::HashMap;
-use std::path::Path;
+use std::future::Future;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
 
-use eyre::ContextCompat;
+use ethers::types::H256;
+use eyre::{Context, ContextCompat};
 use serde::{Deserialize, Serialize};
-use tracing::{info, instrument};
+use tiny_keccak::{Hasher, Keccak};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tracing::{info, instrument, warn};
 
 use crate::config::Config;
 use crate::deployment::mtb_utils::{
-    download_semaphore_mtb_binary, generate_keys, generate_verifier_contract,
-    ProverMode, MTB_BIN,
+    download_semaphore_mtb_binary, estimate_setup, generate_keys,
+    generate_verifier_contract, keys_filename, read_setup_timings,
+    verifier_contract_filename, ProverMode, ESTIMATED_VERIFIER_CONTRACT_BYTES,
+    MTB_BIN,
 };
-use crate::deployment::{DeploymentContext, KEYS_DIR, VERIFIER_CONTRACTS_DIR};
+use crate::deployment::{
+    DeploymentContext, KEYS_DIR, VERIFIER_CONTRACTS_DIR, VERIFIER_SOURCES_DIR,
+};
+use crate::forge_utils::build::FORGE_BUILD_LOCK;
 use crate::forge_utils::ContractSpec;
 use crate::report::contract_deployment::ContractDeployment;
+use crate::report::Report;
+use crate::serde_utils;
 use crate::types::{BatchSize, TreeDepth};
 
 #[derive(Clone, Serialize, Deserialize, Debug, Default)]
@@ -24,6 +36,15 @@ pub struct Verifiers {
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct VerifierDeployment {
     pub deployment: ContractDeployment,
+    /// The tree depth the verifier was generated for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tree_depth: Option<TreeDepth>,
+    /// Keccak hash of the generated verifier contract source
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_hash: Option<H256>,
+    /// Keccak hash of the MTB keys the verifier was generated from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keys_hash: Option<H256>,
 }
 
 #[instrument(skip(context, verifier_contract))]
@@ -36,16 +57,25 @@ pub async fn deploy_verifier_contract(
 ) -> eyre::Result<ContractDeployment> {
     let verifier_contract = verifier_contract.as_ref().canonicalize()?;
 
-    if let Some(existing_deployment) = context
-        .report
-        .insertion_verifiers
-        .as_ref()
-        .and_then(|x| x.verifiers.get(&(tree_depth, batch_size)))
+    if let Some(existing_deployment) =
+        previous_verifier(&context.report, mode, tree_depth, batch_size)
     {
-        info!("Found previous verifier deployment for tree depth {tree_depth} and batch size {batch_size} at {:?}", existing_deployment.deployment.address);
+        info!("Found previous {mode} verifier deployment for tree depth {tree_depth} and batch size {batch_size} at {:?}", existing_deployment.deployment.address);
         return Ok(existing_deployment.deployment.clone());
     }
 
+    // The create compiles the verifier into the shared project, and
+    // concurrent deployments share the isolated sources
+    let _guard = FORGE_BUILD_LOCK.lock().await;
+
+    // Every generated verifier defines a contract named `Verifier`, so each
+    // one is compiled from a directory of its own
+    let verifier_contract = isolate_verifier_contract(
+        &verifier_contract,
+        context.cache_path(VERIFIER_SOURCES_DIR),
+    )
+    .await?;
+
     let verifier_contract_parent = verifier_contract
         .parent()
         .context("Missing verifier contract parent directory")?;
@@ -56,73 +86,521 @@ pub async fn deploy_verifier_contract(
     tracing::info!("Deploying Verifier with {contract_spec}");
 
     let output = context
-        .forge_create(contract_spec.clone())
+        .forge_create_deterministic(
+            contract_spec.clone(),
+            &format!("{mode}_verifier_{tree_depth}_{batch_size}"),
+            None,
+        )
         .with_cwd("./world-id-contracts")
         .with_override_contract_source(verifier_contract_parent)
         .no_verify()
         .run()
         .await?;
 
+    context.record_create_cost(&output).await?;
+
     Ok(output.into())
 }
 
-#[instrument(name = "verifiers", skip(context, config))]
-pub async fn deploy(
+/// Deploys the verifier of every configured tree depth and batch size
+///
+/// `record_partial` is passed the verifiers so far whenever one is done, so a
+/// resume doesn't redeploy them.
+#[instrument(name = "verifiers", skip(context, config, record_partial))]
+pub async fn deploy<R, RF>(
     context: Arc<DeploymentContext>,
     config: Arc<Config>,
     mode: ProverMode,
-) -> eyre::Result<Verifiers> {
-    let mtb_bin_path = context.cache_dir.join(MTB_BIN);
+    record_partial: R,
+) -> eyre::Result<Verifiers>
+where
+    R: Fn(Verifiers) -> RF,
+    RF: Future<Output = eyre::Result<()>>,
+{
+    download_semaphore_mtb_binary(&context.cache_dir).await?;
 
-    download_semaphore_mtb_binary(context.as_ref(), config.as_ref()).await?;
-
-    let verifier_contracts_dir = context.cache_path(VERIFIER_CONTRACTS_DIR);
-    let keys_dir = context.cache_path(KEYS_DIR);
-
-    tokio::fs::create_dir_all(&verifier_contracts_dir).await?;
-    tokio::fs::create_dir_all(&keys_dir).await?;
+    // Keeps the verifiers of the previous report which weren't handled yet
+    let mut partial =
+        context.report.verifiers(mode).cloned().unwrap_or_default();
 
     let mut verifiers = HashMap::new();
     for (tree_depth, batch_size) in
-        config.unique_tree_depths_and_batch_sizes(mode)
+        config.unique_tree_depths_and_batch_sizes(mode)?
     {
-        let mtb_bin_path = mtb_bin_path.clone();
-        let keys_dir = keys_dir.clone();
-        let verifier_contracts_dir = verifier_contracts_dir.clone();
-
-        let keys_file = generate_keys(
-            &mtb_bin_path,
-            &keys_dir,
-            tree_depth,
-            batch_size,
+        let verifier = deploy_verifier(
+            context.as_ref(),
+            config.as_ref(),
             mode,
-        )
-        .await?;
-
-        let context = context.clone();
-
-        let verifier_contract_path = generate_verifier_contract(
-            mtb_bin_path,
-            keys_file,
-            verifier_contracts_dir,
             tree_depth,
             batch_size,
-            mode,
         )
         .await?;
 
-        let deployment = deploy_verifier_contract(
-            context.as_ref(),
-            verifier_contract_path,
-            tree_depth,
-            batch_size,
-            mode,
-        )
-        .await?;
+        partial
+            .verifiers
+            .insert((tree_depth, batch_size), verifier.clone());
+        record_partial(partial.clone()).await?;
 
-        let key = (tree_depth, batch_size);
-        verifiers.insert(key, VerifierDeployment { deployment });
+        verifiers.insert((tree_depth, batch_size), verifier);
     }
 
     Ok(Verifiers { verifiers })
 }
+
+/// The keys & contract of a single verifier, as generated by MTB
+pub struct GeneratedVerifier {
+    pub keys_file: PathBuf,
+    pub verifier_contract: PathBuf,
+}
+
+/// Generates the keys & contract of a single verifier into `cache_dir`, or
+/// reuses them if they're already cached
+///
+/// Expects the MTB binary to be downloaded already.
+pub async fn generate_verifier(
+    config: &Config,
+    cache_dir: &Path,
+    mtb_setup_timeout: Option<Duration>,
+    mode: ProverMode,
+    tree_depth: TreeDepth,
+    batch_size: BatchSize,
+) -> eyre::Result<GeneratedVerifier> {
+    let mtb_bin_path = cache_dir.join(MTB_BIN);
+
+    let verifier_contracts_dir = cache_dir.join(VERIFIER_CONTRACTS_DIR);
+    let keys_dir = cache_dir.join(KEYS_DIR);
+
+    tokio::fs::create_dir_all(&verifier_contracts_dir).await?;
+    tokio::fs::create_dir_all(&keys_dir).await?;
+
+    let keys_file = generate_keys(
+        &mtb_bin_path,
+        &keys_dir,
+        tree_depth,
+        batch_size,
+        mode,
+        &config.misc.mtb_setup_args,
+        mtb_setup_timeout,
+    )
+    .await?;
+
+    let verifier_contract = generate_verifier_contract(
+        mtb_bin_path,
+        &keys_file,
+        verifier_contracts_dir,
+        tree_depth,
+        batch_size,
+        mode,
+        &config.misc.mtb_setup_args,
+    )
+    .await?;
+
+    Ok(GeneratedVerifier {
+        keys_file,
+        verifier_contract,
+    })
+}
+
+/// The verifier of `mode` for the tree depth and batch size in the report
+fn previous_verifier(
+    report: &Report,
+    mode: ProverMode,
+    tree_depth: TreeDepth,
+    batch_size: BatchSize,
+) -> Option<&VerifierDeployment> {
+    report
+        .verifiers(mode)?
+        .verifiers
+        .get(&(tree_depth, batch_size))
+}
+
+/// Generates the keys & contract of a single verifier and deploys it, unless
+/// it's already in the report
+///
+/// Expects the MTB binary to be downloaded already.
+pub async fn deploy_verifier(
+    context: &DeploymentContext,
+    config: &Config,
+    mode: ProverMode,
+    tree_depth: TreeDepth,
+    batch_size: BatchSize,
+) -> eyre::Result<VerifierDeployment> {
+    let GeneratedVerifier {
+        keys_file,
+        verifier_contract: verifier_contract_path,
+    } = generate_verifier(
+        config,
+        &context.cache_dir,
+        context.cmd.mtb_setup_timeout(),
+        mode,
+        tree_depth,
+        batch_size,
+    )
+    .await?;
+
+    let source_hash = file_hash(&verifier_contract_path).await?;
+    let keys_hash = cached_file_hash(&keys_file).await?;
+
+    if let Some(previous) =
+        previous_verifier(&context.report, mode, tree_depth, batch_size)
+    {
+        if previous.source_hash.is_some_and(|hash| hash != source_hash)
+            || previous.keys_hash.is_some_and(|hash| hash != keys_hash)
+        {
+            warn!("The {mode} verifier source for tree depth {tree_depth} and batch size {batch_size} changed since it was deployed - a redeploy may be needed");
+        }
+
+        info!("Found previous {mode} verifier deployment for tree depth {tree_depth} and batch size {batch_size} at {:?}", previous.deployment.address);
+
+        // Keeps the hashes it was deployed with, so a changed source is
+        // still detected by the next run
+        return Ok(VerifierDeployment {
+            tree_depth: Some(tree_depth),
+            ..previous.clone()
+        });
+    }
+
+    let deployment = deploy_verifier_contract(
+        context,
+        verifier_contract_path,
+        tree_depth,
+        batch_size,
+        mode,
+    )
+    .await?;
+
+    Ok(VerifierDeployment {
+        deployment,
+        tree_depth: Some(tree_depth),
+        source_hash: Some(source_hash),
+        keys_hash: Some(keys_hash),
+    })
+}
+
+/// Copies `verifier_contract` into a directory of its own under
+/// `sources_dir`, returning the path of the copy
+async fn isolate_verifier_contract(
+    verifier_contract: &Path,
+    sources_dir: impl AsRef<Path>,
+) -> eyre::Result<PathBuf> {
+    let file_name = verifier_contract
+        .file_name()
+        .context("Missing verifier contract file name")?;
+    let file_stem = verifier_contract
+        .file_stem()
+        .context("Missing verifier contract file name")?;
+
+    let isolated_dir = sources_dir.as_ref().join(file_stem);
+    tokio::fs::create_dir_all(&isolated_dir).await?;
+
+    let isolated_contract = isolated_dir.join(file_name);
+    tokio::fs::copy(verifier_contract, &isolated_contract)
+        .await
+        .with_context(|| {
+            format!("Copying {} for compilation", verifier_contract.display())
+        })?;
+
+    Ok(isolated_contract.canonicalize()?)
+}
+
+/// Keccak hash of a file, read in chunks since MTB keys can be several GB
+async fn file_hash(path: impl AsRef<Path>) -> eyre::Result<H256> {
+    let path = path.as_ref();
+
+    let file = tokio::fs::File::open(path)
+        .await
+        .with_context(|| format!("Opening {}", path.display()))?;
+    let mut reader = BufReader::new(file);
+    let mut hasher = Keccak::v256();
+
+    loop {
+        let chunk = reader
+            .fill_buf()
+            .await
+            .with_context(|| format!("Reading {}", path.display()))?;
+
+        if chunk.is_empty() {
+            break;
+        }
+
+        hasher.update(chunk);
+
+        let len = chunk.len();
+        reader.consume(len);
+    }
+
+    let mut hash = H256::zero();
+    hasher.finalize(&mut hash.0);
+
+    Ok(hash)
+}
+
+/// A file hash along with the size and modification time of the file it was
+/// computed for
+#[derive(Serialize, Deserialize)]
+struct CachedFileHash {
+    size: u64,
+    modified: Duration,
+    hash: H256,
+}
+
+/// Like [`file_hash`], but cached in `<path>.hash` as long as the size and
+/// modification time of the file don't change
+///
+/// Hashing MTB keys of several GB takes a while on every run otherwise.
+async fn cached_file_hash(path: impl AsRef<Path>) -> eyre::Result<H256> {
+    let path = path.as_ref();
+
+    let metadata = tokio::fs::metadata(path)
+        .await
+        .with_context(|| format!("Reading metadata of {}", path.display()))?;
+    let size = metadata.len();
+    let modified = metadata.modified()?.duration_since(UNIX_EPOCH)?;
+
+    let cache_path = PathBuf::from(format!("{}.hash", path.display()));
+
+    if let Ok(cached) =
+        serde_utils::read_deserialize::<CachedFileHash>(&cache_path).await
+    {
+        if cached.size == size && cached.modified == modified {
+            return Ok(cached.hash);
+        }
+    }
+
+    let hash = file_hash(path).await?;
+
+    let cached = CachedFileHash {
+        size,
+        modified,
+        hash,
+    };
+    serde_utils::write_serialize(&cache_path, cached).await?;
+
+    Ok(hash)
+}
+
+/// Prints which keys & verifier contracts a deployment would generate
+/// and whether they're already present in the cache - doesn't invoke MTB
+pub fn list(config: &Config, cache_dir: &Path) -> eyre::Result<()> {
+    let verifier_contracts_dir = cache_dir.join(VERIFIER_CONTRACTS_DIR);
+    let keys_dir = cache_dir.join(KEYS_DIR);
+
+    let setup_args = &config.misc.mtb_setup_args;
+
+    let cached = |path: &Path| if path.exists() { "cached" } else { "missing" };
+
+    for mode in [ProverMode::Insertion, ProverMode::Deletion] {
+        let mut entries: Vec<_> = config
+            .unique_tree_depths_and_batch_sizes(mode)?
+            .into_iter()
+            .collect();
+        entries.sort();
+
+        println!("{mode} verifiers ({}):", entries.len());
+
+        for (tree_depth, batch_size) in entries {
+            let keys_file = keys_dir
+                .join(keys_filename(tree_depth, batch_size, mode, setup_args));
+            let verifier_contract =
+                verifier_contracts_dir.join(verifier_contract_filename(
+                    tree_depth, batch_size, mode, setup_args,
+                ));
+
+            println!(
+                "  tree depth {tree_depth}, batch size {batch_size}: keys {}, verifier {}",
+                cached(&keys_file),
+                cached(&verifier_contract),
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints the estimated duration and disk usage of the MTB setups a
+/// deployment still has to run - doesn't invoke MTB
+///
+/// Estimates are based on the setups previously recorded in the cache and
+/// fall back to a rough model without any.
+pub async fn estimate(config: &Config, cache_dir: &Path) -> eyre::Result<()> {
+    let keys_dir = cache_dir.join(KEYS_DIR);
+    let timings = read_setup_timings(&keys_dir).await?;
+
+    let setup_args = &config.misc.mtb_setup_args;
+
+    let mut total_duration = Duration::ZERO;
+    let mut total_bytes = 0;
+
+    for mode in [ProverMode::Insertion, ProverMode::Deletion] {
+        let mut entries: Vec<_> = config
+            .unique_tree_depths_and_batch_sizes(mode)?
+            .into_iter()
+            .filter(|(tree_depth, batch_size)| {
+                !keys_dir
+                    .join(keys_filename(
+                        *tree_depth,
+                        *batch_size,
+                        mode,
+                        setup_args,
+                    ))
+                    .exists()
+            })
+            .collect();
+        entries.sort();
+
+        println!("Uncached {mode} setups ({}):", entries.len());
+
+        for (tree_depth, batch_size) in entries {
+            let estimate =
+                estimate_setup(tree_depth, batch_size, mode, &timings);
+            let bytes = estimate.keys_bytes + ESTIMATED_VERIFIER_CONTRACT_BYTES;
+
+            println!(
+                "  tree depth {tree_depth}, batch size {batch_size}: ~{}s, ~{} MiB ({})",
+                estimate.duration.as_secs(),
+                bytes / (1024 * 1024),
+                if estimate.from_timings {
+                    "from recorded setups"
+                } else {
+                    "rough model"
+                },
+            );
+
+            total_duration += estimate.duration;
+            total_bytes += bytes;
+        }
+    }
+
+    println!(
+        "Total: ~{}s, ~{} MiB",
+        total_duration.as_secs(),
+        total_bytes / (1024 * 1024)
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::utils::keccak256;
+
+    use super::*;
+
+    #[test]
+    fn previous_verifier_of_the_same_mode() {
+        let config: Config =
+            serde_yaml::from_str("groups: {}\nmisc: {}").unwrap();
+        let mut report = Report::default_with_config(&config);
+
+        let key = (TreeDepth(30), BatchSize(10));
+        report.insertion_verifiers = Some(Verifiers {
+            verifiers: HashMap::from([(
+                key,
+                VerifierDeployment {
+                    deployment: ContractDeployment {
+                        address: ethers::types::Address::repeat_byte(1),
+                        simulated: false,
+                        source_commit: None,
+                        code_hash: None,
+                        block_number: None,
+                        salt: None,
+                    },
+                    tree_depth: Some(key.0),
+                    source_hash: None,
+                    keys_hash: None,
+                },
+            )]),
+        });
+
+        let previous =
+            |mode| previous_verifier(&report, mode, key.0, key.1).is_some();
+
+        assert!(previous(ProverMode::Insertion));
+        assert!(!previous(ProverMode::Deletion));
+    }
+
+    #[tokio::test]
+    async fn file_hash_matches_keccak() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("keys");
+
+        // Larger than the reader's buffer, so it's hashed in several chunks
+        let contents: Vec<u8> = (0..20_000).map(|i| (i % 251) as u8).collect();
+        std::fs::write(&path, &contents).unwrap();
+
+        assert_eq!(file_hash(&path).await.unwrap(), H256(keccak256(contents)));
+    }
+
+    #[tokio::test]
+    async fn file_hash_is_cached_until_the_file_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("keys");
+        let cache_path = dir.path().join("keys.hash");
+
+        std::fs::write(&path, b"keys").unwrap();
+
+        let hash = cached_file_hash(&path).await.unwrap();
+        assert_eq!(hash, H256(keccak256(b"keys")));
+
+        // A cached hash is trusted as long as the metadata matches
+        let mut cached: CachedFileHash =
+            serde_utils::read_deserialize(&cache_path).await.unwrap();
+        cached.hash = H256::repeat_byte(1);
+        serde_utils::write_serialize(&cache_path, cached)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            cached_file_hash(&path).await.unwrap(),
+            H256::repeat_byte(1)
+        );
+
+        std::fs::write(&path, b"other keys").unwrap();
+
+        assert_eq!(
+            cached_file_hash(&path).await.unwrap(),
+            H256(keccak256(b"other keys"))
+        );
+    }
+
+    #[tokio::test]
+    async fn verifiers_from_the_same_cache_dir_are_isolated() {
+        let cache_dir = tempfile::tempdir().unwrap();
+        let contracts_dir = cache_dir.path().join(VERIFIER_CONTRACTS_DIR);
+        let sources_dir = cache_dir.path().join(VERIFIER_SOURCES_DIR);
+        std::fs::create_dir_all(&contracts_dir).unwrap();
+
+        let mut isolated = vec![];
+        for batch_size in [10, 100] {
+            let filename = verifier_contract_filename(
+                TreeDepth(30),
+                BatchSize(batch_size),
+                ProverMode::Insertion,
+                &[],
+            );
+            let contract = contracts_dir.join(filename);
+            std::fs::write(&contract, "contract Verifier {}").unwrap();
+
+            isolated.push(
+                isolate_verifier_contract(&contract, &sources_dir)
+                    .await
+                    .unwrap(),
+            );
+        }
+
+        assert_ne!(isolated[0].parent(), isolated[1].parent());
+
+        for contract in isolated {
+            let sources: Vec<_> = std::fs::read_dir(contract.parent().unwrap())
+                .unwrap()
+                .collect();
+
+            assert_eq!(
+                sources.len(),
+                1,
+                "{} isn't isolated",
+                contract.display()
+            );
+        }
+    }
+}
@@ -2,16 +2,19 @@ use std::collections::HashMap;
 use std::sync::Arc;
 
 use ethers::prelude::encode_function_data;
-use ethers::types::Address;
+use ethers::types::{Address, U256};
 use eyre::{Context as _, ContextCompat};
 use serde::{Deserialize, Serialize};
-use tracing::instrument;
+use tracing::{info, instrument};
 
 use super::identity_manager::WorldIDIdentityManagersDeployment;
 use crate::config::Config;
+use crate::deployment::router_routes::read_routes;
 use crate::deployment::DeploymentContext;
-use crate::ethers_utils::TransactionBuilder;
-use crate::forge_utils::{ContractSpec, ForgeInspectAbi};
+use crate::ethers_utils::{
+    ensure_has_code, initialized_version, proxy_implementation,
+    TransactionBuilder,
+};
 use crate::report::contract_deployment::ContractDeployment;
 use crate::types::GroupId;
 
@@ -19,35 +22,142 @@ use crate::types::GroupId;
 pub struct WorldIdRouterDeployment {
     pub impl_v1_deployment: ContractDeployment,
     pub proxy_deployment: ContractDeployment,
-    pub entries: HashMap<GroupId, Address>,
+    pub entries: HashMap<GroupId, RouterEntry>,
+    /// Groups whose route was disabled, with the target it had before
+    ///
+    /// Tells groups which were deployed and then disabled apart from ones
+    /// which never were.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub disabled: HashMap<GroupId, Address>,
+    /// Set once all the group routes were disabled by `decommission`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub decommissioned: bool,
+    /// The group the router was initialized with, unless it's group 0
+    ///
+    /// Router group 0 keeps routing to the identity manager of this group as
+    /// long as group 0 isn't in the config.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bootstrap_group: Option<GroupId>,
+    /// Proposed as owner of the router and the identity managers by
+    /// `decommission --transfer-ownership-to`
+    ///
+    /// The contracts use two step ownership transfers, so this address only
+    /// becomes the owner once it calls `acceptOwnership()` on each of them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pending_owner: Option<Address>,
+    /// The version the proxy was initialized to, as read from the chain
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub initialized_version: Option<u8>,
+}
+
+/// The route of a group and what's known about its target
+///
+/// Serialized as the plain target address unless there's more to it, which
+/// is also how reports of older versions store it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "RouterEntryRepr", into = "RouterEntryRepr")]
+pub struct RouterEntry {
+    pub target: Address,
+    /// Implementation version of the identity manager the route points to
+    pub impl_version: Option<u8>,
+    /// Free-form description, kept as is by the deployer
+    pub label: Option<String>,
+}
+
+impl From<Address> for RouterEntry {
+    fn from(target: Address) -> Self {
+        Self {
+            target,
+            impl_version: None,
+            label: None,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+enum RouterEntryRepr {
+    Target(Address),
+    Detailed {
+        target: Address,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        impl_version: Option<u8>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        label: Option<String>,
+    },
+}
+
+impl From<RouterEntryRepr> for RouterEntry {
+    fn from(repr: RouterEntryRepr) -> Self {
+        match repr {
+            RouterEntryRepr::Target(target) => target.into(),
+            RouterEntryRepr::Detailed {
+                target,
+                impl_version,
+                label,
+            } => Self {
+                target,
+                impl_version,
+                label,
+            },
+        }
+    }
+}
+
+impl From<RouterEntry> for RouterEntryRepr {
+    fn from(entry: RouterEntry) -> Self {
+        match entry {
+            RouterEntry {
+                target,
+                impl_version: None,
+                label: None,
+            } => Self::Target(target),
+            RouterEntry {
+                target,
+                impl_version,
+                label,
+            } => Self::Detailed {
+                target,
+                impl_version,
+                label,
+            },
+        }
+    }
 }
 
 #[instrument(skip_all)]
 async fn deploy_world_id_router_v1(
     context: &DeploymentContext,
-    first_group_address: Address,
+    config: &Config,
+    bootstrap_address: Address,
 ) -> eyre::Result<WorldIdRouterDeployment> {
     if let Some(previous_deployment) = context.report.world_id_router.as_ref() {
         return Ok(previous_deployment.clone());
     }
 
-    let contract_spec = ContractSpec::name("WorldIDRouter");
-    let impl_spec = ContractSpec::name("WorldIDRouterImplV1");
+    if let Some(router) = context.cmd.adopt_router {
+        return adopt_world_id_router(context, config, router).await;
+    }
+
+    let contract_spec = config.misc.contract_names.router();
+    let impl_spec = config.misc.contract_names.router_impl();
 
     let impl_v1_deployment = context
-        .forge_create(impl_spec.clone())
+        .forge_create_deterministic(impl_spec.clone(), "router_impl_v1", None)
         .with_cwd("./world-id-contracts")
+        .with_expected_code_hash(config.misc.code_hashes.router_impl)
         .run()
         .await?;
 
-    let impl_abi = ForgeInspectAbi::new(impl_spec.clone())
-        .with_cwd("./world-id-contracts")
-        .run()
-        .await?;
+    context.record_create_cost(&impl_v1_deployment).await?;
+
+    context.await_impl_deployment(&impl_v1_deployment).await?;
+
+    let impl_abi = context.inspect_abi(impl_spec.clone()).await?;
 
     let initialize_func = impl_abi.function("initialize")?;
 
-    let call_data = encode_function_data(initialize_func, first_group_address)?;
+    let call_data = encode_function_data(initialize_func, bootstrap_address)?;
 
     let proxy_deployment = context
         .forge_create(contract_spec)
@@ -57,28 +167,99 @@ async fn deploy_world_id_router_v1(
         .run()
         .await?;
 
+    context.record_create_cost(&proxy_deployment).await?;
+
     Ok(WorldIdRouterDeployment {
         impl_v1_deployment: impl_v1_deployment.into(),
         proxy_deployment: proxy_deployment.into(),
+        // `initialize` routes group 0 to the bootstrap group's identity
+        // manager, whatever the id of that group in the config is
         entries: maplit::hashmap! {
-            GroupId(0) => first_group_address
+            GroupId(0) => bootstrap_address.into()
         },
+        disabled: HashMap::new(),
+        decommissioned: false,
+        bootstrap_group: Some(context.cmd.bootstrap_group)
+            .filter(|group_id| *group_id != GroupId(0)),
+        pending_owner: None,
+        initialized_version: None,
     })
 }
 
-#[instrument(skip(context))]
+/// Takes over a router which exists on-chain but is missing from the report,
+/// e.g. after the report was lost
+#[instrument(skip(context, config))]
+async fn adopt_world_id_router(
+    context: &DeploymentContext,
+    config: &Config,
+    router: Address,
+) -> eyre::Result<WorldIdRouterDeployment> {
+    ensure_has_code(&context.rpc_signer, router)
+        .await
+        .context("Validating the adopted router")?;
+
+    let routes = read_routes(context, config, router).await?;
+
+    // The proxy is created with `initialize`, which sets the route of group 0
+    if !matches!(routes.get(&GroupId(0)), Some(Some(_))) {
+        eyre::bail!(
+            "Router {router:?} isn't initialized - it has no route for group 0"
+        );
+    }
+
+    info!("Adopting the existing router {router:?}");
+
+    let implementation =
+        proxy_implementation(&context.rpc_signer, router).await?;
+
+    Ok(WorldIdRouterDeployment {
+        impl_v1_deployment: ContractDeployment {
+            address: implementation,
+            simulated: false,
+            source_commit: None,
+            code_hash: None,
+            block_number: None,
+            salt: None,
+        },
+        proxy_deployment: ContractDeployment {
+            address: router,
+            simulated: false,
+            source_commit: None,
+            code_hash: None,
+            block_number: None,
+            salt: None,
+        },
+        // Disabled routes of configured groups are kept as the zero address,
+        // so they're updated rather than added again
+        entries: routes
+            .into_iter()
+            .filter_map(|(group_id, route)| match route {
+                Some(address) => Some((group_id, address.into())),
+                None if config.groups.contains_key(&group_id) => {
+                    Some((group_id, Address::zero().into()))
+                }
+                None => None,
+            })
+            .collect(),
+        disabled: HashMap::new(),
+        decommissioned: false,
+        bootstrap_group: None,
+        pending_owner: None,
+        initialized_version: None,
+    })
+}
+
+#[instrument(skip(context, config))]
 async fn update_group_route(
     context: &DeploymentContext,
+    config: &Config,
     world_id_router_address: Address,
     group_id: GroupId,
     new_target_address: Address,
 ) -> eyre::Result<()> {
-    let impl_spec = ContractSpec::name("WorldIDRouterImplV1");
+    let impl_spec = config.misc.contract_names.router_impl();
 
-    let impl_abi = ForgeInspectAbi::new(impl_spec.clone())
-        .with_cwd("./world-id-contracts")
-        .run()
-        .await?;
+    let impl_abi = context.inspect_abi(impl_spec.clone()).await?;
 
     let signer = &context.rpc_signer;
 
@@ -96,19 +277,17 @@ async fn update_group_route(
     Ok(())
 }
 
-#[instrument(skip(context))]
+#[instrument(skip(context, config))]
 async fn add_group_route(
     context: &DeploymentContext,
+    config: &Config,
     world_id_router_address: Address,
     group_id: GroupId,
     new_target_address: Address,
 ) -> eyre::Result<()> {
-    let impl_spec = ContractSpec::name("WorldIDRouterImplV1");
+    let impl_spec = config.misc.contract_names.router_impl();
 
-    let impl_abi = ForgeInspectAbi::new(impl_spec.clone())
-        .with_cwd("./world-id-contracts")
-        .run()
-        .await?;
+    let impl_abi = context.inspect_abi(impl_spec.clone()).await?;
 
     let signer = &context.rpc_signer;
 
@@ -123,21 +302,43 @@ async fn add_group_route(
 
     tx.send().await?;
 
+    if context.dumps_transactions() {
+        return Ok(());
+    }
+
+    // `addGroup` assigns the next free group id, make sure it's the one we
+    // expected
+    let group_count: U256 = TransactionBuilder::default()
+        .signer(signer.clone())
+        .abi(impl_abi)
+        .function_name("groupCount")
+        .args(())
+        .to(world_id_router_address)
+        .context(context)
+        .build()?
+        .call()
+        .await?;
+
+    if group_count != U256::from(group_id.0 + 1) {
+        eyre::bail!(
+            "Group {group_id} was added as group {} - the router's group ids are out of sync with the report",
+            group_count.saturating_sub(U256::one())
+        );
+    }
+
     Ok(())
 }
 
-#[instrument(skip(context))]
-async fn remove_group_route(
+#[instrument(skip(context, config))]
+pub async fn remove_group_route(
     context: &DeploymentContext,
+    config: &Config,
     world_id_router_address: Address,
     group_id: GroupId,
 ) -> eyre::Result<()> {
-    let impl_spec = ContractSpec::name("WorldIDRouterImplV1");
+    let impl_spec = config.misc.contract_names.router_impl();
 
-    let impl_abi = ForgeInspectAbi::new(impl_spec.clone())
-        .with_cwd("./world-id-contracts")
-        .run()
-        .await?;
+    let impl_abi = context.inspect_abi(impl_spec.clone()).await?;
 
     let signer = &context.rpc_signer;
 
@@ -161,14 +362,18 @@ pub async fn deploy(
     config: Arc<Config>,
     identity_managers: &WorldIDIdentityManagersDeployment,
 ) -> eyre::Result<WorldIdRouterDeployment> {
-    let first_group = identity_managers
+    let bootstrap_group = context.cmd.bootstrap_group;
+    let bootstrap_identity_manager = identity_managers
         .groups
-        .get(&GroupId(0))
-        .context("Missing group 0")?;
+        .get(&bootstrap_group)
+        .with_context(|| {
+            format!("Missing bootstrap group {bootstrap_group}")
+        })?;
 
     let mut world_id_router_deployment = deploy_world_id_router_v1(
         context.as_ref(),
-        first_group.proxy_deployment.address,
+        config.as_ref(),
+        bootstrap_identity_manager.proxy_deployment.address,
     )
     .await
     .context("deploying world id router implementation")?;
@@ -177,58 +382,199 @@ pub async fn deploy(
     group_ids.sort();
 
     for group_id in group_ids {
-        let group_identity_manager_address = identity_managers
+        context.set_cost_group(Some(group_id));
+
+        if group_id == GroupId(0) {
+            if let Some(bootstrap_group) =
+                world_id_router_deployment.bootstrap_group.take()
+            {
+                info!("Group 0 is in the config, its route replaces the one to bootstrap group {bootstrap_group}");
+            }
+        }
+
+        let group_identity_manager = identity_managers
             .groups
             .get(&group_id)
-            .context("Missing group")?
-            .proxy_deployment
-            .address;
-
-        if let Some(current_group_address) =
+            .context("Missing group")?;
+        let group_identity_manager_address =
+            group_identity_manager.proxy_deployment.address;
+        let impl_version =
+            if group_identity_manager.impl_v2_deployment.is_some() {
+                2
+            } else {
+                1
+            };
+
+        if let Some(current_entry) =
             world_id_router_deployment.entries.get_mut(&group_id)
         {
-            if *current_group_address != group_identity_manager_address {
+            if current_entry.target != group_identity_manager_address {
                 update_group_route(
                     context.as_ref(),
+                    config.as_ref(),
                     world_id_router_deployment.proxy_deployment.address,
                     group_id,
                     group_identity_manager_address,
                 )
                 .await?;
 
-                *current_group_address = group_identity_manager_address;
+                current_entry.target = group_identity_manager_address;
+            }
+
+            current_entry.impl_version = Some(impl_version);
+        } else if let Some(previous_target) =
+            world_id_router_deployment.disabled.remove(&group_id)
+        {
+            // The group keeps its id on the router, so it's re-enabled by
+            // pointing its route at the identity manager again
+            if previous_target == group_identity_manager_address {
+                info!("Re-enabling group {group_id} with its previous identity manager");
+            } else {
+                info!(
+                    "Re-enabling group {group_id}, previously routed to {previous_target:?}"
+                );
             }
+
+            update_group_route(
+                context.as_ref(),
+                config.as_ref(),
+                world_id_router_deployment.proxy_deployment.address,
+                group_id,
+                group_identity_manager_address,
+            )
+            .await?;
+
+            world_id_router_deployment.entries.insert(
+                group_id,
+                RouterEntry {
+                    target: group_identity_manager_address,
+                    impl_version: Some(impl_version),
+                    label: None,
+                },
+            );
         } else {
             add_group_route(
                 context.as_ref(),
+                config.as_ref(),
                 world_id_router_deployment.proxy_deployment.address,
                 group_id,
                 group_identity_manager_address,
             )
             .await?;
 
-            world_id_router_deployment
+            world_id_router_deployment.entries.insert(
+                group_id,
+                RouterEntry {
+                    target: group_identity_manager_address,
+                    impl_version: Some(impl_version),
+                    label: None,
+                },
+            );
+        }
+
+        // Router group 0 follows the identity manager of the bootstrap group
+        if world_id_router_deployment.bootstrap_group == Some(group_id) {
+            let bootstrap_entry = world_id_router_deployment
                 .entries
-                .insert(group_id, group_identity_manager_address);
+                .get_mut(&GroupId(0))
+                .context(
+                    "Missing the route of group 0 to the bootstrap group",
+                )?;
+
+            if bootstrap_entry.target != group_identity_manager_address {
+                update_group_route(
+                    context.as_ref(),
+                    config.as_ref(),
+                    world_id_router_deployment.proxy_deployment.address,
+                    GroupId(0),
+                    group_identity_manager_address,
+                )
+                .await?;
+
+                bootstrap_entry.target = group_identity_manager_address;
+            }
+
+            bootstrap_entry.impl_version = Some(impl_version);
         }
 
         let deployment_group_ids: Vec<_> =
             world_id_router_deployment.entries.keys().copied().collect();
         for deployment_group_id in deployment_group_ids {
-            if !config.groups.contains_key(&deployment_group_id) {
+            let routes_bootstrap_group = deployment_group_id == GroupId(0)
+                && world_id_router_deployment.bootstrap_group.is_some();
+
+            if !config.groups.contains_key(&deployment_group_id)
+                && !routes_bootstrap_group
+            {
                 remove_group_route(
                     context.as_ref(),
+                    config.as_ref(),
                     world_id_router_deployment.proxy_deployment.address,
                     deployment_group_id,
                 )
                 .await?;
 
-                world_id_router_deployment
+                if let Some(entry) = world_id_router_deployment
                     .entries
-                    .remove(&deployment_group_id);
+                    .remove(&deployment_group_id)
+                {
+                    world_id_router_deployment
+                        .disabled
+                        .insert(deployment_group_id, entry.target);
+                }
             }
         }
     }
 
+    context.set_cost_group(None);
+
+    let proxy_deployment = &world_id_router_deployment.proxy_deployment;
+    if !proxy_deployment.simulated {
+        world_id_router_deployment.initialized_version = Some(
+            initialized_version(&context.rpc_signer, proxy_deployment.address)
+                .await?,
+        );
+    }
+
     Ok(world_id_router_deployment)
 }
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use super::*;
+
+    #[test]
+    fn router_entries_from_plain_addresses() {
+        let entries: HashMap<GroupId, RouterEntry> =
+            serde_yaml::from_str(indoc! { r#"
+                0: "0x0000000000000000000000000000000000000001"
+                1:
+                  target: "0x0000000000000000000000000000000000000002"
+                  impl_version: 2
+                  label: orb
+            "# })
+            .unwrap();
+
+        assert_eq!(
+            entries[&GroupId(0)],
+            RouterEntry::from(Address::from_low_u64_be(1))
+        );
+        assert_eq!(
+            entries[&GroupId(1)],
+            RouterEntry {
+                target: Address::from_low_u64_be(2),
+                impl_version: Some(2),
+                label: Some("orb".to_string()),
+            }
+        );
+
+        // Entries without metadata stay readable by older versions
+        let plain = serde_yaml::to_string(&entries[&GroupId(0)]).unwrap();
+        assert_eq!(
+            plain.trim(),
+            "'0x0000000000000000000000000000000000000001'"
+        );
+    }
+}
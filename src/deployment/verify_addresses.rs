@@ -0,0 +1,68 @@
+use std::collections::BTreeMap;
+
+use ethers::types::Address;
+use eyre::{Context, ContextCompat};
+use tracing::{error, info};
+
+use crate::cli::{Args, VerifyAddressesArgs};
+use crate::forge_utils::verify::ForgeVerify;
+use crate::forge_utils::ContractSpec;
+
+/// Verifies contracts listed in an addresses file, without a report
+///
+/// Each entry maps a contract spec (`Name` or `path/to/Contract.sol:Name`)
+/// to the address it's deployed at.
+pub async fn run_verify_addresses(
+    cmd: Args,
+    args: VerifyAddressesArgs,
+) -> eyre::Result<()> {
+    let etherscan_api_key = cmd
+        .resolve_etherscan_api_key()
+        .await?
+        .context("Verifying contracts requires an etherscan API key")?;
+
+    let remappings = cmd.resolve_remappings().await?;
+
+    let contents = tokio::fs::read_to_string(&args.file)
+        .await
+        .with_context(|| format!("Reading {}", args.file.display()))?;
+
+    let addresses: BTreeMap<String, Address> = serde_yaml::from_str(&contents)
+        .with_context(|| format!("Parsing {}", args.file.display()))?;
+
+    let mut failed = 0;
+
+    for (contract_spec, address) in addresses {
+        let contract_spec: ContractSpec = contract_spec.parse()?;
+
+        info!("Verifying {contract_spec} at {address:?}");
+
+        let mut forge_verify = ForgeVerify::new(contract_spec.clone(), address)
+            .with_root("./world-id-contracts")
+            .with_chain(args.chain_id)
+            .with_etherscan_api_key(&etherscan_api_key)
+            .with_retries(cmd.verify_retries)
+            .with_delay(cmd.verify_delay)
+            .with_timeout(cmd.verify_timeout());
+
+        for remapping in &remappings {
+            forge_verify = forge_verify.with_remapping(remapping);
+        }
+
+        for forge_env in &cmd.forge_env {
+            forge_verify =
+                forge_verify.with_env(&forge_env.key, &forge_env.value);
+        }
+
+        if let Err(err) = forge_verify.run().await {
+            error!("Failed to verify {contract_spec} at {address:?}: {err:?}");
+            failed += 1;
+        }
+    }
+
+    if failed > 0 {
+        eyre::bail!("Failed to verify {failed} contract(s)");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,325 @@
+use std::time::Duration;
+
+use ethers::abi::Token;
+use ethers::types::{Address, U256};
+use futures::StreamExt;
+use tracing::{error, info, warn};
+
+use super::mtb_utils::{verifier_contract_filename, ProverMode};
+use super::steps::timelock::TIMELOCK_SPEC;
+use super::{
+    init_context, ChainAccess, DeploymentContext, VERIFIER_SOURCES_DIR,
+};
+use crate::cli::{Args, VerifyAllArgs};
+use crate::config::Config;
+use crate::forge_utils::verify::ForgeVerify;
+use crate::forge_utils::{ContractSpec, ExternalDep};
+use crate::report::contract_deployment::ContractDeployment;
+
+/// Base delay between verification attempts, doubled on every retry
+const RETRY_BACKOFF: Duration = Duration::from_secs(5);
+
+enum Outcome {
+    Verified,
+    Failed,
+    Skipped(&'static str),
+}
+
+struct Target {
+    name: String,
+    address: Address,
+    verify: Result<ForgeVerify, &'static str>,
+}
+
+/// Verifies every contract in the report concurrently, e.g. after a
+/// deployment with verification disabled
+pub async fn run_verify_all(
+    cmd: Args,
+    args: VerifyAllArgs,
+) -> eyre::Result<()> {
+    let (context, config) = init_context(cmd, ChainAccess::Read).await?;
+
+    if context.etherscan_api_key.is_none() {
+        eyre::bail!("Verifying contracts requires an etherscan API key");
+    }
+
+    let targets = targets(context.as_ref(), config.as_ref());
+
+    let verifications = targets.into_iter().map(|target| async move {
+        let outcome = match target.verify {
+            Ok(forge_verify) => {
+                verify_with_retries(&target.name, &forge_verify, args.retries)
+                    .await
+            }
+            Err(reason) => Outcome::Skipped(reason),
+        };
+
+        (target.name, target.address, outcome)
+    });
+
+    let mut results: Vec<_> = futures::stream::iter(verifications)
+        .buffer_unordered(args.parallelism.max(1))
+        .collect()
+        .await;
+
+    results.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut num_failed = 0;
+
+    // Printed rather than logged so it's shown even with --quiet
+    println!("Verification summary:");
+
+    for (name, address, outcome) in results {
+        let status = match outcome {
+            Outcome::Verified => "verified".to_string(),
+            Outcome::Failed => {
+                num_failed += 1;
+                "FAILED".to_string()
+            }
+            Outcome::Skipped(reason) => format!("skipped ({reason})"),
+        };
+
+        println!("  {name} {address:?}: {status}");
+    }
+
+    if num_failed > 0 {
+        eyre::bail!("Failed to verify {num_failed} contract(s)");
+    }
+
+    Ok(())
+}
+
+async fn verify_with_retries(
+    name: &str,
+    forge_verify: &ForgeVerify,
+    retries: u32,
+) -> Outcome {
+    for attempt in 0..=retries {
+        if attempt > 0 {
+            let backoff = RETRY_BACKOFF * 2u32.pow(attempt - 1);
+            warn!("Retrying the verification of {name} in {backoff:?}");
+            tokio::time::sleep(backoff).await;
+        }
+
+        match forge_verify.run().await {
+            Ok(()) => {
+                info!("Verified {name}");
+                return Outcome::Verified;
+            }
+            Err(err) => error!("Failed to verify {name}: {err:?}"),
+        }
+    }
+
+    Outcome::Failed
+}
+
+/// Every contract of the report with how to verify it
+fn targets(context: &DeploymentContext, config: &Config) -> Vec<Target> {
+    let report = &context.report;
+    let contract_names = &config.misc.contract_names;
+
+    let mut targets = vec![];
+
+    let mut push =
+        |name: String,
+         deployment: &ContractDeployment,
+         spec: Result<ContractSpec, &'static str>,
+         configure: &dyn Fn(ForgeVerify) -> ForgeVerify| {
+            let verify = if deployment.simulated {
+                Err("simulated")
+            } else {
+                spec.map(|spec| {
+                    let forge_verify = context
+                        .forge_verify(spec, deployment.address)
+                        .with_root("./world-id-contracts")
+                        .with_chain(context.chain_id);
+
+                    configure(forge_verify)
+                })
+            };
+
+            targets.push(Target {
+                name,
+                address: deployment.address,
+                verify,
+            });
+        };
+
+    let no_args = |forge_verify| forge_verify;
+    let guess_args = |forge_verify: ForgeVerify| {
+        forge_verify.with_guessed_constructor_args(&context.rpc_url)
+    };
+
+    for (mode, verifiers) in [
+        (ProverMode::Insertion, &report.insertion_verifiers),
+        (ProverMode::Deletion, &report.deletion_verifiers),
+    ] {
+        for ((tree_depth, batch_size), verifier) in
+            verifiers.iter().flat_map(|v| &v.verifiers)
+        {
+            let filename = verifier_contract_filename(
+                *tree_depth,
+                *batch_size,
+                mode,
+                &config.misc.mtb_setup_args,
+            );
+
+            // Verifiers are compiled from their own directory, see
+            // `deploy_verifier_contract`
+            let source = filename.file_stem().map(|stem| {
+                context
+                    .cache_path(VERIFIER_SOURCES_DIR)
+                    .join(stem)
+                    .join(&filename)
+            });
+
+            let spec = match source {
+                Some(source) if source.exists() => {
+                    Ok(ContractSpec::path_name(source, "Verifier"))
+                }
+                _ => Err("verifier source not in the cache"),
+            };
+
+            push(
+                format!("{mode} verifier {tree_depth}/{batch_size}"),
+                &verifier.deployment,
+                spec,
+                &no_args,
+            );
+        }
+    }
+
+    for (group_id, tables) in
+        report.lookup_tables.iter().flat_map(|t| &t.groups)
+    {
+        for (name, table) in [
+            ("insert", &tables.insert),
+            ("update", &tables.update),
+            ("delete", &tables.delete),
+        ] {
+            if let Some(table) = table {
+                push(
+                    format!("group {group_id} {name} lookup table"),
+                    &table.deployment,
+                    Ok(contract_names.verifier_lookup_table()),
+                    &no_args,
+                );
+            }
+        }
+    }
+
+    if let Some(semaphore_verifier) = report.semaphore_verifier.as_ref() {
+        let pairing = &semaphore_verifier.pairing_deployment;
+
+        push(
+            "semaphore pairing".to_string(),
+            pairing,
+            Ok(contract_names.pairing()),
+            &no_args,
+        );
+
+        if let Some(verifier_deployment) =
+            semaphore_verifier.verifier_deployment.as_ref()
+        {
+            push(
+                "semaphore verifier".to_string(),
+                verifier_deployment,
+                Ok(contract_names.semaphore_verifier()),
+                &|forge_verify: ForgeVerify| {
+                    forge_verify.with_external_dep(ExternalDep {
+                        contract_spec: contract_names.pairing(),
+                        address: pairing.address,
+                    })
+                },
+            );
+        }
+    }
+
+    for (group_id, identity_manager) in
+        report.identity_managers.iter().flat_map(|i| &i.groups)
+    {
+        push(
+            format!("group {group_id} identity manager proxy"),
+            &identity_manager.proxy_deployment,
+            Ok(contract_names.identity_manager()),
+            &guess_args,
+        );
+
+        if let Some(impl_v1) = identity_manager.impl_v1_deployment.as_ref() {
+            push(
+                format!("group {group_id} identity manager impl v1"),
+                impl_v1,
+                Ok(contract_names.identity_manager_impl_v1()),
+                &no_args,
+            );
+        }
+
+        if let Some(impl_v2) = identity_manager.impl_v2_deployment.as_ref() {
+            push(
+                format!("group {group_id} identity manager impl v2"),
+                impl_v2,
+                Ok(contract_names.identity_manager_impl_v2()),
+                &no_args,
+            );
+        }
+    }
+
+    if let Some(world_id_router) = report.world_id_router.as_ref() {
+        push(
+            "world id router proxy".to_string(),
+            &world_id_router.proxy_deployment,
+            Ok(contract_names.router()),
+            &guess_args,
+        );
+        push(
+            "world id router impl v1".to_string(),
+            &world_id_router.impl_v1_deployment,
+            Ok(contract_names.router_impl()),
+            &no_args,
+        );
+    }
+
+    if let Some(timelock) = report.timelock.as_ref() {
+        let spec = config
+            .misc
+            .timelock
+            .as_ref()
+            .and_then(|timelock| timelock.contract.as_deref())
+            .unwrap_or(TIMELOCK_SPEC)
+            .parse()
+            .map_err(|_| "invalid timelock contract spec");
+
+        let constructor_args = ethers::abi::encode(&[
+            Token::Uint(U256::from(timelock.min_delay)),
+            Token::Array(
+                timelock
+                    .proposers
+                    .iter()
+                    .copied()
+                    .map(Token::Address)
+                    .collect(),
+            ),
+            Token::Array(
+                timelock
+                    .executors
+                    .iter()
+                    .copied()
+                    .map(Token::Address)
+                    .collect(),
+            ),
+            Token::Address(timelock.admin),
+        ]);
+
+        push(
+            "timelock".to_string(),
+            &timelock.deployment,
+            spec,
+            &|forge_verify: ForgeVerify| {
+                forge_verify
+                    .with_constructor_args(constructor_args.clone().into())
+            },
+        );
+    }
+
+    targets
+}
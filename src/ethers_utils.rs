@@ -1,11 +1,18 @@
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use ethers::abi::Tokenizable;
-use ethers::prelude::encode_function_data;
-use ethers::providers::Middleware;
+use ethers::abi::{AbiDecode, Detokenize, Tokenize};
+use ethers::prelude::{decode_function_data, encode_function_data};
+use ethers::providers::{Middleware, MiddlewareError};
+use ethers::signers::Signer;
 use ethers::types::transaction::eip2718::TypedTransaction;
-use ethers::types::{Address, Eip1559TransactionRequest};
+use ethers::types::{
+    Address, Bytes, Eip1559TransactionRequest, TransactionReceipt,
+    TransactionRequest, H256, U256,
+};
+use ethers::utils::keccak256;
 use eyre::{bail, Context, ContextCompat};
+use tracing::{field, info, instrument, warn, Span};
 
 use crate::common_keys::RpcSigner;
 use crate::deployment::DeploymentContext;
@@ -17,6 +24,7 @@ pub struct Transaction<'a, T> {
     args: T,
     signer: Arc<RpcSigner>,
     to: Address,
+    legacy: bool,
 }
 
 #[derive(Default, Clone, Debug)]
@@ -27,6 +35,7 @@ pub struct TransactionBuilder<'a, T> {
     args: Option<T>,
     signer: Option<Arc<RpcSigner>>,
     to: Option<Address>,
+    legacy: Option<bool>,
 }
 
 impl<'a, T> TransactionBuilder<'a, T> {
@@ -60,11 +69,19 @@ impl<'a, T> TransactionBuilder<'a, T> {
         self
     }
 
+    /// Send a legacy transaction instead of an EIP-1559 one, defaults to
+    /// `--legacy`
+    pub fn legacy(mut self, legacy: bool) -> Self {
+        self.legacy = Some(legacy);
+        self
+    }
+
     pub fn build(self) -> eyre::Result<Transaction<'a, T>> {
+        let context =
+            self.context.context("TransactionBuilder missing context")?;
+
         Ok(Transaction {
-            context: self
-                .context
-                .context("TransactionBuilder missing context")?,
+            context,
             abi: self.abi.context("TransactionBuilder missing abi")?,
             function_name: self
                 .function_name
@@ -72,43 +89,472 @@ impl<'a, T> TransactionBuilder<'a, T> {
             args: self.args.context("TransactionBuilder missing args")?,
             signer: self.signer.context("TransactionBuilder missing signer")?,
             to: self.to.context("TransactionBuilder missing to")?,
+            legacy: self.legacy.unwrap_or(context.cmd.legacy),
         })
     }
 }
 
 impl<'a, T> Transaction<'a, T>
 where
-    T: Tokenizable,
+    T: Tokenize,
 {
+    #[instrument(
+        name = "send_transaction",
+        skip_all,
+        fields(
+            function = %self.function_name,
+            to = ?self.to,
+            transaction_hash = tracing::field::Empty,
+        )
+    )]
     pub async fn send(self) -> eyre::Result<()> {
         let func = self.abi.function(&self.function_name)?;
         let call_data = encode_function_data(func, self.args)?;
 
-        let mut tx = TypedTransaction::Eip1559(
-            Eip1559TransactionRequest::new()
-                .to(self.to)
-                .data(call_data)
-                .nonce(self.context.next_nonce()),
-        );
+        let mut tx = request(self.to, call_data, self.legacy);
+        tx.set_nonce(self.context.next_nonce());
+
+        if self.context.dumps_transactions() {
+            // Filled from the `--dump-*` options rather than the node
+            fill_offline(self.context, &mut tx, self.signer.0.address())?;
+
+            let signature = self
+                .signer
+                .0
+                .signer()
+                .sign_transaction(&tx)
+                .await
+                .context("Signing transaction")?;
+
+            return self
+                .context
+                .dump_transaction(tx.rlp_signed(&signature))
+                .await;
+        }
+
+        if let Some(gas_oracle) = self.context.cmd.gas_oracle() {
+            match gas_oracle.fetch_fees().await {
+                Ok(fees) => match &mut tx {
+                    TypedTransaction::Eip1559(inner) => {
+                        inner.max_fee_per_gas = Some(fees.max_fee_per_gas);
+                        inner.max_priority_fee_per_gas =
+                            Some(fees.max_priority_fee_per_gas);
+                    }
+                    // Legacy transactions pay at most the max fee per gas
+                    _ => {
+                        tx.set_gas_price(fees.max_fee_per_gas);
+                    }
+                },
+                Err(err) => {
+                    warn!("Gas oracle failed, falling back to the node's fee estimation: {err:?}");
+                }
+            }
+        }
 
         self.signer.0.fill_transaction(&mut tx, None).await?;
 
-        let tx = self
+        let max_retries = self.context.cmd.max_retries_per_tx;
+        let mut sent = vec![];
+        let mut attempt = 0;
+
+        let receipt = loop {
+            self.context.throttle().await;
+
+            let err = match send_and_confirm(
+                &self.signer,
+                &tx,
+                &mut sent,
+                self.context.cmd.tx_timeout(),
+            )
+            .await
+            {
+                Ok(receipt) => break receipt,
+                Err(err) => err,
+            };
+
+            // Once any of the attempts is mined its nonce is used, so that
+            // attempt is final
+            if let Some(receipt) = mined_receipt(&self.signer, &sent).await? {
+                if receipt.status == Some(1.into()) {
+                    break receipt;
+                }
+
+                return Err(err);
+            }
+
+            if attempt >= max_retries {
+                return Err(err);
+            }
+
+            attempt += 1;
+
+            warn!(
+                "Transaction attempt {attempt}/{} failed, retrying with bumped fees: {err:?}",
+                max_retries + 1
+            );
+
+            bump_fees(&mut tx);
+
+            tokio::time::sleep(RETRY_BACKOFF * attempt).await;
+        };
+
+        self.context.record_cost(&receipt);
+
+        Ok(())
+    }
+
+    /// Executes the function as a read-only call against the latest block
+    pub async fn call<R: Detokenize>(self) -> eyre::Result<R> {
+        let func = self.abi.function(&self.function_name)?;
+        let call_data = encode_function_data(func, self.args)?;
+
+        let tx = request(self.to, call_data, self.legacy);
+
+        let output = self
             .signer
             .0
-            .send_transaction(tx, None)
+            .call(&tx, None)
             .await
-            .context("Send transaction")?;
+            .with_context(|| format!("Calling {}", self.function_name))?;
+
+        Ok(decode_function_data(func, output, false)?)
+    }
+
+    /// Like [`Self::call`], but a revert is `None` instead of an error
+    ///
+    /// Other failures, e.g. of the transport, are still errors - so they
+    /// aren't mistaken for the contract reverting.
+    pub async fn call_unless_reverted<R: Detokenize>(
+        self,
+    ) -> eyre::Result<Option<R>> {
+        let func = self.abi.function(&self.function_name)?;
+        let call_data = encode_function_data(func, self.args)?;
+
+        let tx = request(self.to, call_data, self.legacy);
+
+        match self.signer.0.call(&tx, None).await {
+            Ok(output) => Ok(Some(decode_function_data(func, output, false)?)),
+            Err(err) if is_revert(&err) => Ok(None),
+            Err(err) => Err(err)
+                .with_context(|| format!("Calling {}", self.function_name)),
+        }
+    }
+}
+
+/// Whether the node rejected a call because the contract reverted, as opposed
+/// to e.g. the request failing
+fn is_revert(err: &impl MiddlewareError) -> bool {
+    err.as_error_response()
+        .is_some_and(|response| response.is_revert())
+}
+
+/// Errors if there's no contract code deployed at `address`
+pub async fn ensure_has_code(
+    signer: &RpcSigner,
+    address: Address,
+) -> eyre::Result<()> {
+    code_hash(signer, address).await?;
+
+    Ok(())
+}
+
+/// Keccak hash of the runtime code at `address`, errors if there's none
+pub async fn code_hash(
+    signer: &RpcSigner,
+    address: Address,
+) -> eyre::Result<H256> {
+    let code = signer
+        .0
+        .get_code(address, None)
+        .await
+        .with_context(|| format!("Fetching code at {address:?}"))?;
+
+    if code.is_empty() {
+        bail!("No contract code found at {address:?}");
+    }
+
+    Ok(H256(keccak256(&code)))
+}
+
+/// `keccak256("eip1967.proxy.implementation") - 1`
+const EIP1967_IMPLEMENTATION_SLOT: [u8; 32] = [
+    0x36, 0x08, 0x94, 0xa1, 0x3b, 0xa1, 0xa3, 0x21, 0x06, 0x67, 0xc8, 0x28,
+    0x49, 0x2d, 0xb9, 0x8d, 0xca, 0x3e, 0x20, 0x76, 0xcc, 0x37, 0x35, 0xa9,
+    0x20, 0xa3, 0xca, 0x50, 0x5d, 0x38, 0x2b, 0xbc,
+];
+
+/// The version a proxy was initialized to, e.g. 2 after `initializeV2`
+///
+/// Read from OpenZeppelin's `Initializable`, which keeps it in the lowest
+/// byte of the first storage slot. Zero if the proxy isn't initialized.
+pub async fn initialized_version(
+    signer: &RpcSigner,
+    proxy: Address,
+) -> eyre::Result<u8> {
+    let slot = signer
+        .0
+        .get_storage_at(proxy, H256::zero(), None)
+        .await
+        .with_context(|| format!("Reading initialized version of {proxy:?}"))?;
+
+    Ok(slot.0[31])
+}
+
+/// The implementation an EIP-1967 proxy currently delegates to
+pub async fn proxy_implementation(
+    signer: &RpcSigner,
+    proxy: Address,
+) -> eyre::Result<Address> {
+    let slot = signer
+        .0
+        .get_storage_at(proxy, H256(EIP1967_IMPLEMENTATION_SLOT), None)
+        .await
+        .with_context(|| format!("Reading implementation of {proxy:?}"))?;
+
+    Ok(Address::from_slice(&slot.as_bytes()[12..]))
+}
+
+const CONFIRMATIONS_POLL_INTERVAL: Duration = Duration::from_secs(2);
 
-        let receipt = tx
+/// How long to wait for a transaction to be mined and confirmed, unless
+/// configured with `--tx-timeout`
+pub const TX_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Base delay between attempts of a transaction, multiplied by the attempt
+pub const RETRY_BACKOFF: Duration = Duration::from_secs(2);
+
+fn request(to: Address, call_data: Bytes, legacy: bool) -> TypedTransaction {
+    if legacy {
+        TransactionRequest::new().to(to).data(call_data).into()
+    } else {
+        Eip1559TransactionRequest::new()
+            .to(to)
+            .data(call_data)
+            .into()
+    }
+}
+
+/// Fills a transaction to be dumped from the `--dump-*` options instead of
+/// querying the chain
+fn fill_offline(
+    context: &DeploymentContext,
+    tx: &mut TypedTransaction,
+    from: Address,
+) -> eyre::Result<()> {
+    let cmd = &context.cmd;
+
+    tx.set_from(from);
+    tx.set_chain_id(context.chain_id);
+    tx.set_gas(
+        cmd.dump_gas_limit
+            .context("--dump-gas-limit is required with --dump-tx-for")?,
+    );
+
+    match tx {
+        TypedTransaction::Eip1559(inner) => {
+            let max_fee_per_gas = cmd.dump_max_fee_per_gas.context(
+                "--dump-max-fee-per-gas is required with --dump-tx-for",
+            )?;
+            let max_priority_fee_per_gas =
+                cmd.dump_max_priority_fee_per_gas.context(
+                    "--dump-max-priority-fee-per-gas is required with --dump-tx-for",
+                )?;
+
+            inner.max_fee_per_gas = Some(max_fee_per_gas.into());
+            inner.max_priority_fee_per_gas =
+                Some(max_priority_fee_per_gas.into());
+        }
+        _ => {
+            let gas_price = cmd.dump_gas_price.context(
+                "--dump-gas-price is required with --dump-tx-for and --legacy",
+            )?;
+
+            tx.set_gas_price(gas_price);
+        }
+    }
+
+    Ok(())
+}
+
+/// Raises the fees by 1/8, above the 10% nodes require to replace a pending
+/// transaction with the same nonce
+fn bump_fees(tx: &mut TypedTransaction) {
+    let bump = |fee: U256| fee + fee / 8 + 1;
+
+    match tx {
+        TypedTransaction::Eip1559(inner) => {
+            inner.max_fee_per_gas = inner.max_fee_per_gas.map(bump);
+            inner.max_priority_fee_per_gas =
+                inner.max_priority_fee_per_gas.map(bump);
+        }
+        TypedTransaction::Legacy(inner) => {
+            inner.gas_price = inner.gas_price.map(bump);
+        }
+        TypedTransaction::Eip2930(inner) => {
+            inner.tx.gas_price = inner.tx.gas_price.map(bump);
+        }
+    }
+}
+
+/// Sends `tx`, recording its hash in `sent`, and waits up to `timeout` for
+/// its receipt
+async fn send_and_confirm(
+    signer: &RpcSigner,
+    tx: &TypedTransaction,
+    sent: &mut Vec<H256>,
+    timeout: Duration,
+) -> eyre::Result<TransactionReceipt> {
+    let pending = signer
+        .0
+        .send_transaction(tx.clone(), None)
+        .await
+        .context("Send transaction")?;
+
+    let tx_hash = pending.tx_hash();
+    sent.push(tx_hash);
+
+    Span::current().record("transaction_hash", field::debug(tx_hash));
+
+    await_confirmed(signer, tx_hash, 1, timeout).await
+}
+
+/// The receipt of the first of `tx_hashes` that has been mined, if any
+async fn mined_receipt(
+    signer: &RpcSigner,
+    tx_hashes: &[H256],
+) -> eyre::Result<Option<TransactionReceipt>> {
+    for tx_hash in tx_hashes {
+        let receipt = signer
+            .0
+            .get_transaction_receipt(*tx_hash)
             .await
-            .context("Awaiting receipt")?
-            .context("Failed to execute")?;
+            .context("Fetching receipt")?;
 
-        if receipt.status != Some(1.into()) {
-            bail!("Failed!");
+        if let Some(receipt) = receipt.filter(|r| r.block_number.is_some()) {
+            return Ok(Some(receipt));
         }
+    }
 
-        Ok(())
+    Ok(None)
+}
+
+/// Selector of the standard `Error(string)` revert
+const ERROR_STRING_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+
+/// Waits until the transaction has been included with at least `confirmations`
+/// blocks on top of (and including) the one it was mined in
+///
+/// Errors fetching the receipt or the block number are retried until
+/// `timeout`, so a flaky RPC doesn't fail a transaction that was sent. Errors
+/// with the decoded revert reason if the transaction failed, or if it isn't
+/// confirmed within `timeout`
+#[instrument(skip(provider))]
+pub async fn await_confirmed(
+    provider: &RpcSigner,
+    tx_hash: H256,
+    confirmations: u64,
+    timeout: Duration,
+) -> eyre::Result<TransactionReceipt> {
+    let deadline = Instant::now() + timeout;
+    let mut last_error = None;
+
+    let receipt = loop {
+        match poll_confirmations(provider, tx_hash, confirmations).await {
+            Ok(Some(receipt)) => break receipt,
+            Ok(None) => {}
+            Err(err) => {
+                warn!("Polling transaction {tx_hash:?} failed: {err:?}");
+                last_error = Some(err);
+            }
+        }
+
+        if Instant::now() >= deadline {
+            let err = eyre::eyre!(
+                "Transaction {tx_hash:?} not confirmed within {timeout:?}"
+            );
+
+            return Err(match last_error {
+                Some(last_error) => last_error.wrap_err(err),
+                None => err,
+            });
+        }
+
+        tokio::time::sleep(CONFIRMATIONS_POLL_INTERVAL).await;
+    };
+
+    if receipt.status != Some(1.into()) {
+        let reason = revert_reason(provider, &receipt).await;
+
+        bail!(
+            "Transaction {tx_hash:?} reverted: {}",
+            reason.as_deref().unwrap_or("unknown reason")
+        );
+    }
+
+    Ok(receipt)
+}
+
+/// The receipt of the transaction once it has `confirmations`
+async fn poll_confirmations(
+    provider: &RpcSigner,
+    tx_hash: H256,
+    confirmations: u64,
+) -> eyre::Result<Option<TransactionReceipt>> {
+    let receipt = provider
+        .0
+        .get_transaction_receipt(tx_hash)
+        .await
+        .context("Fetching receipt")?;
+
+    // The receipt might disappear during a reorg, keep waiting for it
+    let Some(receipt) = receipt else {
+        return Ok(None);
+    };
+
+    let Some(block_number) = receipt.block_number else {
+        return Ok(None);
+    };
+
+    let current_block = provider
+        .0
+        .get_block_number()
+        .await
+        .context("Fetching block number")?;
+    let current_confirmations =
+        (current_block + 1).saturating_sub(block_number).as_u64();
+
+    if current_confirmations < confirmations {
+        info!(
+            "Waiting for confirmations ({current_confirmations}/{confirmations})"
+        );
+
+        return Ok(None);
+    }
+
+    Ok(Some(receipt))
+}
+
+/// Replays a failed transaction at its block to recover the revert reason
+async fn revert_reason(
+    provider: &RpcSigner,
+    receipt: &TransactionReceipt,
+) -> Option<String> {
+    let tx = provider
+        .0
+        .get_transaction(receipt.transaction_hash)
+        .await
+        .ok()??;
+
+    let err = provider
+        .0
+        .call(&(&tx).into(), receipt.block_number.map(Into::into))
+        .await
+        .err()?;
+
+    let revert_data = err.as_error_response()?.as_revert_data()?;
+
+    match revert_data.strip_prefix(&ERROR_STRING_SELECTOR) {
+        Some(encoded) => String::decode(encoded).ok(),
+        None => Some(format!("{revert_data}")),
     }
 }
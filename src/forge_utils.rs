@@ -1,3 +1,4 @@
+pub mod build;
 pub mod common;
 pub mod create;
 pub mod inspect_abi;
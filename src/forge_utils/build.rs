@@ -0,0 +1,83 @@
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Instant;
+
+use tracing::{info, instrument};
+
+use super::common::{ensure_non_interactive, forge_command};
+
+/// Serializes compilations into the shared forge project, so concurrent (e.g.
+/// multi-chain) deployments don't write the same artifacts at the same time
+///
+/// Held by [`ForgeBuild::run`], and by anything else which compiles new
+/// sources into the project.
+pub static FORGE_BUILD_LOCK: tokio::sync::Mutex<()> =
+    tokio::sync::Mutex::const_new(());
+
+/// Compiles the whole project, so subsequent creates and inspections find
+/// up-to-date artifacts instead of each compiling on their own
+#[derive(Debug, Default)]
+pub struct ForgeBuild {
+    cwd: Option<PathBuf>,
+    env: Vec<(String, String)>,
+    remappings: Vec<String>,
+}
+
+impl ForgeBuild {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_cwd(mut self, cwd: impl AsRef<Path>) -> Self {
+        self.cwd = Some(cwd.as_ref().to_owned());
+        self
+    }
+
+    pub fn with_remapping(mut self, remapping: impl ToString) -> Self {
+        self.remappings.push(remapping.to_string());
+        self
+    }
+
+    pub fn with_env(
+        mut self,
+        key: impl ToString,
+        value: impl ToString,
+    ) -> Self {
+        self.env.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    #[instrument(name = "forge_build", skip_all)]
+    pub async fn run(&self) -> eyre::Result<()> {
+        let mut cmd = forge_command();
+        cmd.envs(self.env.iter().map(|(key, value)| (key, value)));
+        cmd.arg("build");
+
+        if let Some(cwd) = &self.cwd {
+            cmd.current_dir(cwd);
+        }
+
+        for remapping in &self.remappings {
+            cmd.arg("--remappings");
+            cmd.arg(remapping);
+        }
+
+        cmd.stdin(Stdio::null());
+
+        let _guard = FORGE_BUILD_LOCK.lock().await;
+
+        let start = Instant::now();
+
+        let output = cmd.output().await?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            ensure_non_interactive(&stderr)?;
+            eyre::bail!("forge build failed: {}", stderr);
+        }
+
+        info!("Built contracts in {:?}", start.elapsed());
+
+        Ok(())
+    }
+}
@@ -1,9 +1,29 @@
 use std::fmt;
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::OnceLock;
 
 use ethers::types::Address;
 
-#[derive(Debug, Clone)]
+/// Env var with the path of the forge binary, for when it's not on `PATH`
+const FORGE_BIN_VAR: &str = "FORGE_BIN";
+
+/// A command running the forge binary of `FORGE_BIN`, `forge` by default
+///
+/// The variable is read once, so every invocation uses the same binary.
+pub fn forge_command() -> tokio::process::Command {
+    static FORGE_BIN: OnceLock<PathBuf> = OnceLock::new();
+
+    let forge_bin = FORGE_BIN.get_or_init(|| {
+        std::env::var_os(FORGE_BIN_VAR)
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from("forge"))
+    });
+
+    tokio::process::Command::new(forge_bin)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct ContractSpec {
     pub path: Option<PathBuf>,
     pub name: String,
@@ -23,6 +43,54 @@ impl ContractSpec {
             name: name.to_string(),
         }
     }
+
+    /// Turns forge's failure to resolve a bare contract name into an
+    /// actionable error
+    ///
+    /// Bare names break once several contracts share the name (e.g. generated
+    /// verifiers are all called `Verifier`), in which case the spec has to be
+    /// qualified with a path.
+    pub fn ensure_unambiguous(&self, forge_stderr: &str) -> eyre::Result<()> {
+        if self.path.is_some() {
+            return Ok(());
+        }
+
+        let stderr = forge_stderr.to_lowercase();
+
+        if stderr.contains("multiple")
+            && stderr.contains(&self.name.to_lowercase())
+        {
+            eyre::bail!(
+                "Contract name {} is ambiguous - qualify the contract spec with a path (path/to/Contract.sol:{})",
+                self.name,
+                self.name
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Turns forge failing on an interactive prompt (e.g. a keystore password)
+/// into an actionable error
+///
+/// forge is always run with a null stdin, so instead of hanging on the prompt
+/// it fails to read the input.
+pub fn ensure_non_interactive(forge_stderr: &str) -> eyre::Result<()> {
+    let stderr = forge_stderr.to_lowercase();
+
+    let prompted = stderr.contains("password")
+        || stderr.contains("not a terminal")
+        || stderr.contains("inappropriate ioctl for device");
+
+    if prompted {
+        eyre::bail!(
+            "forge tried to prompt for input (e.g. a keystore password) - pass the private key explicitly instead: {}",
+            forge_stderr.trim()
+        );
+    }
+
+    Ok(())
 }
 
 #[derive(Debug)]
@@ -54,6 +122,27 @@ impl ExternalDep {
     }
 }
 
+impl FromStr for ContractSpec {
+    type Err = eyre::Error;
+
+    /// Parses `Name` or `path/to/Contract.sol:Name`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (path, name) = match s.rsplit_once(':') {
+            Some((path, name)) => (Some(PathBuf::from(path)), name),
+            None => (None, s),
+        };
+
+        if name.is_empty() {
+            eyre::bail!("Missing contract name in {s}");
+        }
+
+        Ok(Self {
+            path,
+            name: name.to_string(),
+        })
+    }
+}
+
 impl fmt::Display for ContractSpec {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         if let Some(path) = self.path.as_deref() {
@@ -69,3 +158,56 @@ impl fmt::Display for ExternalDep {
         write!(f, "{}:{:?}", self.contract_spec, self.address)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AMBIGUOUS_STDERR: &str = "Error: \nMultiple contracts found matching `Verifier`, please specify the full path";
+
+    #[test]
+    fn ambiguous_bare_name() {
+        let spec = ContractSpec::name("Verifier");
+
+        assert!(spec.ensure_unambiguous(AMBIGUOUS_STDERR).is_err());
+        assert!(spec.ensure_unambiguous("Error: compilation failed").is_ok());
+    }
+
+    #[test]
+    fn qualified_name_is_never_ambiguous() {
+        let spec = ContractSpec::path_name(
+            PathBuf::from("./insertion_30_10.sol"),
+            "Verifier",
+        );
+
+        assert!(spec.ensure_unambiguous(AMBIGUOUS_STDERR).is_ok());
+    }
+
+    #[test]
+    fn parse_contract_spec() {
+        let spec: ContractSpec = "Verifier".parse().unwrap();
+        assert_eq!(spec.path, None);
+        assert_eq!(spec.name, "Verifier");
+
+        let spec: ContractSpec =
+            "./verifier_contracts/insertion_30_10.sol:Verifier"
+                .parse()
+                .unwrap();
+        assert_eq!(
+            spec.path,
+            Some(PathBuf::from("./verifier_contracts/insertion_30_10.sol"))
+        );
+        assert_eq!(spec.name, "Verifier");
+
+        assert!("Verifier.sol:".parse::<ContractSpec>().is_err());
+    }
+
+    #[test]
+    fn interactive_prompt() {
+        assert!(ensure_non_interactive(
+            "Error: \nEnter keystore password: IO error: not a terminal"
+        )
+        .is_err());
+        assert!(ensure_non_interactive("Error: compilation failed").is_ok());
+    }
+}
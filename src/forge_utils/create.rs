@@ -1,11 +1,55 @@
 use std::path::{Path, PathBuf};
+use std::process::{Output, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use ethers::types::{Address, H256};
+use ethers::abi::token::{LenientTokenizer, Tokenizer};
+use ethers::abi::Constructor;
+use ethers::providers::{Middleware, Provider};
+use ethers::signers::{LocalWallet, Signer};
+use ethers::types::{Address, BlockNumber, TransactionReceipt, H256, U256};
+use ethers::utils::{get_contract_address, get_create2_address, keccak256};
+use eyre::{Context, ContextCompat};
+use reqwest::Url;
 use serde::{Deserialize, Serialize};
-use tracing::{info, instrument};
+use tracing::{field, info, instrument, warn, Span};
 
-use super::common::{ContractSpec, ExternalDep};
-use crate::cli::PrivateKey;
+use super::common::{
+    ensure_non_interactive, forge_command, ContractSpec, ExternalDep,
+};
+use super::inspect_abi::ForgeInspectAbi;
+use crate::cli::{PrivateKey, RpcHeader};
+use crate::common_keys::RpcTransport;
+use crate::ethers_utils::{RETRY_BACKOFF, TX_TIMEOUT};
+use crate::serde_utils;
+use crate::utils::git_head;
+
+const RECEIPT_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+const DEFAULT_MAX_RETRIES: u32 = 3;
+
+/// Transient RPC failures, matched case-insensitively against forge's stderr
+///
+/// Status codes are only matched together with their wording, a bare "502"
+/// would also match addresses, hashes and calldata in the output
+const RETRYABLE_ERRORS: &[&str] = &[
+    "timed out",
+    "timeout",
+    "http error 429",
+    "status code: 429",
+    "too many requests",
+    "rate limit",
+    "http error 502",
+    "status code: 502",
+    "bad gateway",
+    "http error 503",
+    "status code: 503",
+    "service unavailable",
+    "connection reset",
+    "connection refused",
+    "replacement transaction underpriced",
+];
 
 #[derive(Debug)]
 pub struct ForgeCreate {
@@ -14,11 +58,31 @@ pub struct ForgeCreate {
     override_contract_source: Option<PathBuf>,
     private_key: Option<PrivateKey>,
     rpc_url: Option<String>,
+    rpc_headers: Vec<RpcHeader>,
     external_deps: Vec<ExternalDep>,
     override_nonce: Option<u64>,
+    create2_deployer: Option<Address>,
+    salt: Option<H256>,
+    expected_code_hash: Option<H256>,
+    delay: Option<Duration>,
+    max_retries: u32,
+    retry_backoff: Duration,
+    receipt_timeout: Duration,
+    retryable_errors: Vec<String>,
+    nonce_counter: Option<Arc<AtomicU64>>,
+    gas_price: Option<U256>,
+    priority_gas_price: Option<U256>,
+    gas_limit: Option<U256>,
+    legacy: bool,
     constructor_args: Vec<String>,
+    extra_constructor_args: Vec<String>,
     verification_args: ForgeCreateVerificationArgs,
     no_verify: bool,
+    broadcast: bool,
+    refuse_broadcast: Option<String>,
+    use_receipt_address: bool,
+    env: Vec<(String, String)>,
+    remappings: Vec<String>,
 }
 
 #[derive(Debug)]
@@ -34,6 +98,25 @@ pub struct ForgeOutput {
     pub deployer: Address,
     pub deployed_to: Address,
     pub transaction_hash: H256,
+    /// Set when the create was simulated - `deployed_to` is then the predicted
+    /// address and `transaction_hash` is zero
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub simulated: bool,
+    /// `HEAD` of the contracts source checkout at the time of the create
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_commit: Option<String>,
+    /// Keccak hash of the created contract's runtime code
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code_hash: Option<H256>,
+    /// Block the create was included in, zero when simulated
+    #[serde(default, deserialize_with = "serde_utils::quantity::deserialize")]
+    pub block_number: u64,
+    /// Gas used by the create, zero when simulated
+    #[serde(default, deserialize_with = "serde_utils::quantity::deserialize")]
+    pub gas_used: U256,
+    /// Salt of a deterministic create
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub salt: Option<H256>,
 }
 
 impl ForgeCreate {
@@ -43,19 +126,65 @@ impl ForgeCreate {
             contract_spec,
             override_contract_source: None,
             override_nonce: None,
+            create2_deployer: None,
+            salt: None,
+            expected_code_hash: None,
+            delay: None,
+            max_retries: DEFAULT_MAX_RETRIES,
+            retry_backoff: RETRY_BACKOFF,
+            receipt_timeout: TX_TIMEOUT,
+            retryable_errors: RETRYABLE_ERRORS
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+            nonce_counter: None,
+            gas_price: None,
+            priority_gas_price: None,
+            gas_limit: None,
+            legacy: false,
             private_key: None,
             rpc_url: None,
+            rpc_headers: vec![],
             external_deps: vec![],
             constructor_args: vec![],
+            extra_constructor_args: vec![],
             verification_args: ForgeCreateVerificationArgs {
                 verification_api_key: None,
                 verifier: None,
                 verifier_url: None,
             },
             no_verify: false,
+            broadcast: true,
+            refuse_broadcast: None,
+            use_receipt_address: false,
+            env: vec![],
+            remappings: vec![],
         }
     }
 
+    /// Only simulate the create instead of broadcasting the transaction
+    pub fn simulate(self) -> Self {
+        self.with_broadcast(false)
+    }
+
+    pub fn with_broadcast(mut self, broadcast: bool) -> Self {
+        self.broadcast = broadcast;
+        self
+    }
+
+    /// Fail with `reason` instead of broadcasting the create
+    pub fn with_broadcast_refused(mut self, reason: impl ToString) -> Self {
+        self.refuse_broadcast = Some(reason.to_string());
+        self
+    }
+
+    /// Take the address of the created contract from the transaction receipt
+    /// instead of forge's output, for chains with custom CREATE semantics
+    pub fn with_receipt_address(mut self, use_receipt_address: bool) -> Self {
+        self.use_receipt_address = use_receipt_address;
+        self
+    }
+
     pub fn no_verify(mut self) -> Self {
         self.no_verify = true;
         self
@@ -104,6 +233,104 @@ impl ForgeCreate {
         self
     }
 
+    /// The CREATE2 factory used for deterministic deployments
+    pub fn with_create2_deployer(mut self, create2_deployer: Address) -> Self {
+        self.create2_deployer = Some(create2_deployer);
+        self
+    }
+
+    /// Create the contract deterministically through the CREATE2 deployer
+    ///
+    /// The address then only depends on the deployer, `salt` and the init
+    /// code, so it's the same on every chain given identical bytecode and
+    /// constructor args - see [`create2_address`].
+    pub fn with_salt(mut self, salt: H256) -> Self {
+        self.salt = Some(salt);
+        self
+    }
+
+    /// Fail unless the runtime code of the created contract hashes to
+    /// `expected_code_hash`, if set
+    pub fn with_expected_code_hash(
+        mut self,
+        expected_code_hash: Option<H256>,
+    ) -> Self {
+        self.expected_code_hash = expected_code_hash;
+        self
+    }
+
+    /// Wait before broadcasting, to throttle the transactions sent
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = Some(delay);
+        self
+    }
+
+    /// Retry a create which failed with a retryable error this many times,
+    /// as long as its nonce is unused
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Delay before the first retry, doubled on every further one
+    pub fn with_retry_backoff(mut self, retry_backoff: Duration) -> Self {
+        self.retry_backoff = retry_backoff;
+        self
+    }
+
+    /// How long to wait for the receipt of the create's transaction
+    pub fn with_receipt_timeout(mut self, receipt_timeout: Duration) -> Self {
+        self.receipt_timeout = receipt_timeout;
+        self
+    }
+
+    /// Also retry failures whose stderr contains `pattern`, case-insensitive
+    pub fn with_retryable_error(mut self, pattern: impl ToString) -> Self {
+        self.retryable_errors.push(pattern.to_string());
+        self
+    }
+
+    /// The counter the nonce of the create was taken from, advanced when the
+    /// nonce has to be resynced from the chain
+    pub fn with_nonce_counter(mut self, nonce_counter: Arc<AtomicU64>) -> Self {
+        self.nonce_counter = Some(nonce_counter);
+        self
+    }
+
+    /// Gas price of a legacy create transaction
+    ///
+    /// forge takes it from the same flag as the max fee per gas of EIP-1559
+    /// transactions, so this overrides [`Self::with_max_fee_per_gas`].
+    pub fn with_gas_price(mut self, gas_price: U256) -> Self {
+        self.gas_price = Some(gas_price);
+        self
+    }
+
+    pub fn with_max_fee_per_gas(mut self, max_fee_per_gas: U256) -> Self {
+        self.gas_price = Some(max_fee_per_gas);
+        self
+    }
+
+    pub fn with_max_priority_fee_per_gas(
+        mut self,
+        max_priority_fee_per_gas: U256,
+    ) -> Self {
+        self.priority_gas_price = Some(max_priority_fee_per_gas);
+        self
+    }
+
+    /// Create the contract with a legacy transaction instead of an EIP-1559
+    /// one, in which case the max fee per gas is used as the gas price
+    pub fn with_legacy(mut self, legacy: bool) -> Self {
+        self.legacy = legacy;
+        self
+    }
+
+    pub fn with_gas_limit(mut self, gas_limit: U256) -> Self {
+        self.gas_limit = Some(gas_limit);
+        self
+    }
+
     pub fn with_private_key(mut self, private_key: PrivateKey) -> Self {
         self.private_key = Some(private_key);
         self
@@ -114,6 +341,11 @@ impl ForgeCreate {
         self
     }
 
+    pub fn with_rpc_header(mut self, rpc_header: RpcHeader) -> Self {
+        self.rpc_headers.push(rpc_header);
+        self
+    }
+
     pub fn with_external_dep(mut self, external_dep: ExternalDep) -> Self {
         self.external_deps.push(external_dep);
         self
@@ -124,9 +356,163 @@ impl ForgeCreate {
         self
     }
 
-    #[instrument(name = "forge_create", skip_all)]
+    /// Constructor args passed after the ones of [`Self::with_constructor_arg`]
+    ///
+    /// Unlike those they're checked against the constructor in the ABI
+    /// before creating the contract.
+    pub fn with_extra_constructor_args(
+        mut self,
+        extra_constructor_args: Vec<String>,
+    ) -> Self {
+        self.extra_constructor_args = extra_constructor_args;
+        self
+    }
+
+    pub fn with_remapping(mut self, remapping: impl ToString) -> Self {
+        self.remappings.push(remapping.to_string());
+        self
+    }
+
+    pub fn with_env(
+        mut self,
+        key: impl ToString,
+        value: impl ToString,
+    ) -> Self {
+        self.env.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    #[instrument(
+        name = "forge_create",
+        skip_all,
+        fields(
+            contract = %self.contract_spec,
+            deployed_to = tracing::field::Empty,
+            transaction_hash = tracing::field::Empty,
+        )
+    )]
     pub async fn run(&self) -> eyre::Result<ForgeOutput> {
-        let mut cmd = tokio::process::Command::new("forge");
+        if let Some(reason) = self.refuse_broadcast.as_ref() {
+            if self.broadcast {
+                eyre::bail!("{reason}");
+            }
+        }
+
+        if !self.extra_constructor_args.is_empty() {
+            self.check_extra_constructor_args().await?;
+        }
+
+        let output = self.output_with_retries().await?;
+
+        let mut output: ForgeOutput = if self.broadcast {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+
+            parse_forge_output(&stdout)?
+        } else {
+            self.simulated_output()?
+        };
+
+        output.source_commit = self.source_commit().await;
+        output.salt = self.salt;
+
+        // Older forge versions leave out where the create landed, so it's
+        // taken from the receipt instead
+        let needs_receipt =
+            self.use_receipt_address || output.block_number == 0;
+
+        let receipt = if self.broadcast && needs_receipt {
+            Some(self.receipt(&output).await?)
+        } else {
+            None
+        };
+
+        if let Some(receipt) = receipt.as_ref() {
+            if output.block_number == 0 {
+                output.block_number = receipt
+                    .block_number
+                    .map(|block_number| block_number.as_u64())
+                    .unwrap_or_default();
+                output.gas_used = receipt.gas_used.unwrap_or_default();
+            }
+        }
+
+        if let Some(receipt) =
+            receipt.as_ref().filter(|_| self.use_receipt_address)
+        {
+            let receipt_address = Self::receipt_address(&output, receipt)?;
+
+            if receipt_address != output.deployed_to {
+                warn!(
+                    "forge reported {:?} but the receipt has {receipt_address:?} - using the receipt's address",
+                    output.deployed_to
+                );
+
+                output.deployed_to = receipt_address;
+            }
+        }
+
+        if self.broadcast {
+            let code_hash = self.code_hash(output.deployed_to).await?;
+
+            if let Some(expected_code_hash) = self.expected_code_hash {
+                if code_hash != expected_code_hash {
+                    eyre::bail!(
+                        "Code of {} at {:?} hashes to {code_hash:?}, expected {expected_code_hash:?}",
+                        self.contract_spec,
+                        output.deployed_to
+                    );
+                }
+            }
+
+            output.code_hash = Some(code_hash);
+        }
+
+        let span = Span::current();
+        span.record("deployed_to", field::debug(output.deployed_to));
+        span.record("transaction_hash", field::debug(output.transaction_hash));
+
+        info!("Created: {output:?}");
+
+        Ok(output)
+    }
+}
+
+impl ForgeCreate {
+    async fn check_extra_constructor_args(&self) -> eyre::Result<()> {
+        let mut inspect_abi = ForgeInspectAbi::new(self.contract_spec.clone());
+
+        if let Some(cwd) = &self.cwd {
+            inspect_abi = inspect_abi.with_cwd(cwd);
+        }
+
+        if let Some(override_contract_source) = &self.override_contract_source {
+            inspect_abi = inspect_abi
+                .with_override_contract_source(override_contract_source);
+        }
+
+        for remapping in &self.remappings {
+            inspect_abi = inspect_abi.with_remapping(remapping);
+        }
+
+        for (key, value) in &self.env {
+            inspect_abi = inspect_abi.with_env(key, value);
+        }
+
+        let abi = inspect_abi.run().await?;
+
+        check_constructor_args(
+            abi.constructor(),
+            self.constructor_args.len(),
+            &self.extra_constructor_args,
+        )
+        .with_context(|| {
+            format!("Invalid constructor args for {}", self.contract_spec)
+        })
+    }
+
+    fn command(&self, nonce: Option<u64>) -> tokio::process::Command {
+        let mut cmd = forge_command();
+        cmd.envs(self.env.iter().map(|(key, value)| (key, value)));
         cmd.arg("create");
 
         if let Some(cwd) = &self.cwd {
@@ -141,6 +527,11 @@ impl ForgeCreate {
 
         cmd.arg(self.contract_spec.to_string());
 
+        for remapping in &self.remappings {
+            cmd.arg("--remappings");
+            cmd.arg(remapping);
+        }
+
         if !self.external_deps.is_empty() {
             let mut external_deps = Vec::new();
 
@@ -166,12 +557,52 @@ impl ForgeCreate {
             cmd.arg(rpc_url);
         }
 
-        if let Some(nonce) = self.override_nonce {
+        for rpc_header in &self.rpc_headers {
+            cmd.arg("--rpc-headers");
+            cmd.arg(format!("{rpc_header:#}"));
+        }
+
+        if let Some(nonce) = nonce {
             cmd.arg("--nonce");
             cmd.arg(nonce.to_string());
         }
 
-        for constructor_arg in &self.constructor_args {
+        if let Some(create2_deployer) = self.create2_deployer {
+            cmd.arg("--create2-deployer");
+            cmd.arg(format!("{create2_deployer:?}"));
+        }
+
+        if let Some(salt) = self.salt {
+            cmd.arg("--salt");
+            cmd.arg(format!("{salt:?}"));
+        }
+
+        if let Some(gas_price) = self.gas_price {
+            cmd.arg("--gas-price");
+            cmd.arg(gas_price.to_string());
+        }
+
+        if self.legacy {
+            cmd.arg("--legacy");
+
+            if self.priority_gas_price.is_some() {
+                warn!("Ignoring the priority fee of a legacy create");
+            }
+        } else if let Some(priority_gas_price) = self.priority_gas_price {
+            cmd.arg("--priority-gas-price");
+            cmd.arg(priority_gas_price.to_string());
+        }
+
+        if let Some(gas_limit) = self.gas_limit {
+            cmd.arg("--gas-limit");
+            cmd.arg(gas_limit.to_string());
+        }
+
+        for constructor_arg in self
+            .constructor_args
+            .iter()
+            .chain(&self.extra_constructor_args)
+        {
             cmd.arg("--constructor-args");
             cmd.arg(constructor_arg);
         }
@@ -207,32 +638,543 @@ impl ForgeCreate {
             }
         }
 
+        if self.broadcast {
+            cmd.arg("--broadcast");
+        }
+
         cmd.arg("--json");
 
-        info!("Creating contract with {cmd:#?}");
+        let mut cmd_description = format!("{cmd:#?}");
+        for rpc_header in &self.rpc_headers {
+            if !rpc_header.value.is_empty() {
+                cmd_description =
+                    cmd_description.replace(&rpc_header.value, "***");
+            }
+        }
+
+        info!("Creating contract with {cmd_description}");
+
+        cmd.stdin(Stdio::null());
+
+        cmd
+    }
 
-        let output = cmd.output().await?;
+    /// Runs the create until it succeeds or the retries are used up
+    ///
+    /// Only failures matching one of the retryable errors are retried, with
+    /// exponential backoff, and only as long as the create's nonce is unused -
+    /// otherwise its transaction landed and a retry could only fail. A nonce
+    /// which was used by something else is resynced from the chain's pending
+    /// nonce instead, skipping nonces the counter already handed out.
+    async fn output_with_retries(&self) -> eyre::Result<Output> {
+        let mut nonce = self.override_nonce;
+        let mut attempt = 0;
+
+        loop {
+            if let Some(delay) = self.delay.filter(|_| self.broadcast) {
+                tokio::time::sleep(delay).await;
+            }
+
+            let output = self.command(nonce).output().await?;
+
+            if output.status.success() {
+                return Ok(output);
+            }
 
-        if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
-            eyre::bail!("forge create failed: {}", stderr);
+            ensure_non_interactive(&stderr)?;
+            self.contract_spec.ensure_unambiguous(&stderr)?;
+
+            if attempt >= self.max_retries {
+                eyre::bail!("forge create failed: {}", stderr);
+            }
+
+            if is_nonce_too_low(&stderr) {
+                if attempt > 0 {
+                    eyre::bail!(
+                        "The nonce of forge create was used after an earlier attempt, which may have created the contract - check the deployer's transactions before resuming: {}",
+                        stderr
+                    );
+                }
+
+                let Some(pending_nonce) =
+                    self.chain_nonce(BlockNumber::Pending).await?
+                else {
+                    eyre::bail!("forge create failed: {}", stderr);
+                };
+
+                // Nonces the counter handed out may still be outstanding, so
+                // it's only ever moved forward
+                let resynced_nonce = match &self.nonce_counter {
+                    Some(nonce_counter) => {
+                        nonce_counter
+                            .fetch_max(pending_nonce, Ordering::SeqCst);
+                        nonce_counter.fetch_add(1, Ordering::SeqCst)
+                    }
+                    None => pending_nonce,
+                };
+
+                warn!(
+                    "Nonce {} was already used, resyncing to {resynced_nonce}",
+                    nonce.unwrap_or_default()
+                );
+
+                nonce = Some(resynced_nonce);
+            } else if !self.is_retryable(&stderr) {
+                eyre::bail!("forge create failed: {}", stderr);
+            } else if self.nonce_used(nonce).await? {
+                eyre::bail!(
+                    "forge create failed after its transaction was sent, not retrying: {}",
+                    stderr
+                );
+            }
+
+            attempt += 1;
+
+            let backoff = self.retry_backoff * 2u32.pow(attempt - 1);
+
+            warn!(
+                "forge create attempt {attempt}/{} failed, retrying in {backoff:?}: {stderr}",
+                self.max_retries + 1
+            );
+
+            tokio::time::sleep(backoff).await;
         }
+    }
 
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        let s = strip_non_json(&stdout);
+    fn is_retryable(&self, stderr: &str) -> bool {
+        let stderr = stderr.to_lowercase();
 
-        let output = serde_json::from_str(s)?;
+        self.retryable_errors
+            .iter()
+            .any(|pattern| stderr.contains(&pattern.to_lowercase()))
+    }
 
-        info!("Created: {output:?}");
+    /// The deployer's nonce at `block`, if the create has a private key
+    async fn chain_nonce(
+        &self,
+        block: BlockNumber,
+    ) -> eyre::Result<Option<u64>> {
+        let Some(private_key) = self.private_key.as_ref() else {
+            return Ok(None);
+        };
 
-        Ok(output)
+        let deployer = LocalWallet::from(private_key.key.clone()).address();
+
+        let transaction_count = self
+            .provider()
+            .await?
+            .get_transaction_count(deployer, Some(block.into()))
+            .await
+            .context("Fetching the deployer's nonce")?;
+
+        Ok(Some(transaction_count.as_u64()))
+    }
+
+    /// Whether `nonce` has been used on-chain
+    async fn nonce_used(&self, nonce: Option<u64>) -> eyre::Result<bool> {
+        let Some(nonce) = nonce.filter(|_| self.broadcast) else {
+            return Ok(false);
+        };
+
+        Ok(self
+            .chain_nonce(BlockNumber::Latest)
+            .await?
+            .is_some_and(|chain_nonce| chain_nonce > nonce))
+    }
+
+    /// forge doesn't report an address when not broadcasting,
+    /// so predict it from the deployer and nonce
+    fn simulated_output(&self) -> eyre::Result<ForgeOutput> {
+        if self.salt.is_some() {
+            eyre::bail!(
+                "Simulating a deterministic create isn't supported - its address depends on the init code"
+            );
+        }
+
+        let private_key = self
+            .private_key
+            .as_ref()
+            .context("Simulating a create requires a private key")?;
+        let nonce = self
+            .override_nonce
+            .context("Simulating a create requires a nonce")?;
+
+        let deployer = LocalWallet::from(private_key.key.clone()).address();
+
+        Ok(ForgeOutput {
+            deployer,
+            deployed_to: get_contract_address(deployer, nonce),
+            transaction_hash: H256::zero(),
+            simulated: true,
+            source_commit: None,
+            code_hash: None,
+            block_number: 0,
+            gas_used: U256::zero(),
+            salt: None,
+        })
     }
+
+    async fn provider(&self) -> eyre::Result<Provider<RpcTransport>> {
+        let rpc_url: Url = self
+            .rpc_url
+            .as_ref()
+            .context("Reading from the chain requires an RPC url")?
+            .parse()?;
+
+        let transport =
+            RpcTransport::connect(&rpc_url, &self.rpc_headers).await?;
+
+        Ok(Provider::new(transport))
+    }
+
+    /// Keccak hash of the runtime code at `address`
+    async fn code_hash(&self, address: Address) -> eyre::Result<H256> {
+        let code = self
+            .provider()
+            .await?
+            .get_code(address, None)
+            .await
+            .with_context(|| format!("Fetching code at {address:?}"))?;
+
+        if code.is_empty() {
+            eyre::bail!("No contract code found at {address:?}");
+        }
+
+        Ok(H256(keccak256(&code)))
+    }
+
+    /// Waits for the receipt of the create's transaction
+    ///
+    /// Errors fetching the receipt are retried until the receipt timeout, the
+    /// create has been sent at this point
+    async fn receipt(
+        &self,
+        output: &ForgeOutput,
+    ) -> eyre::Result<TransactionReceipt> {
+        let provider = self.provider().await?;
+        let tx_hash = output.transaction_hash;
+        let deadline = Instant::now() + self.receipt_timeout;
+        let mut last_error = None;
+
+        loop {
+            match provider.get_transaction_receipt(tx_hash).await {
+                Ok(Some(receipt)) => return Ok(receipt),
+                Ok(None) => {}
+                Err(err) => {
+                    warn!("Fetching receipt of {tx_hash:?} failed: {err}");
+                    last_error = Some(err);
+                }
+            }
+
+            if Instant::now() >= deadline {
+                let err = eyre::eyre!("Missing receipt for {tx_hash:?}");
+
+                return Err(match last_error {
+                    Some(last_error) => {
+                        eyre::Report::new(last_error).wrap_err(err)
+                    }
+                    None => err,
+                });
+            }
+
+            tokio::time::sleep(RECEIPT_POLL_INTERVAL).await;
+        }
+    }
+
+    fn receipt_address(
+        output: &ForgeOutput,
+        receipt: &TransactionReceipt,
+    ) -> eyre::Result<Address> {
+        receipt.contract_address.with_context(|| {
+            format!(
+                "Receipt of {:?} has no contract address",
+                output.transaction_hash
+            )
+        })
+    }
+
+    /// The commit checked out in the working directory, if it's a git
+    /// repository
+    async fn source_commit(&self) -> Option<String> {
+        let cwd = self.cwd.clone().unwrap_or_else(|| PathBuf::from("."));
+
+        match git_head(cwd).await {
+            Ok(commit) => Some(commit),
+            Err(err) => {
+                warn!("Failed to read the contracts source commit: {err}");
+                None
+            }
+        }
+    }
+}
+
+/// forge's default CREATE2 deployer, used when no other one is set
+pub const DEFAULT_CREATE2_DEPLOYER: Address = ethers::types::H160(
+    hex_literal::hex!("4e59b44847b379578588920cA78FbF26c0B4956C"),
+);
+
+/// The address of a contract created through `create2_deployer` with `salt`
+///
+/// `init_code` is the creation bytecode followed by the ABI-encoded
+/// constructor args.
+pub fn create2_address(
+    create2_deployer: Option<Address>,
+    salt: H256,
+    init_code: &[u8],
+) -> Address {
+    get_create2_address(
+        create2_deployer.unwrap_or(DEFAULT_CREATE2_DEPLOYER),
+        salt,
+        init_code,
+    )
+}
+
+fn is_nonce_too_low(stderr: &str) -> bool {
+    let stderr = stderr.to_lowercase();
+
+    stderr.contains("nonce too low") || stderr.contains("nonce is too low")
+}
+
+/// Checks that the extra args complete the ones provided in code and that
+/// each parses as the type of its constructor input
+fn check_constructor_args(
+    constructor: Option<&Constructor>,
+    num_code_args: usize,
+    extra_args: &[String],
+) -> eyre::Result<()> {
+    let inputs = constructor.map(|c| c.inputs.as_slice()).unwrap_or_default();
+
+    let num_args = num_code_args + extra_args.len();
+    if inputs.len() != num_args {
+        eyre::bail!(
+            "The constructor takes {} args, got {num_args} ({num_code_args} from the deployer and {} extra)",
+            inputs.len(),
+            extra_args.len()
+        );
+    }
+
+    for (input, value) in inputs[num_code_args..].iter().zip(extra_args) {
+        LenientTokenizer::tokenize(&input.kind, value).with_context(|| {
+            format!("Parsing {value} as {} ({})", input.name, input.kind)
+        })?;
+    }
+
+    Ok(())
+}
+
+/// The output of the create in forge's stdout
+///
+/// Besides the JSON of the create forge prints compiler logs before it and
+/// warnings or verification logs (submission, GUID, polling) after it, all of
+/// which can contain braces of their own. So the first balanced `{...}` block
+/// which deserializes into a [`ForgeOutput`] is taken.
+fn parse_forge_output(stdout: &str) -> eyre::Result<ForgeOutput> {
+    stdout
+        .match_indices('{')
+        .filter_map(|(start, _)| balanced_object(&stdout[start..]))
+        .find_map(|object| serde_json::from_str(object).ok())
+        .with_context(|| format!("No forge create output in: {stdout}"))
+}
+
+/// The `{...}` block `s` starts with, if its braces are balanced
+///
+/// Braces within JSON strings don't count.
+fn balanced_object(s: &str) -> Option<&str> {
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for (idx, c) in s.char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+
+            continue;
+        }
+
+        match c {
+            '"' => in_string = true,
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+
+                if depth == 0 {
+                    return Some(&s[..=idx]);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    None
 }
 
-fn strip_non_json(s: &str) -> &str {
-    if let Some(last_closing_brace) = s.rfind('}') {
-        &s[..=last_closing_brace]
-    } else {
-        s
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use super::*;
+
+    const CREATE_WITH_VERIFY: &str = indoc! { r#"
+        {"deployer":"0x1111111111111111111111111111111111111111","deployedTo":"0x2222222222222222222222222222222222222222","transactionHash":"0x3333333333333333333333333333333333333333333333333333333333333333"}
+        Starting contract verification...
+        Waiting for etherscan to detect contract deployment...
+        Start verifying contract `0x2222222222222222222222222222222222222222` deployed on sepolia
+
+        Submitting verification for [src/WorldIDRouter.sol:WorldIDRouter] 0x2222222222222222222222222222222222222222.
+        Submitted contract for verification:
+        	Response: `OK`
+        	GUID: `a1b2c3d4e5f6`
+        	URL: https://sepolia.etherscan.io/address/0x2222222222222222222222222222222222222222
+        Contract verification status:
+        Response: `NOK`
+        Details: `{"status":"0","message":"NOTOK","result":"Pending in queue"}`
+        Contract verification status:
+        Response: `OK`
+        Details: `Pass - Verified`
+        Contract successfully verified
+    "# };
+
+    const CREATE_WITH_COMPILER_LOGS: &str = indoc! { r#"
+        [⠊] Compiling...
+        [⠒] Compiling 3 files with Solc 0.8.21
+        [⠢] Solc 0.8.21 finished in 1.42s
+        Compiler run successful with warnings:
+        Warning (2072): Unused local variable.
+          --> src/Verifier.sol:42:9:
+           |
+        42 |         uint256 unused = 0;
+           |         ^^^^^^^^^^^^^^
+        {"deployer":"0x1111111111111111111111111111111111111111","deployedTo":"0x2222222222222222222222222222222222222222","transactionHash":"0x3333333333333333333333333333333333333333333333333333333333333333"}
+        Warning: Contract code size exceeds 24576 bytes (a limit introduced in Spurious Dragon).
+        Note: consider enabling the optimizer, e.g. in foundry.toml:
+           |
+         3 | [profile.default] optimizer = true
+           |
+        Note: in Solidity this is `function verifyProof(uint256[8] calldata proof) external view { ... }`
+        Note: a stray brace } in a warning
+    "# };
+
+    fn assert_create_output(output: ForgeOutput) {
+        assert_eq!(output.deployer, Address::repeat_byte(0x11));
+        assert_eq!(output.deployed_to, Address::repeat_byte(0x22));
+        assert_eq!(output.transaction_hash, H256::repeat_byte(0x33));
+    }
+
+    #[test]
+    fn parses_create_output_with_verification_logs() {
+        assert_create_output(parse_forge_output(CREATE_WITH_VERIFY).unwrap());
+    }
+
+    #[test]
+    fn parses_create_output_between_compiler_logs_and_warnings() {
+        assert_create_output(
+            parse_forge_output(CREATE_WITH_COMPILER_LOGS).unwrap(),
+        );
+
+        // Leading braces which aren't the create's output
+        let stdout = format!(
+            "Note: `{{ unbalanced\n{{\"status\":\"0\"}}\n{CREATE_WITH_COMPILER_LOGS}"
+        );
+        assert_create_output(parse_forge_output(&stdout).unwrap());
+
+        assert!(parse_forge_output("Compiler run successful! {}").is_err());
+    }
+
+    #[test]
+    fn braces_in_json_strings_are_ignored() {
+        assert_eq!(
+            balanced_object(r#"{"a":"}{\"}"} trailing }"#),
+            Some(r#"{"a":"}{\"}"}"#)
+        );
+        assert_eq!(balanced_object("{ never closed"), None);
+    }
+
+    #[test]
+    fn extra_constructor_args_follow_the_code_args() {
+        let abi: ethers::abi::Abi = serde_json::from_str(
+            r#"[{
+                "type": "constructor",
+                "stateMutability": "nonpayable",
+                "inputs": [
+                    { "name": "implementation", "type": "address" },
+                    { "name": "data", "type": "bytes" },
+                    { "name": "delay", "type": "uint256" }
+                ]
+            }]"#,
+        )
+        .unwrap();
+
+        check_constructor_args(abi.constructor(), 2, &["3600".to_string()])
+            .unwrap();
+
+        // Wrong type
+        assert!(check_constructor_args(
+            abi.constructor(),
+            2,
+            &["an hour".to_string()]
+        )
+        .is_err());
+
+        // Wrong count
+        assert!(check_constructor_args(abi.constructor(), 2, &[]).is_err());
+        assert!(check_constructor_args(None, 0, &["1".to_string()]).is_err());
+    }
+
+    #[test]
+    fn only_transient_failures_are_retried() {
+        let forge_create = ForgeCreate::new(ContractSpec::name("Verifier"))
+            .with_retryable_error("header not found");
+
+        assert!(forge_create.is_retryable(
+            "Error: HTTP error 429 with body: Too Many Requests"
+        ));
+        assert!(forge_create.is_retryable(
+            "Error: (code: -32000, message: replacement transaction underpriced)"
+        ));
+        assert!(forge_create.is_retryable(
+            "Error: server returned an error response: status code: 502"
+        ));
+        assert!(forge_create.is_retryable("Error: 503 Service Unavailable"));
+        assert!(forge_create.is_retryable("Error: header not found"));
+        assert!(!forge_create.is_retryable("Error: compiler run failed"));
+        assert!(!forge_create
+            .is_retryable("Error: execution reverted, data: \"0x4290502503\""));
+
+        assert!(is_nonce_too_low(
+            "Error: (code: -32000, message: nonce too low, data: None)"
+        ));
+        assert!(!is_nonce_too_low("Error: request timed out"));
+    }
+
+    #[test]
+    fn create2_address_is_deterministic() {
+        let salt = H256::from_low_u64_be(0xcafebabe);
+        let init_code = hex::decode("deadbeef").unwrap();
+        let deployer: Address = "0x00000000000000000000000000000000deadbeef"
+            .parse()
+            .unwrap();
+
+        // Example from EIP-1014
+        let expected: Address = "0x60f3f640a8508fC6a86d45DF051962668E1e8AC7"
+            .parse()
+            .unwrap();
+
+        let first = create2_address(Some(deployer), salt, &init_code);
+        let second = create2_address(Some(deployer), salt, &init_code);
+
+        assert_eq!(first, expected);
+        assert_eq!(first, second);
+
+        assert_ne!(
+            create2_address(Some(deployer), H256::zero(), &init_code),
+            expected
+        );
+        assert_ne!(create2_address(None, salt, &init_code), expected);
     }
 }
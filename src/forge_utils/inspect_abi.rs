@@ -1,13 +1,16 @@
 use std::path::{Path, PathBuf};
+use std::process::Stdio;
 
 use tracing::instrument;
 
-use super::common::ContractSpec;
+use super::common::{ensure_non_interactive, forge_command, ContractSpec};
 
 pub struct ForgeInspectAbi {
     cwd: Option<PathBuf>,
     contract_spec: ContractSpec,
     override_contract_source: Option<PathBuf>,
+    env: Vec<(String, String)>,
+    remappings: Vec<String>,
 }
 
 impl ForgeInspectAbi {
@@ -16,6 +19,8 @@ impl ForgeInspectAbi {
             cwd: None,
             contract_spec,
             override_contract_source: None,
+            env: vec![],
+            remappings: vec![],
         }
     }
 
@@ -38,9 +43,24 @@ impl ForgeInspectAbi {
         self
     }
 
+    pub fn with_remapping(mut self, remapping: impl ToString) -> Self {
+        self.remappings.push(remapping.to_string());
+        self
+    }
+
+    pub fn with_env(
+        mut self,
+        key: impl ToString,
+        value: impl ToString,
+    ) -> Self {
+        self.env.push((key.to_string(), value.to_string()));
+        self
+    }
+
     #[instrument(name = "forge_inspect_abi", skip_all)]
     pub async fn run(&self) -> eyre::Result<ethers::abi::Abi> {
-        let mut cmd = tokio::process::Command::new("forge");
+        let mut cmd = forge_command();
+        cmd.envs(self.env.iter().map(|(key, value)| (key, value)));
 
         cmd.arg("inspect");
 
@@ -56,12 +76,21 @@ impl ForgeInspectAbi {
 
         cmd.arg(self.contract_spec.to_string());
 
+        for remapping in &self.remappings {
+            cmd.arg("--remappings");
+            cmd.arg(remapping);
+        }
+
         cmd.arg("abi");
 
+        cmd.stdin(Stdio::null());
+
         let output = cmd.output().await?;
 
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
+            ensure_non_interactive(&stderr)?;
+            self.contract_spec.ensure_unambiguous(&stderr)?;
             return Err(eyre::eyre!("forge create failed: {}", stderr));
         }
 
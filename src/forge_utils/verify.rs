@@ -1,10 +1,11 @@
 use std::path::PathBuf;
+use std::time::Duration;
 
-use ethers::types::Address;
+use ethers::types::{Address, Bytes};
 use eyre::ContextCompat;
 use tracing::{info, instrument};
 
-use super::ContractSpec;
+use super::{forge_command, ContractSpec, ExternalDep};
 
 pub struct ForgeVerify {
     spec: ContractSpec,
@@ -12,6 +13,14 @@ pub struct ForgeVerify {
     root: Option<PathBuf>,
     chain: Option<u64>,
     etherscan_api_key: Option<String>,
+    env: Vec<(String, String)>,
+    remappings: Vec<String>,
+    constructor_args: Option<Bytes>,
+    guess_constructor_args_rpc_url: Option<String>,
+    external_deps: Vec<ExternalDep>,
+    retries: Option<u32>,
+    delay: Option<u32>,
+    timeout: Option<Duration>,
 }
 
 impl ForgeVerify {
@@ -22,6 +31,14 @@ impl ForgeVerify {
             root: None,
             chain: None,
             etherscan_api_key: None,
+            env: vec![],
+            remappings: vec![],
+            constructor_args: None,
+            guess_constructor_args_rpc_url: None,
+            external_deps: vec![],
+            retries: None,
+            delay: None,
+            timeout: None,
         }
     }
 
@@ -43,9 +60,64 @@ impl ForgeVerify {
         self
     }
 
+    pub fn with_remapping(mut self, remapping: impl ToString) -> Self {
+        self.remappings.push(remapping.to_string());
+        self
+    }
+
+    /// ABI encoded constructor arguments
+    pub fn with_constructor_args(mut self, constructor_args: Bytes) -> Self {
+        self.constructor_args = Some(constructor_args);
+        self
+    }
+
+    /// Let forge take the constructor arguments from the creation
+    /// transaction, e.g. for proxies whose initializer call data isn't known
+    pub fn with_guessed_constructor_args(
+        mut self,
+        rpc_url: impl ToString,
+    ) -> Self {
+        self.guess_constructor_args_rpc_url = Some(rpc_url.to_string());
+        self
+    }
+
+    pub fn with_external_dep(mut self, external_dep: ExternalDep) -> Self {
+        self.external_deps.push(external_dep);
+        self
+    }
+
+    /// How often forge retries a failed submission or status check
+    pub fn with_retries(mut self, retries: u32) -> Self {
+        self.retries = Some(retries);
+        self
+    }
+
+    /// Seconds forge waits between its retries
+    pub fn with_delay(mut self, delay: u32) -> Self {
+        self.delay = Some(delay);
+        self
+    }
+
+    /// Kill forge if the verification takes longer than `timeout`, so a hung
+    /// verification can't stall everything after it
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn with_env(
+        mut self,
+        key: impl ToString,
+        value: impl ToString,
+    ) -> Self {
+        self.env.push((key.to_string(), value.to_string()));
+        self
+    }
+
     #[instrument(name = "forge_verify", skip_all)]
     pub async fn run(&self) -> eyre::Result<()> {
-        let mut cmd = tokio::process::Command::new("forge");
+        let mut cmd = forge_command();
+        cmd.envs(self.env.iter().map(|(key, value)| (key, value)));
         cmd.arg("verify-contract");
 
         cmd.arg("--watch");
@@ -69,12 +141,51 @@ impl ForgeVerify {
         cmd.arg("--etherscan-api-key");
         cmd.arg(etherscan_api_key);
 
+        for remapping in &self.remappings {
+            cmd.arg("--remappings");
+            cmd.arg(remapping);
+        }
+
+        if let Some(constructor_args) = &self.constructor_args {
+            cmd.arg("--constructor-args");
+            cmd.arg(hex::encode(constructor_args));
+        } else if let Some(rpc_url) = &self.guess_constructor_args_rpc_url {
+            cmd.arg("--guess-constructor-args");
+            cmd.arg("--rpc-url");
+            cmd.arg(rpc_url);
+        }
+
+        for external_dep in &self.external_deps {
+            cmd.arg("--libraries");
+            cmd.arg(external_dep.to_string());
+        }
+
+        if let Some(retries) = self.retries {
+            cmd.arg("--retries");
+            cmd.arg(retries.to_string());
+        }
+
+        if let Some(delay) = self.delay {
+            cmd.arg("--delay");
+            cmd.arg(delay.to_string());
+        }
+
         cmd.arg(format!("{:?}", self.address));
         cmd.arg(self.spec.to_string());
 
         info!("Verifying contract with {cmd:#?}");
 
-        let output = cmd.output().await?;
+        // Dropping the output future on a timeout kills forge
+        cmd.kill_on_drop(true);
+
+        let output = match self.timeout {
+            Some(timeout) => {
+                tokio::time::timeout(timeout, cmd.output()).await.map_err(
+                    |_| eyre::eyre!("forge verify timed out after {timeout:?}"),
+                )??
+            }
+            None => cmd.output().await?,
+        };
 
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
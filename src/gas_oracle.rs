@@ -0,0 +1,128 @@
+use std::time::Duration;
+
+use ethers::types::U256;
+use ethers::utils::parse_units;
+use eyre::{Context, ContextCompat};
+use reqwest::Url;
+use serde_json::Value;
+
+use crate::cli::RpcHeader;
+use crate::common_keys::header_map;
+
+/// An external source of EIP-1559 fees, for chains where the node's
+/// suggestions are poor
+#[derive(Debug, Clone)]
+pub struct GasOracle {
+    pub url: Url,
+    pub headers: Vec<RpcHeader>,
+    /// Dot separated path of the max fee per gas in the response, array
+    /// elements are selected by index (e.g. `blockPrices.0.maxFeePerGas`)
+    pub max_fee_path: String,
+    /// Dot separated path of the max priority fee per gas in the response
+    pub priority_fee_path: String,
+    /// Decimals of the fees in the response, e.g. 9 for gwei
+    pub decimals: u32,
+    /// Timeout of a whole request, so a hanging oracle doesn't stall the
+    /// transaction
+    pub timeout: Duration,
+}
+
+/// EIP-1559 fees in wei
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fees {
+    pub max_fee_per_gas: U256,
+    pub max_priority_fee_per_gas: U256,
+}
+
+impl GasOracle {
+    pub async fn fetch_fees(&self) -> eyre::Result<Fees> {
+        let client = reqwest::Client::builder()
+            .default_headers(header_map(&self.headers)?)
+            .timeout(self.timeout)
+            .build()?;
+
+        let response: Value = client
+            .get(self.url.clone())
+            .send()
+            .await
+            .context("Requesting gas oracle")?
+            .error_for_status()?
+            .json()
+            .await
+            .context("Parsing gas oracle response")?;
+
+        self.parse_fees(&response)
+    }
+
+    fn parse_fees(&self, response: &Value) -> eyre::Result<Fees> {
+        Ok(Fees {
+            max_fee_per_gas: self.parse_fee(response, &self.max_fee_path)?,
+            max_priority_fee_per_gas: self
+                .parse_fee(response, &self.priority_fee_path)?,
+        })
+    }
+
+    fn parse_fee(&self, response: &Value, path: &str) -> eyre::Result<U256> {
+        let value = lookup(response, path).with_context(|| {
+            format!("Missing {path} in gas oracle response")
+        })?;
+
+        let value = match value {
+            Value::Number(number) => number.to_string(),
+            Value::String(string) => string.clone(),
+            value => eyre::bail!("Unexpected value for {path}: {value}"),
+        };
+
+        let fee = parse_units(&value, self.decimals)
+            .with_context(|| format!("Parsing {path} = {value}"))?;
+
+        Ok(fee.into())
+    }
+}
+
+/// Follows a dot separated path of object keys and array indices
+fn lookup<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.')
+        .try_fold(value, |value, segment| match value {
+            Value::Array(array) => array.get(segment.parse::<usize>().ok()?),
+            value => value.get(segment),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn parse_nested_fees() {
+        let oracle = GasOracle {
+            url: "http://localhost".parse().unwrap(),
+            headers: vec![],
+            max_fee_path: "blockPrices.0.estimatedPrices.0.maxFeePerGas"
+                .to_string(),
+            priority_fee_path:
+                "blockPrices.0.estimatedPrices.0.maxPriorityFeePerGas"
+                    .to_string(),
+            decimals: 9,
+            timeout: Duration::from_secs(5),
+        };
+
+        let response = json!({
+            "blockPrices": [{
+                "estimatedPrices": [{
+                    "maxFeePerGas": 31.5,
+                    "maxPriorityFeePerGas": "1.2"
+                }]
+            }]
+        });
+
+        let fees = oracle.parse_fees(&response).unwrap();
+
+        assert_eq!(fees.max_fee_per_gas, U256::from(31_500_000_000u64));
+        assert_eq!(fees.max_priority_fee_per_gas, U256::from(1_200_000_000u64));
+
+        assert!(lookup(&response, "blockPrices.1").is_none());
+    }
+}
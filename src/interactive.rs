@@ -0,0 +1,69 @@
+use crate::cli::{Args, CreateConfigArgs};
+use crate::config::Config;
+use crate::serde_utils;
+use crate::types::BatchSize;
+
+mod add_group;
+mod create_config;
+pub mod prompt;
+mod utils;
+
+pub use create_config::create_config_interactive;
+
+use self::prompt::{InquirePrompt, Prompt, ScriptedPrompt};
+
+/// Creates a config by prompting for its groups, or by answering the prompts
+/// from `--answers-file`
+pub async fn run_create_config(
+    args: Args,
+    create_config_args: CreateConfigArgs,
+) -> eyre::Result<()> {
+    let mut prompt: Box<dyn Prompt> =
+        if let Some(answers_file) = &create_config_args.answers_file {
+            let answers: Vec<Option<String>> =
+                serde_utils::read_deserialize(answers_file).await?;
+
+            Box::new(ScriptedPrompt::new(answers))
+        } else {
+            Box::new(InquirePrompt {
+                assume_yes: args.yes,
+            })
+        };
+
+    if let Some(config_path) =
+        create_config_interactive(prompt.as_mut()).await?
+    {
+        println!("Wrote config to {}", config_path.display());
+    }
+
+    Ok(())
+}
+
+fn print_deployment_info(config: &Config) {
+    let mut group_ids: Vec<_> = config.groups.keys().copied().collect();
+    group_ids.sort();
+
+    println!("Groups:");
+
+    for group_id in group_ids {
+        let group = &config.groups[&group_id];
+
+        let tree_depth = group
+            .tree_depth
+            .map_or_else(|| "default".to_string(), |depth| depth.to_string());
+
+        println!(
+            "  {group_id}: tree depth {tree_depth}, insertion batch sizes [{}], deletion batch sizes [{}]",
+            join(&group.insertion_batch_sizes),
+            join(&group.deletion_batch_sizes),
+        );
+    }
+}
+
+fn join(batch_sizes: &[BatchSize]) -> String {
+    batch_sizes
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
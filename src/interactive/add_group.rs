@@ -1,27 +1,47 @@
+use super::prompt::Prompt;
 use super::utils::{
     prompt_text_handle_errors, prompt_text_skippable_handle_errors,
 };
 use crate::config::GroupConfig;
-use crate::types::{GroupId, TreeDepth};
+use crate::types::{BatchSize, GroupId, TreeDepth};
 
-pub fn add_group() -> eyre::Result<(GroupId, GroupConfig)> {
-    let group_id: GroupId = prompt_text_handle_errors("Group id:")?;
+pub fn add_group(
+    prompt: &mut dyn Prompt,
+) -> eyre::Result<(GroupId, GroupConfig)> {
+    let group_id: GroupId = prompt_text_handle_errors(prompt, "Group id:")?;
 
-    let tree_depth: TreeDepth = prompt_text_handle_errors("Tree depth:")?;
+    let tree_depth: TreeDepth =
+        prompt_text_handle_errors(prompt, "Tree depth:")?;
 
-    let mut batch_sizes = vec![];
+    let insertion_batch_sizes =
+        prompt_batch_sizes(prompt, "Enter new insertion batch size")?;
 
-    while let Some(batch_size) = prompt_text_skippable_handle_errors(
-        "Enter new batch size (Esc to finish):",
-    )? {
-        batch_sizes.push(batch_size);
-    }
+    let deletion_batch_sizes =
+        prompt_batch_sizes(prompt, "Enter new deletion batch size")?;
 
     let group = GroupConfig {
-        tree_depth,
-        batch_sizes,
+        tree_depth: Some(tree_depth),
+        insertion_batch_sizes,
+        deletion_batch_sizes,
         initial_root: None,
     };
 
     Ok((group_id, group))
 }
+
+fn prompt_batch_sizes(
+    prompt: &mut dyn Prompt,
+    message: &str,
+) -> eyre::Result<Vec<BatchSize>> {
+    let message = format!("{message} (Esc to finish):");
+
+    let mut batch_sizes = vec![];
+
+    while let Some(batch_size) =
+        prompt_text_skippable_handle_errors(prompt, &message)?
+    {
+        batch_sizes.push(batch_size);
+    }
+
+    Ok(batch_sizes)
+}
@@ -2,10 +2,10 @@ use std::collections::HashMap;
 use std::path::PathBuf;
 
 use derive_more::Display;
-use ethers::types::H256;
 
 use super::add_group::add_group;
 use super::print_deployment_info;
+use super::prompt::Prompt;
 use crate::config::{Config, MiscConfig};
 
 #[derive(Debug, Clone, Copy, Display)]
@@ -18,15 +18,19 @@ enum CreateConfigMenu {
     Proceed,
 }
 
-pub async fn create_config_interactive() -> eyre::Result<PathBuf> {
+/// Prompts for the groups of a new config and writes it
+///
+/// Returns the path of the written config, `None` if the user quit
+pub async fn create_config_interactive(
+    prompt: &mut dyn Prompt,
+) -> eyre::Result<Option<PathBuf>> {
     let config_path = loop {
-        let filename = inquire::Text::new("Config filename:").prompt()?;
+        let filename = prompt.text("Config filename:")?;
 
         let config_path = PathBuf::from(filename);
 
         if config_path.exists() {
-            let overwrite =
-                inquire::Confirm::new("Overwrite existing file?").prompt()?;
+            let overwrite = prompt.confirm("Overwrite existing file?")?;
 
             if !overwrite {
                 continue;
@@ -38,27 +42,27 @@ pub async fn create_config_interactive() -> eyre::Result<PathBuf> {
 
     let mut config = Config {
         groups: HashMap::default(),
-        misc: MiscConfig {
-            initial_leaf_value: H256::zero(),
-        },
+        misc: MiscConfig::default(),
     };
 
     loop {
         print_deployment_info(&config);
 
-        let option = inquire::Select::new(
-            "Menu (Esc to quit):",
-            vec![
-                CreateConfigMenu::AddGroup,
-                CreateConfigMenu::RemoveGroup,
-                CreateConfigMenu::Proceed,
-            ],
-        )
-        .prompt_skippable()?;
+        let menu = [
+            CreateConfigMenu::AddGroup,
+            CreateConfigMenu::RemoveGroup,
+            CreateConfigMenu::Proceed,
+        ];
+        let menu_options: Vec<_> =
+            menu.iter().map(|option| option.to_string()).collect();
+
+        let option = prompt
+            .select("Menu (Esc to quit):", &menu_options)?
+            .map(|index| menu[index]);
 
         match option {
             Some(CreateConfigMenu::AddGroup) => {
-                let (group_id, group) = add_group()?;
+                let (group_id, group) = add_group(prompt)?;
 
                 config.groups.insert(group_id, group);
             }
@@ -66,25 +70,73 @@ pub async fn create_config_interactive() -> eyre::Result<PathBuf> {
                 let existing_groups =
                     config.groups.keys().copied().collect::<Vec<_>>();
 
-                let Some(selected_groups) = inquire::MultiSelect::new(
-                        "Select groups to remove:",
-                        existing_groups,
-                    )
-                    .prompt_skippable()?
+                let group_options: Vec<_> = existing_groups
+                    .iter()
+                    .map(|group_id| group_id.to_string())
+                    .collect();
+
+                let Some(selected) = prompt
+                    .multi_select("Select groups to remove:", &group_options)?
                 else {
                     continue;
                 };
 
+                let selected_groups =
+                    selected.into_iter().map(|index| existing_groups[index]);
+
                 for group_id in selected_groups {
                     config.groups.remove(&group_id);
                 }
             }
             Some(CreateConfigMenu::Proceed) => break,
-            None => std::process::exit(0),
+            None => return Ok(None),
         }
     }
 
     crate::serde_utils::write_serialize(&config_path, config).await?;
 
-    Ok(config_path)
+    Ok(Some(config_path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::prompt::ScriptedPrompt;
+    use super::*;
+    use crate::types::{BatchSize, GroupId, TreeDepth};
+
+    #[tokio::test]
+    async fn scripted_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = dir.path().join("config.yml");
+
+        let answers = [
+            Some(config_path.to_str().unwrap()),
+            Some("Add group"),
+            Some("1"),
+            Some("16"),
+            Some("10"),
+            Some("100"),
+            None,
+            Some("10"),
+            None,
+            Some("Proceed"),
+        ];
+        let mut prompt =
+            ScriptedPrompt::new(answers.map(|a| a.map(ToString::to_string)));
+
+        let written = create_config_interactive(&mut prompt).await.unwrap();
+        assert_eq!(written, Some(config_path.clone()));
+
+        let config: Config = crate::serde_utils::read_deserialize(&config_path)
+            .await
+            .unwrap();
+        let group = &config.groups[&GroupId(1)];
+
+        assert_eq!(group.tree_depth, Some(TreeDepth(16)));
+        assert_eq!(
+            group.insertion_batch_sizes,
+            vec![BatchSize(10), BatchSize(100)]
+        );
+        assert_eq!(group.deletion_batch_sizes, vec![BatchSize(10)]);
+    }
 }
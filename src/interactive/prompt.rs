@@ -0,0 +1,176 @@
+use std::collections::VecDeque;
+
+/// Backend answering the interactive prompts
+///
+/// Selections are returned as indices into the given options, `None` means
+/// the prompt was skipped (Esc)
+pub trait Prompt {
+    fn text(&mut self, message: &str) -> eyre::Result<String>;
+
+    fn text_skippable(&mut self, message: &str)
+        -> eyre::Result<Option<String>>;
+
+    fn confirm(&mut self, message: &str) -> eyre::Result<bool>;
+
+    fn select(
+        &mut self,
+        message: &str,
+        options: &[String],
+    ) -> eyre::Result<Option<usize>>;
+
+    fn multi_select(
+        &mut self,
+        message: &str,
+        options: &[String],
+    ) -> eyre::Result<Option<Vec<usize>>>;
+}
+
+/// Prompts the user in the terminal
+#[derive(Debug, Default)]
+pub struct InquirePrompt {
+    /// Confirm without asking, i.e. `--yes`
+    pub assume_yes: bool,
+}
+
+impl Prompt for InquirePrompt {
+    fn text(&mut self, message: &str) -> eyre::Result<String> {
+        Ok(inquire::Text::new(message).prompt()?)
+    }
+
+    fn text_skippable(
+        &mut self,
+        message: &str,
+    ) -> eyre::Result<Option<String>> {
+        Ok(inquire::Text::new(message).prompt_skippable()?)
+    }
+
+    fn confirm(&mut self, message: &str) -> eyre::Result<bool> {
+        if self.assume_yes {
+            tracing::warn!("{message} - confirmed by --yes");
+            return Ok(true);
+        }
+
+        Ok(inquire::Confirm::new(message).prompt()?)
+    }
+
+    fn select(
+        &mut self,
+        message: &str,
+        options: &[String],
+    ) -> eyre::Result<Option<usize>> {
+        let selected = inquire::Select::new(message, indexed_options(options))
+            .prompt_skippable()?;
+
+        Ok(selected.map(|option| option.index))
+    }
+
+    fn multi_select(
+        &mut self,
+        message: &str,
+        options: &[String],
+    ) -> eyre::Result<Option<Vec<usize>>> {
+        let selected =
+            inquire::MultiSelect::new(message, indexed_options(options))
+                .prompt_skippable()?;
+
+        Ok(selected
+            .map(|options| options.iter().map(|option| option.index).collect()))
+    }
+}
+
+/// An option displayed by its label which remembers its position
+struct IndexedOption<'a> {
+    index: usize,
+    label: &'a str,
+}
+
+impl std::fmt::Display for IndexedOption<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(self.label)
+    }
+}
+
+fn indexed_options(options: &[String]) -> Vec<IndexedOption<'_>> {
+    options
+        .iter()
+        .enumerate()
+        .map(|(index, label)| IndexedOption { index, label })
+        .collect()
+}
+
+/// Answers prompts from a predefined sequence, e.g. in tests or for
+/// unattended config creation
+///
+/// A `None` answer skips the prompt. Selections are answered with the
+/// displayed option, multi selections with a comma separated list of them.
+#[derive(Debug, Default)]
+pub struct ScriptedPrompt {
+    answers: VecDeque<Option<String>>,
+}
+
+impl ScriptedPrompt {
+    pub fn new(answers: impl IntoIterator<Item = Option<String>>) -> Self {
+        Self {
+            answers: answers.into_iter().collect(),
+        }
+    }
+
+    fn next_answer(&mut self, message: &str) -> eyre::Result<Option<String>> {
+        self.answers
+            .pop_front()
+            .ok_or_else(|| eyre::eyre!("No scripted answer for {message:?}"))
+    }
+
+    fn find_option(options: &[String], answer: &str) -> eyre::Result<usize> {
+        options
+            .iter()
+            .position(|option| option == answer.trim())
+            .ok_or_else(|| eyre::eyre!("{answer:?} is not one of {options:?}"))
+    }
+}
+
+impl Prompt for ScriptedPrompt {
+    fn text(&mut self, message: &str) -> eyre::Result<String> {
+        self.next_answer(message)?
+            .ok_or_else(|| eyre::eyre!("{message:?} can't be skipped"))
+    }
+
+    fn text_skippable(
+        &mut self,
+        message: &str,
+    ) -> eyre::Result<Option<String>> {
+        self.next_answer(message)
+    }
+
+    fn confirm(&mut self, message: &str) -> eyre::Result<bool> {
+        let answer = self.text(message)?;
+
+        Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+    }
+
+    fn select(
+        &mut self,
+        message: &str,
+        options: &[String],
+    ) -> eyre::Result<Option<usize>> {
+        self.next_answer(message)?
+            .map(|answer| Self::find_option(options, &answer))
+            .transpose()
+    }
+
+    fn multi_select(
+        &mut self,
+        message: &str,
+        options: &[String],
+    ) -> eyre::Result<Option<Vec<usize>>> {
+        self.next_answer(message)?
+            .map(|answer| {
+                answer
+                    .split(',')
+                    .filter(|answer| !answer.trim().is_empty())
+                    .map(|answer| Self::find_option(options, answer))
+                    .collect()
+            })
+            .transpose()
+    }
+}
@@ -1,12 +1,17 @@
 use std::str::FromStr;
 
-pub fn prompt_text_handle_errors<T>(prompt: &str) -> eyre::Result<T>
+use super::prompt::Prompt;
+
+pub fn prompt_text_handle_errors<T>(
+    prompt: &mut dyn Prompt,
+    message: &str,
+) -> eyre::Result<T>
 where
     T: FromStr,
     <T as FromStr>::Err: std::error::Error,
 {
     loop {
-        let t = inquire::Text::new(prompt).prompt()?;
+        let t = prompt.text(message)?;
 
         match t.trim().parse() {
             Ok(t) => return Ok(t),
@@ -19,14 +24,15 @@ where
 }
 
 pub fn prompt_text_skippable_handle_errors<T>(
-    prompt: &str,
+    prompt: &mut dyn Prompt,
+    message: &str,
 ) -> eyre::Result<Option<T>>
 where
     T: FromStr,
     <T as FromStr>::Err: std::error::Error,
 {
     loop {
-        let t = inquire::Text::new(prompt).prompt_skippable()?;
+        let t = prompt.text_skippable(message)?;
 
         let Some(t) = t else {
             return Ok(None);
@@ -1,13 +1,30 @@
 #![allow(clippy::too_many_arguments)]
 
 use clap::Parser;
-use cli::Args;
+use cli::{Args, Command};
+use deployment::broadcast::run_broadcast;
+use deployment::check_lookup_tables::run_check_lookup_tables;
+use deployment::compute_root::run_compute_root;
+use deployment::decommission::run_decommission;
+use deployment::discover::run_discover;
+use deployment::dump_config::run_dump_config;
+use deployment::generate_verifiers::run_generate_verifiers;
+use deployment::import::run_import;
+use deployment::multi_chain::run_multi_chain;
+use deployment::pause::run_pause;
+use deployment::plan::run_plan;
+use deployment::reassemble::run_reassemble;
+use deployment::router_routes::run_router_routes;
 use deployment::run_deployment;
+use deployment::verify_addresses::run_verify_addresses;
+use deployment::verify_all::run_verify_all;
+use interactive::run_create_config;
 use tracing_subscriber::EnvFilter;
 
 pub mod common_keys;
 pub mod ethers_utils;
 pub mod forge_utils;
+pub mod gas_oracle;
 pub mod serde_utils;
 pub mod utils;
 
@@ -17,6 +34,7 @@ mod report;
 mod types;
 
 mod deployment;
+mod interactive;
 
 #[tokio::main]
 async fn main() -> eyre::Result<()> {
@@ -24,12 +42,63 @@ async fn main() -> eyre::Result<()> {
 
     dotenv::dotenv().ok();
 
-    let filter = EnvFilter::from_default_env();
+    let args = Args::parse();
+
+    let filter = if args.quiet {
+        EnvFilter::new("warn")
+    } else if args.verbose {
+        EnvFilter::new("info,bootstrapper=debug")
+    } else {
+        EnvFilter::from_default_env()
+    };
     tracing_subscriber::fmt().with_env_filter(filter).init();
 
-    let args = Args::parse();
+    let result = match args.command.clone() {
+        Some(Command::Decommission(decommission_args)) => {
+            run_decommission(args, decommission_args).await
+        }
+        Some(Command::RouterRoutes(router_routes_args)) => {
+            run_router_routes(args, router_routes_args).await
+        }
+        Some(Command::CheckLookupTables) => run_check_lookup_tables(args).await,
+        Some(Command::MultiChain(multi_chain_args)) => {
+            run_multi_chain(args, multi_chain_args).await
+        }
+        Some(Command::Broadcast(broadcast_args)) => {
+            run_broadcast(args, broadcast_args).await
+        }
+        Some(Command::Discover(discover_args)) => {
+            run_discover(args, discover_args).await
+        }
+        Some(Command::Import(import_args)) => {
+            run_import(args, import_args).await
+        }
+        Some(Command::Reassemble) => run_reassemble(args).await,
+        Some(Command::Pause) => run_pause(args, true).await,
+        Some(Command::Unpause) => run_pause(args, false).await,
+        Some(Command::Plan(plan_args)) => run_plan(args, plan_args).await,
+        Some(Command::ComputeRoot(compute_root_args)) => {
+            run_compute_root(compute_root_args)
+        }
+        Some(Command::DumpConfig(dump_config_args)) => {
+            run_dump_config(args, dump_config_args).await
+        }
+        Some(Command::GenerateVerifiers(generate_verifiers_args)) => {
+            run_generate_verifiers(args, generate_verifiers_args).await
+        }
+        Some(Command::VerifyAddresses(verify_addresses_args)) => {
+            run_verify_addresses(args, verify_addresses_args).await
+        }
+        Some(Command::VerifyAll(verify_all_args)) => {
+            run_verify_all(args, verify_all_args).await
+        }
+        Some(Command::CreateConfig(create_config_args)) => {
+            run_create_config(args, create_config_args).await
+        }
+        None => run_deployment(args).await,
+    };
 
-    match run_deployment(args).await {
+    match result {
         Ok(()) => Ok(()),
         Err(err) => {
             tracing::error!("{:?}", err);
@@ -1,19 +1,48 @@
+use std::collections::BTreeMap;
+
+use ethers::types::Address;
+use eyre::Context;
 use serde::{Deserialize, Serialize};
 
 use crate::config::Config;
-use crate::deployment::steps::identity_manager::WorldIDIdentityManagersDeployment;
-use crate::deployment::steps::lookup_tables::LookupTables;
+use crate::deployment::mtb_utils::ProverMode;
+use crate::deployment::steps::identity_manager::{
+    WorldIDIdentityManagersDeployment, WorldIdIdentityManagerDeployment,
+};
+use crate::deployment::steps::lookup_tables::{
+    GroupLookupTables, LookupTables,
+};
 use crate::deployment::steps::semaphore_verifier::SemaphoreVerifierDeployment;
+use crate::deployment::steps::timelock::TimelockDeployment;
 use crate::deployment::steps::verifiers::Verifiers;
 use crate::deployment::steps::world_id_router::WorldIdRouterDeployment;
-use crate::types::GroupId;
+use crate::types::{BatchSize, GroupId, TreeDepth};
 
 pub mod contract_deployment;
+pub mod costs;
+
+use self::costs::{DeploymentCosts, GasToken};
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Report {
     pub config: Config,
 
+    /// The address which created the deployment
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deployer: Option<Address>,
+
+    /// The CREATE2 factory forge deployed deterministic contracts through
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub create2_factory: Option<Address>,
+
+    /// Whether the last deployment run reached its target
+    #[serde(default)]
+    pub complete: bool,
+
+    /// The last step a deployment run reached
+    #[serde(default)]
+    pub last_step: String,
+
     #[serde(default)]
     #[serde(alias = "verifiers")]
     pub insertion_verifiers: Option<Verifiers>,
@@ -32,19 +61,372 @@ pub struct Report {
 
     #[serde(default)]
     pub world_id_router: Option<WorldIdRouterDeployment>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timelock: Option<TimelockDeployment>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub costs: Option<DeploymentCosts>,
+}
+
+/// The part of a [`Report`] relevant to a single group
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GroupReport {
+    pub group_id: GroupId,
+    pub tree_depth: TreeDepth,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub identity_manager: Option<WorldIdIdentityManagerDeployment>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lookup_tables: Option<GroupLookupTables>,
+
+    /// Insertion verifiers of the group keyed by batch size
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub insertion_verifiers: BTreeMap<BatchSize, Address>,
+
+    /// Deletion verifiers of the group keyed by batch size
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub deletion_verifiers: BTreeMap<BatchSize, Address>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub semaphore_verifier: Option<Address>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub world_id_router: Option<Address>,
 }
 
 impl Report {
     pub fn default_with_config(config: &Config) -> Self {
         Self {
             config: config.clone(),
+            deployer: Default::default(),
+            create2_factory: Default::default(),
+            complete: Default::default(),
+            last_step: Default::default(),
             insertion_verifiers: Default::default(),
             deletion_verifiers: Default::default(),
             lookup_tables: Default::default(),
             semaphore_verifier: Default::default(),
             identity_managers: Default::default(),
             world_id_router: Default::default(),
+            timelock: Default::default(),
+            costs: Default::default(),
+        }
+    }
+
+    /// Address of the world id router proxy
+    pub fn router_address(&self) -> Option<Address> {
+        self.world_id_router
+            .as_ref()
+            .map(|router| router.proxy_deployment.address)
+    }
+
+    /// Address of the identity manager proxy of `group_id`
+    pub fn identity_manager_for_group(
+        &self,
+        group_id: GroupId,
+    ) -> Option<Address> {
+        self.group_identity_manager(group_id)
+            .map(|identity_manager| identity_manager.proxy_deployment.address)
+    }
+
+    /// Address of the insert lookup table of `group_id`
+    pub fn insert_lookup_table_for_group(
+        &self,
+        group_id: GroupId,
+    ) -> Option<Address> {
+        self.group_lookup_tables(group_id)?
+            .insert
+            .as_ref()
+            .map(|table| table.deployment.address)
+    }
+
+    /// Address of the update lookup table of `group_id`
+    pub fn update_lookup_table_for_group(
+        &self,
+        group_id: GroupId,
+    ) -> Option<Address> {
+        self.group_lookup_tables(group_id)?
+            .update
+            .as_ref()
+            .map(|table| table.deployment.address)
+    }
+
+    /// Address of the delete lookup table of `group_id`
+    pub fn delete_lookup_table_for_group(
+        &self,
+        group_id: GroupId,
+    ) -> Option<Address> {
+        self.group_lookup_tables(group_id)?
+            .delete
+            .as_ref()
+            .map(|table| table.deployment.address)
+    }
+
+    /// Address of the `mode` verifier for `tree_depth` and `batch_size`
+    pub fn verifier_for(
+        &self,
+        mode: ProverMode,
+        tree_depth: TreeDepth,
+        batch_size: BatchSize,
+    ) -> Option<Address> {
+        self.verifiers(mode)?
+            .verifiers
+            .get(&(tree_depth, batch_size))
+            .map(|verifier| verifier.deployment.address)
+    }
+
+    /// The verifiers of `mode`
+    pub fn verifiers(&self, mode: ProverMode) -> Option<&Verifiers> {
+        match mode {
+            ProverMode::Insertion => self.insertion_verifiers.as_ref(),
+            ProverMode::Deletion => self.deletion_verifiers.as_ref(),
+        }
+    }
+
+    /// The identity manager deployment of `group_id`
+    pub fn group_identity_manager(
+        &self,
+        group_id: GroupId,
+    ) -> Option<&WorldIdIdentityManagerDeployment> {
+        self.identity_managers.as_ref()?.groups.get(&group_id)
+    }
+
+    /// The lookup tables of `group_id`
+    pub fn group_lookup_tables(
+        &self,
+        group_id: GroupId,
+    ) -> Option<&GroupLookupTables> {
+        self.lookup_tables.as_ref()?.groups.get(&group_id)
+    }
+
+    /// Human readable overview of the deployed contracts
+    ///
+    /// Costs are formatted in units of `gas_token`
+    pub fn summary(&self, gas_token: &GasToken) -> String {
+        let mut summary = String::from("Deployment summary:");
+
+        if !self.last_step.is_empty() {
+            let status = if self.complete {
+                "complete"
+            } else {
+                "incomplete"
+            };
+
+            summary += &format!("\n  last step: {} ({status})", self.last_step);
+        }
+
+        if let Some(router) = self.router_address() {
+            summary += &format!("\n  world id router: {router:?}");
+        }
+
+        if let Some(timelock) = self.timelock.as_ref() {
+            summary +=
+                &format!("\n  timelock: {:?}", timelock.deployment.address);
+        }
+
+        if let Some(identity_managers) = self.identity_managers.as_ref() {
+            let mut group_ids: Vec<_> =
+                identity_managers.groups.keys().copied().collect();
+            group_ids.sort();
+
+            for group_id in group_ids {
+                summary += &format!(
+                    "\n  group {group_id} identity manager: {:?}",
+                    identity_managers.groups[&group_id]
+                        .proxy_deployment
+                        .address
+                );
+            }
+        }
+
+        if let Some(verifier_deployment) = self
+            .semaphore_verifier
+            .as_ref()
+            .and_then(|s| s.verifier_deployment.as_ref())
+        {
+            summary += &format!(
+                "\n  semaphore verifier: {:?}",
+                verifier_deployment.address
+            );
+        }
+
+        if let Some(lookup_tables) = self.lookup_tables.as_ref() {
+            summary += &format!(
+                "\n  lookup tables: {} group(s)",
+                lookup_tables.groups.len()
+            );
+        }
+
+        for (name, verifiers) in [
+            ("insertion", &self.insertion_verifiers),
+            ("deletion", &self.deletion_verifiers),
+        ] {
+            if let Some(verifiers) = verifiers {
+                summary += &format!(
+                    "\n  {name} verifiers: {}",
+                    verifiers.verifiers.len()
+                );
+            }
+        }
+
+        if let Some(costs) = self.costs.as_ref() {
+            summary +=
+                &format!("\n  total cost: {}", gas_token.format(costs.total));
+
+            for (step, cost) in &costs.by_step {
+                summary +=
+                    &format!("\n    {step}: {}", gas_token.format(*cost));
+            }
+
+            for (group_id, cost) in &costs.by_group {
+                summary += &format!(
+                    "\n    group {group_id}: {}",
+                    gas_token.format(*cost)
+                );
+            }
+        }
+
+        summary
+    }
+
+    /// Addresses of all the deployed contracts keyed by a descriptive name
+    pub fn contract_addresses(&self) -> BTreeMap<String, Address> {
+        let mut addresses = BTreeMap::new();
+
+        for (name, verifiers) in [
+            ("insertion", &self.insertion_verifiers),
+            ("deletion", &self.deletion_verifiers),
+        ] {
+            for ((tree_depth, batch_size), verifier) in
+                verifiers.iter().flat_map(|v| &v.verifiers)
+            {
+                addresses.insert(
+                    format!("{name} verifier {tree_depth}/{batch_size}"),
+                    verifier.deployment.address,
+                );
+            }
+        }
+
+        for (group_id, tables) in
+            self.lookup_tables.iter().flat_map(|t| &t.groups)
+        {
+            for (name, table) in [
+                ("insert", &tables.insert),
+                ("update", &tables.update),
+                ("delete", &tables.delete),
+            ] {
+                if let Some(table) = table {
+                    addresses.insert(
+                        format!("group {group_id} {name} lookup table"),
+                        table.deployment.address,
+                    );
+                }
+            }
+        }
+
+        if let Some(semaphore_verifier) = self.semaphore_verifier.as_ref() {
+            if let Some(verifier_deployment) =
+                semaphore_verifier.verifier_deployment.as_ref()
+            {
+                addresses.insert(
+                    "semaphore verifier".to_string(),
+                    verifier_deployment.address,
+                );
+            }
+            addresses.insert(
+                "semaphore pairing".to_string(),
+                semaphore_verifier.pairing_deployment.address,
+            );
+        }
+
+        for (group_id, identity_manager) in
+            self.identity_managers.iter().flat_map(|i| &i.groups)
+        {
+            let deployments = [
+                ("proxy", Some(&identity_manager.proxy_deployment)),
+                ("impl v1", identity_manager.impl_v1_deployment.as_ref()),
+                ("impl v2", identity_manager.impl_v2_deployment.as_ref()),
+            ];
+
+            for (name, deployment) in deployments {
+                if let Some(deployment) = deployment {
+                    addresses.insert(
+                        format!("group {group_id} identity manager {name}"),
+                        deployment.address,
+                    );
+                }
+            }
+        }
+
+        if let Some(world_id_router) = self.world_id_router.as_ref() {
+            addresses.insert(
+                "world id router proxy".to_string(),
+                world_id_router.proxy_deployment.address,
+            );
+            addresses.insert(
+                "world id router impl v1".to_string(),
+                world_id_router.impl_v1_deployment.address,
+            );
         }
+
+        if let Some(timelock) = self.timelock.as_ref() {
+            addresses
+                .insert("timelock".to_string(), timelock.deployment.address);
+        }
+
+        addresses
+    }
+
+    /// Per group views of the report, one for each group in the config
+    pub fn group_reports(&self) -> eyre::Result<Vec<GroupReport>> {
+        let mut group_ids: Vec<_> =
+            self.config.groups.keys().copied().collect();
+        group_ids.sort();
+
+        group_ids
+            .into_iter()
+            .map(|group_id| self.group_report(group_id))
+            .collect()
+    }
+
+    fn group_report(&self, group_id: GroupId) -> eyre::Result<GroupReport> {
+        let group_config = &self.config.groups[&group_id];
+        let tree_depth = group_config
+            .tree_depth()
+            .with_context(|| format!("Group {group_id}"))?;
+
+        let verifiers = |mode, batch_sizes: &[BatchSize]| {
+            batch_sizes
+                .iter()
+                .filter_map(|&batch_size| {
+                    self.verifier_for(mode, tree_depth, batch_size)
+                        .map(|address| (batch_size, address))
+                })
+                .collect()
+        };
+
+        Ok(GroupReport {
+            group_id,
+            tree_depth,
+            identity_manager: self.group_identity_manager(group_id).cloned(),
+            lookup_tables: self.group_lookup_tables(group_id).cloned(),
+            insertion_verifiers: verifiers(
+                ProverMode::Insertion,
+                &group_config.insertion_batch_sizes,
+            ),
+            deletion_verifiers: verifiers(
+                ProverMode::Deletion,
+                &group_config.deletion_batch_sizes,
+            ),
+            semaphore_verifier: self
+                .semaphore_verifier
+                .as_ref()
+                .and_then(|s| s.verifier_deployment.as_ref())
+                .map(|deployment| deployment.address),
+            world_id_router: self.router_address(),
+        })
     }
 
     pub fn invalidate_group_id(&mut self, group_id: GroupId) {
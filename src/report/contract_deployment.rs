@@ -1,4 +1,4 @@
-use ethers::types::Address;
+use ethers::types::{Address, H256};
 use serde::{Deserialize, Serialize};
 
 use crate::forge_utils::ForgeOutput;
@@ -6,12 +6,34 @@ use crate::forge_utils::ForgeOutput;
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub struct ContractDeployment {
     pub address: Address,
+    /// Whether the deployment was only simulated and never broadcast
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub simulated: bool,
+    /// The commit of the contracts source the contract was created from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_commit: Option<String>,
+    /// Keccak hash of the runtime code, as read from the chain after the
+    /// create
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code_hash: Option<H256>,
+    /// The block the contract was created in
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub block_number: Option<u64>,
+    /// Salt of a deterministic (CREATE2) deployment
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub salt: Option<H256>,
 }
 
 impl From<ForgeOutput> for ContractDeployment {
     fn from(value: ForgeOutput) -> Self {
         Self {
             address: value.deployed_to,
+            simulated: value.simulated,
+            source_commit: value.source_commit,
+            code_hash: value.code_hash,
+            block_number: (!value.simulated && value.block_number > 0)
+                .then_some(value.block_number),
+            salt: value.salt,
         }
     }
 }
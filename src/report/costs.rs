@@ -0,0 +1,71 @@
+use std::collections::BTreeMap;
+
+use ethers::types::{H256, U256};
+use ethers::utils::format_units;
+use serde::{Deserialize, Serialize};
+
+use crate::types::GroupId;
+
+/// What the deployment transactions cost, in wei
+///
+/// Accumulated across resumed runs of the same deployment
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+pub struct DeploymentCosts {
+    pub total: U256,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub by_step: BTreeMap<String, U256>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub by_group: BTreeMap<GroupId, U256>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub transactions: Vec<TransactionCost>,
+}
+
+/// What a single transaction actually paid
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct TransactionCost {
+    pub transaction_hash: H256,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub step: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group_id: Option<GroupId>,
+    pub gas_used: U256,
+    /// The price per gas paid, as opposed to the max fee offered
+    pub effective_gas_price: U256,
+    /// `gas_used * effective_gas_price`
+    pub cost: U256,
+}
+
+impl DeploymentCosts {
+    pub fn add(&mut self, transaction: TransactionCost) {
+        let cost = transaction.cost;
+
+        self.total += cost;
+
+        if let Some(step) = transaction.step.as_ref() {
+            *self.by_step.entry(step.clone()).or_default() += cost;
+        }
+
+        if let Some(group_id) = transaction.group_id {
+            *self.by_group.entry(group_id).or_default() += cost;
+        }
+
+        self.transactions.push(transaction);
+    }
+}
+
+/// The chain's native token, used to format costs
+#[derive(Clone, Debug)]
+pub struct GasToken {
+    pub symbol: String,
+    pub decimals: u32,
+}
+
+impl GasToken {
+    pub fn format(&self, amount: U256) -> String {
+        match format_units(amount, self.decimals) {
+            Ok(amount) => format!("{amount} {}", self.symbol),
+            // Only for absurd decimals, fall back to the raw amount
+            Err(_) => format!("{amount} base units of {}", self.symbol),
+        }
+    }
+}
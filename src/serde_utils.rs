@@ -33,6 +33,65 @@ pub mod secret_key {
     }
 }
 
+/// Numbers which may be given either as JSON numbers or as decimal or `0x`
+/// prefixed hex strings, like the quantities of JSON-RPC receipts
+pub mod quantity {
+    use ethers::types::U256;
+    use serde::{Deserialize, Deserializer};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Repr {
+        Number(u64),
+        String(String),
+    }
+
+    pub fn deserialize<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+    where
+        D: Deserializer<'de>,
+        T: TryFrom<U256>,
+    {
+        let value = match Repr::deserialize(deserializer)? {
+            Repr::Number(value) => U256::from(value),
+            Repr::String(value) => match value.strip_prefix("0x") {
+                Some(hex) => U256::from_str_radix(hex, 16)
+                    .map_err(serde::de::Error::custom)?,
+                None => U256::from_dec_str(&value)
+                    .map_err(serde::de::Error::custom)?,
+            },
+        };
+
+        T::try_from(value)
+            .map_err(|_| serde::de::Error::custom("Quantity out of range"))
+    }
+}
+
+/// A leaf value as parsed by [`crate::utils::parse_leaf_value`], i.e. also
+/// accepting decimals and preset names
+pub mod leaf_value {
+    use ethers::types::H256;
+    use serde::{Deserialize, Deserializer};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Repr {
+        Number(u64),
+        String(String),
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<H256, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = match Repr::deserialize(deserializer)? {
+            Repr::Number(value) => value.to_string(),
+            Repr::String(value) => value,
+        };
+
+        crate::utils::parse_leaf_value(&value).map_err(serde::de::Error::custom)
+    }
+}
+
 pub async fn read_deserialize<T>(path: impl AsRef<Path>) -> eyre::Result<T>
 where
     T: DeserializeOwned,
@@ -50,6 +109,42 @@ where
     Ok(value)
 }
 
+/// Like [`read_deserialize`] but fails on fields which aren't part of `T`
+/// instead of silently ignoring them
+pub async fn read_deserialize_strict<T>(
+    path: impl AsRef<Path>,
+) -> eyre::Result<T>
+where
+    T: DeserializeOwned,
+{
+    let path = path.as_ref();
+
+    let content = tokio::fs::read_to_string(path)
+        .await
+        .with_context(|| format!("Reading from {}", path.display()))?;
+
+    deserialize_strict(&content)
+        .with_context(|| format!("Parsing {}", path.display()))
+}
+
+fn deserialize_strict<T>(content: &str) -> eyre::Result<T>
+where
+    T: DeserializeOwned,
+{
+    let mut unknown_fields = vec![];
+
+    let value = serde_ignored::deserialize(
+        serde_yaml::Deserializer::from_str(content),
+        |path| unknown_fields.push(path.to_string()),
+    )?;
+
+    if !unknown_fields.is_empty() {
+        eyre::bail!("Unknown fields: {}", unknown_fields.join(", "));
+    }
+
+    Ok(value)
+}
+
 pub async fn write_serialize<T>(
     path: impl AsRef<Path>,
     value: T,
@@ -68,3 +163,65 @@ where
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Debug, Deserialize)]
+    struct Outer {
+        #[serde(default)]
+        inner: Option<Inner>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Inner {
+        #[serde(alias = "old_value")]
+        value: u64,
+    }
+
+    #[test]
+    fn quantities_from_numbers_and_strings() {
+        #[derive(Deserialize)]
+        struct Receipt {
+            #[serde(deserialize_with = "quantity::deserialize")]
+            block_number: u64,
+            #[serde(deserialize_with = "quantity::deserialize")]
+            gas_used: ethers::types::U256,
+        }
+
+        let receipt: Receipt =
+            serde_json::from_str(r#"{"block_number":"0x10","gas_used":21000}"#)
+                .unwrap();
+
+        assert_eq!(receipt.block_number, 16);
+        assert_eq!(receipt.gas_used, 21000.into());
+
+        let receipt: Receipt =
+            serde_json::from_str(r#"{"block_number":16,"gas_used":"21000"}"#)
+                .unwrap();
+
+        assert_eq!(receipt.block_number, 16);
+        assert_eq!(receipt.gas_used, 21000.into());
+    }
+
+    #[test]
+    fn strict_rejects_unknown_fields() {
+        let err = deserialize_strict::<Outer>("inner:\n  valeu: 1\n  value: 2")
+            .unwrap_err();
+
+        let err = err.to_string();
+        assert!(err.starts_with("Unknown fields: inner."), "{err}");
+        assert!(err.ends_with(".valeu"), "{err}");
+    }
+
+    #[test]
+    fn strict_accepts_aliases() {
+        let outer: Outer =
+            deserialize_strict("inner:\n  old_value: 1").unwrap();
+
+        assert_eq!(outer.inner.unwrap().value, 1);
+    }
+}
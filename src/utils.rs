@@ -1,4 +1,7 @@
-use ethers::types::H256;
+use std::path::Path;
+use std::process::Stdio;
+
+use ethers::types::{H256, U256};
 use semaphore::poseidon_tree::LazyPoseidonTree;
 use semaphore::Field;
 
@@ -15,3 +18,96 @@ pub fn initial_root_hash(
 
     H256(initial_root_hash.to_be_bytes())
 }
+
+/// Order of the BN254 scalar field, every leaf has to be below it
+const SNARK_SCALAR_FIELD: &str =
+    "21888242871839275222246405745257275088548364400416034343698204186575808495617";
+
+/// Leaf values which can be referred to by name
+///
+/// `semaphore_empty` is the empty leaf of World ID's semaphore trees, i.e.
+/// the leaf of every identity slot which hasn't been inserted yet.
+pub const LEAF_VALUE_PRESETS: &[(&str, H256)] =
+    &[("semaphore_empty", H256([0; 32]))];
+
+/// Parses a leaf value given as a preset name, `0x` prefixed hex or decimal
+///
+/// Fails for values which aren't a field element, since those would silently
+/// be reduced into a different leaf.
+pub fn parse_leaf_value(value: &str) -> eyre::Result<H256> {
+    if let Some((_, preset)) =
+        LEAF_VALUE_PRESETS.iter().find(|(name, _)| *name == value)
+    {
+        return Ok(*preset);
+    }
+
+    let parsed = match value.strip_prefix("0x") {
+        Some(hex) => U256::from_str_radix(hex, 16).ok(),
+        None => U256::from_dec_str(value).ok(),
+    };
+
+    let Some(parsed) = parsed else {
+        let presets: Vec<_> =
+            LEAF_VALUE_PRESETS.iter().map(|(name, _)| *name).collect();
+
+        eyre::bail!(
+            "Invalid leaf value {value} - expected hex, decimal or one of {presets:?}"
+        );
+    };
+
+    let field = U256::from_dec_str(SNARK_SCALAR_FIELD)
+        .expect("The field order is a decimal number");
+    if parsed >= field {
+        eyre::bail!("Leaf value {value} is not below the SNARK scalar field");
+    }
+
+    let mut leaf_value = H256::zero();
+    parsed.to_big_endian(leaf_value.as_bytes_mut());
+
+    Ok(leaf_value)
+}
+
+/// Chains on which destructive operations require an explicit `--yes`
+pub const PROTECTED_CHAIN_IDS: &[u64] = &[
+    1,   // Ethereum mainnet
+    10,  // Optimism
+    137, // Polygon
+];
+
+/// Asks the operator for a y/N confirmation on stdin
+///
+/// With `assume_yes` (i.e. `--yes`) the prompt is only logged and confirmed
+pub fn confirm(prompt: &str, assume_yes: bool) -> eyre::Result<bool> {
+    use std::io::Write;
+
+    if assume_yes {
+        tracing::warn!("{prompt} - confirmed by --yes");
+        return Ok(true);
+    }
+
+    print!("{prompt} [y/N]: ");
+    std::io::stdout().flush()?;
+
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// The commit checked out in the git repository at `dir`
+pub async fn git_head(dir: impl AsRef<Path>) -> eyre::Result<String> {
+    let output = tokio::process::Command::new("git")
+        .arg("rev-parse")
+        .arg("HEAD")
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .output()
+        .await?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        eyre::bail!("git rev-parse failed: {}", stderr.trim());
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}